@@ -6,11 +6,37 @@ extern crate streamers;
 
 use criterion::Criterion;
 use unicode_segmentation::UnicodeSegmentation;
-use streamers::radix_tree::tree::RadixTree;
+use streamers::radix_tree::{ArenaRadixTree, FrozenRadixTree, RadixSet, RadixTree, RebuiltKeys};
 
+use std::alloc::{GlobalAlloc, Layout, System};
 use std::io::{self, Read};
 use std::fs;
 use std::iter;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Tracks the heap usage of the benchmarks so the memory taken by a tree can
+// be reported next to the timings
+struct CountingAllocator;
+
+static ALLOCATED_BYTES: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED_BYTES.fetch_sub(layout.size(), Ordering::Relaxed);
+        ALLOCATIONS.fetch_sub(1, Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
 
 fn read_file_into_words(filename: &str) -> io::Result<Vec<String>> {
     let mut file = fs::File::open(filename)?;
@@ -30,7 +56,7 @@ const BASE_NUM: usize = 100;
 const NUM_GROUPS: usize = 5;
 
 fn radix_build_tree(c: &mut Criterion) {
-    let input_sizes = iter::repeat(BASE_NUM).take(NUM_GROUPS).enumerate().map(|(idx, value)| (idx + 1) * value).collect::<Vec<usize>>();
+    let input_sizes = iter::repeat_n(BASE_NUM, NUM_GROUPS).enumerate().map(|(idx, value)| (idx + 1) * value).collect::<Vec<usize>>();
     c.bench_function_over_inputs("build radix tree from large file", |b, n| {
         let inferno_words = read_file_into_words("assets/inferno.txt").expect("Loading file failed");
         b.iter(|| {
@@ -43,5 +69,264 @@ fn radix_build_tree(c: &mut Criterion) {
     }, input_sizes);
 }
 
-criterion_group!(radix_tree_benches, radix_build_tree);
+fn radix_lookup_words(c: &mut Criterion) {
+    let words = read_file_into_words("assets/words.txt").expect("Loading file failed");
+    let mut rax = RadixTree::new();
+
+    for (idx, word) in words.iter().enumerate() {
+        rax.insert(word.clone(), idx);
+    }
+
+    c.bench_function("lookup every word of words.txt", move |b| {
+        b.iter(|| {
+            words
+                .iter()
+                .filter(|word| rax.get(*word).is_some())
+                .count()
+        })
+    });
+}
+
+fn radix_memory_words(c: &mut Criterion) {
+    let words = read_file_into_words("assets/words.txt").expect("Loading file failed");
+
+    let bytes_before = ALLOCATED_BYTES.load(Ordering::Relaxed);
+    let allocations_before = ALLOCATIONS.load(Ordering::Relaxed);
+    let mut rax = RadixTree::new();
+    for word in words.iter() {
+        rax.insert(word.clone(), ());
+    }
+    let bytes = ALLOCATED_BYTES.load(Ordering::Relaxed) - bytes_before;
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations_before;
+
+    println!(
+        "radix tree over words.txt: {} keys, {} bytes in {} allocations ({:.1} bytes per key)",
+        rax.len(),
+        bytes,
+        allocations,
+        bytes as f64 / rax.len() as f64
+    );
+
+    c.bench_function("build radix tree from words.txt", move |b| {
+        b.iter(|| {
+            let mut rax = RadixTree::new();
+
+            for word in words.iter() {
+                rax.insert(word.clone(), ());
+            }
+
+            rax.len()
+        })
+    });
+}
+
+fn radix_memory_rebuilt_words(c: &mut Criterion) {
+    let words = read_file_into_words("assets/words.txt").expect("Loading file failed");
+
+    let bytes_before = ALLOCATED_BYTES.load(Ordering::Relaxed);
+    let allocations_before = ALLOCATIONS.load(Ordering::Relaxed);
+    let mut rax: RadixTree<String, (), RebuiltKeys> = RadixTree::with_rebuilt_keys();
+    for word in words.iter() {
+        rax.insert(word.clone(), ());
+    }
+    let bytes = ALLOCATED_BYTES.load(Ordering::Relaxed) - bytes_before;
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations_before;
+
+    println!(
+        "radix tree with rebuilt keys over words.txt: {} keys, {} bytes in {} allocations ({:.1} bytes per key)",
+        rax.len(),
+        bytes,
+        allocations,
+        bytes as f64 / rax.len() as f64
+    );
+
+    c.bench_function("iterate rebuilt keys of words.txt", move |b| {
+        b.iter(|| rax.iter().map(|(key, _)| key.len()).sum::<usize>())
+    });
+}
+
+fn radix_memory_set_words(c: &mut Criterion) {
+    let words = read_file_into_words("assets/words.txt").expect("Loading file failed");
+
+    let bytes_before = ALLOCATED_BYTES.load(Ordering::Relaxed);
+    let allocations_before = ALLOCATIONS.load(Ordering::Relaxed);
+    let mut set = RadixSet::new();
+    for word in words.iter() {
+        set.insert(word.clone());
+    }
+    let bytes = ALLOCATED_BYTES.load(Ordering::Relaxed) - bytes_before;
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations_before;
+
+    println!(
+        "radix set over words.txt: {} keys, {} bytes in {} allocations ({:.1} bytes per key)",
+        set.len(),
+        bytes,
+        allocations,
+        bytes as f64 / set.len() as f64
+    );
+
+    c.bench_function("lookup words.txt in radix set", move |b| {
+        b.iter(|| words.iter().filter(|word| set.contains(*word)).count())
+    });
+}
+
+// Prints the shape of the tree built over each asset, next to how long it
+// takes to walk it
+fn radix_stats_assets(c: &mut Criterion) {
+    for asset in ["inferno", "don_quixote", "words"].iter() {
+        let words = read_file_into_words(&format!("assets/{}.txt", asset)).expect("Loading file failed");
+        let mut rax = RadixTree::new();
+        for word in words.iter() {
+            rax.insert(word.clone(), ());
+        }
+
+        println!("radix tree over {}.txt: {} keys\n{}", asset, rax.len(), rax.stats());
+
+        c.bench_function(&format!("collect stats of {}.txt", asset), move |b| {
+            b.iter(|| rax.stats().heap_bytes)
+        });
+    }
+}
+
+// The frozen layout of words.txt, looked up in place
+fn frozen_lookup_words(c: &mut Criterion) {
+    let words = read_file_into_words("assets/words.txt").expect("Loading file failed");
+    let rax = words
+        .iter()
+        .enumerate()
+        .map(|(idx, word)| (word.as_bytes().to_vec(), idx as u32))
+        .collect::<RadixTree<Vec<u8>, u32>>();
+    let bytes = rax.to_frozen().expect("Freezing tree failed");
+
+    println!(
+        "frozen radix tree over words.txt: {} keys, {} bytes ({:.1} bytes per key)",
+        rax.len(),
+        bytes.len(),
+        bytes.len() as f64 / rax.len() as f64
+    );
+
+    c.bench_function("lookup every word of words.txt in frozen tree", move |b| {
+        let frozen = FrozenRadixTree::<u32>::new(&bytes).expect("Opening tree failed");
+
+        b.iter(|| {
+            words
+                .iter()
+                .filter(|word| frozen.get_bytes(word.as_bytes()).is_some())
+                .count()
+        })
+    });
+}
+
+// Sizes of the static layouts of words.txt next to the boxed tree, and
+// lookups in the succinct one
+fn louds_lookup_words(c: &mut Criterion) {
+    let words = read_file_into_words("assets/words.txt").expect("Loading file failed");
+    let rax = words
+        .iter()
+        .map(|word| (word.as_bytes().to_vec(), ()))
+        .collect::<RadixTree<Vec<u8>, ()>>();
+    let louds = rax.to_louds();
+    let key_bytes = words.iter().map(|word| word.len()).sum::<usize>();
+
+    println!(
+        "words.txt: {} keys, {} key bytes, radix tree ~{} bytes, frozen {} bytes, louds {} bytes ({:.1} bytes per key)",
+        louds.len(),
+        key_bytes,
+        rax.stats().heap_bytes,
+        rax.to_frozen().expect("Freezing tree failed").len(),
+        louds.heap_bytes(),
+        louds.heap_bytes() as f64 / louds.len() as f64
+    );
+
+    c.bench_function("lookup every word of words.txt in louds trie", move |b| {
+        b.iter(|| words.iter().filter(|word| louds.contains(word.as_bytes())).count())
+    });
+}
+
+fn arena_memory_words(c: &mut Criterion) {
+    let words = read_file_into_words("assets/words.txt").expect("Loading file failed");
+
+    let bytes_before = ALLOCATED_BYTES.load(Ordering::Relaxed);
+    let allocations_before = ALLOCATIONS.load(Ordering::Relaxed);
+    let mut rax = ArenaRadixTree::new();
+    for word in words.iter() {
+        rax.insert(word.clone(), ());
+    }
+    let bytes = ALLOCATED_BYTES.load(Ordering::Relaxed) - bytes_before;
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations_before;
+
+    println!(
+        "arena radix tree over words.txt: {} keys, {} bytes in {} allocations ({:.1} bytes per key)",
+        rax.len(),
+        bytes,
+        allocations,
+        bytes as f64 / rax.len() as f64
+    );
+
+    c.bench_function("build arena radix tree from words.txt", move |b| {
+        b.iter(|| {
+            let mut rax = ArenaRadixTree::new();
+
+            for word in words.iter() {
+                rax.insert(word.clone(), ());
+            }
+
+            rax.len()
+        })
+    });
+}
+
+// Long keys with long shared prefixes, where copying prefixes on every split
+// dominates the cost of an insert
+fn radix_insert_long_keys(c: &mut Criterion) {
+    let words = read_file_into_words("assets/inferno.txt").expect("Loading file failed");
+    let keys = words
+        .iter()
+        .enumerate()
+        .map(|(idx, word)| format!("/assets/inferno/canto/{}/{}/{}", idx % 34, word, idx))
+        .collect::<Vec<String>>();
+
+    c.bench_function("insert long keys from inferno.txt", move |b| {
+        b.iter(|| {
+            let mut rax = RadixTree::new();
+
+            for key in keys.iter() {
+                rax.insert(key.clone(), ());
+            }
+
+            rax.len()
+        })
+    });
+}
+
+fn radix_insert_words(c: &mut Criterion) {
+    let words = read_file_into_words("assets/don_quixote.txt").expect("Loading file failed");
+
+    c.bench_function("insert every word of don_quixote.txt", move |b| {
+        b.iter(|| {
+            let mut rax = RadixTree::new();
+
+            for word in words.iter() {
+                rax.insert(word.clone(), ());
+            }
+
+            rax.len()
+        })
+    });
+}
+
+criterion_group!(
+    radix_tree_benches,
+    radix_build_tree,
+    radix_lookup_words,
+    radix_memory_words,
+    radix_memory_rebuilt_words,
+    radix_memory_set_words,
+    radix_stats_assets,
+    frozen_lookup_words,
+    louds_lookup_words,
+    arena_memory_words,
+    radix_insert_long_keys,
+    radix_insert_words
+);
 criterion_main!(radix_tree_benches);
@@ -1 +1,139 @@
-error_chain!{}
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::ascii;
+use core::error;
+use core::fmt;
+use core::result;
+#[cfg(feature = "std")]
+use std::io;
+
+// Everything that can go wrong outside of the trees themselves, when loading,
+// decoding or persisting them, or when adding to a stream
+#[derive(Debug)]
+pub enum Error {
+    // Keys handed to a sorted bulk load were not strictly ascending, equal
+    // keys mean a duplicate
+    InvalidKeyOrder { previous: Vec<u8>, next: Vec<u8> },
+    // Bytes read back, from a buffer, a file or an encoded key, do not decode
+    Corrupt(String),
+    // A file operation failed, `action` says which one
+    #[cfg(feature = "std")]
+    Io { action: String, error: io::Error },
+    // Adding `needed` of `what` would go over `limit`
+    CapacityExceeded {
+        what: &'static str,
+        limit: usize,
+        needed: usize,
+    },
+    // A network was given more prefix bits than its addresses have
+    InvalidPrefixLength { prefix_len: u8, max: u8 },
+    // An entry was added to a stream under an ID not above the last one, an
+    // ID being a millisecond time and a sequence number as in Redis streams
+    StreamIdViolation { last: (u64, u64), given: (u64, u64) },
+}
+
+pub type Result<T> = result::Result<T, Error>;
+
+// Returns an `Error::Corrupt` with a formatted message
+macro_rules! corrupt {
+    ($($arg:tt)*) => {
+        return Err(::error::Error::Corrupt(format!($($arg)*)))
+    };
+}
+
+#[cfg(feature = "std")]
+impl Error {
+    pub fn io<S: Into<String>>(action: S, error: io::Error) -> Self {
+        Error::Io {
+            action: action.into(),
+            error,
+        }
+    }
+}
+
+fn escaped(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .flat_map(|&byte| ascii::escape_default(byte))
+        .map(char::from)
+        .collect()
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::InvalidKeyOrder { ref previous, ref next } => write!(
+                f,
+                "key \"{}\" follows key \"{}\"",
+                escaped(next),
+                escaped(previous)
+            ),
+            Error::Corrupt(ref message) => write!(f, "{}", message),
+            #[cfg(feature = "std")]
+            Error::Io { ref action, ref error } => write!(f, "{}: {}", action, error),
+            Error::CapacityExceeded { what, limit, needed } => {
+                write!(f, "{} needs {}, over the limit of {}", what, needed, limit)
+            }
+            Error::InvalidPrefixLength { prefix_len, max } => {
+                write!(f, "prefix length {} is over the {} bits of the address", prefix_len, max)
+            }
+            Error::StreamIdViolation { last, given } => write!(
+                f,
+                "stream ID {}-{} is not above the last ID {}-{}",
+                given.0, given.1, last.0, last.1
+            ),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            #[cfg(feature = "std")]
+            Error::Io { ref error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod error_tests {
+    use super::*;
+    use std::error::Error as StdError;
+
+    #[test]
+    fn messages_and_causes() {
+        let order = Error::InvalidKeyOrder {
+            previous: b"b\n".to_vec(),
+            next: b"a".to_vec(),
+        };
+        assert_eq!(order.to_string(), "key \"a\" follows key \"b\\n\"");
+        assert!(order.source().is_none());
+
+        #[cfg(feature = "std")]
+        {
+            let io = Error::io("could not open file", io::Error::new(io::ErrorKind::NotFound, "gone"));
+            assert_eq!(io.to_string(), "could not open file: gone");
+            assert_eq!(io.source().map(|cause| cause.to_string()), Some("gone".to_string()));
+        }
+
+        let capacity = Error::CapacityExceeded {
+            what: "tree length",
+            limit: 2,
+            needed: 3,
+        };
+        assert_eq!(capacity.to_string(), "tree length needs 3, over the limit of 2");
+
+        let prefix = Error::InvalidPrefixLength { prefix_len: 33, max: 32 };
+        assert_eq!(prefix.to_string(), "prefix length 33 is over the 32 bits of the address");
+
+        let stream_id = Error::StreamIdViolation {
+            last: (1526919030474, 55),
+            given: (1526919030474, 12),
+        };
+        assert_eq!(
+            stream_id.to_string(),
+            "stream ID 1526919030474-12 is not above the last ID 1526919030474-55"
+        );
+    }
+}
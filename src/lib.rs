@@ -1,8 +1,33 @@
-#![feature(try_trait, box_syntax, box_patterns, slice_patterns, associated_type_defaults)]
+// Lints at odds with how the crate is written: patterns bind with `ref`,
+// lifetimes are spelled out, and node walks pass `&Box<RadixNode>` straight
+// from the owning slots
+#![allow(
+    clippy::needless_borrowed_reference,
+    clippy::needless_lifetimes,
+    clippy::borrowed_box,
+    clippy::new_without_default
+)]
+// Unit tests link `std` whatever the features, for the test harness and the
+// prelude
+#![cfg_attr(all(not(feature = "std"), not(test)), no_std)]
 
-extern crate bytes;
+// Modules import allocating types from `alloc`, which names `std` when it is
+// there, so the same paths work with and without `std`
+#[cfg(not(feature = "std"))]
 #[macro_use]
-extern crate error_chain;
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std as alloc;
+#[cfg(any(feature = "std", test))]
+extern crate core;
 
-pub mod radix_tree;
+extern crate allocator_api2;
+extern crate bytes;
+#[cfg(feature = "std")]
+extern crate crossbeam_epoch;
+#[cfg(feature = "serde")]
+extern crate serde;
+
+#[macro_use]
 pub mod error;
+pub mod radix_tree;
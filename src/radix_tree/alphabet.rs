@@ -0,0 +1,169 @@
+use alloc::vec::Vec;
+use core::ops::Deref;
+
+use allocator_api2::alloc::{Allocator, Global};
+
+use super::shared::SharedBytes;
+
+// How keys are cut into the symbols a tree branches on. Keys are expanded to
+// one symbol per byte before they reach the nodes, so prefixes are split,
+// matched and joined by the same code whatever the alphabet, while a smaller
+// alphabet compresses paths at a finer granularity. Symbols are taken from
+// the most significant bits of each byte first, which keeps expanded keys in
+// the order of the bytes they came from.
+pub trait KeyAlphabet {
+    // Bits in a symbol, one of 1, 2, 4 or 8
+    const SYMBOL_BITS: usize;
+
+    // The number of distinct symbols, so the most children an interior node
+    // can reach through a branching symbol
+    const SYMBOLS: usize = 1 << Self::SYMBOL_BITS;
+
+    const SYMBOLS_PER_BYTE: usize = 8 / Self::SYMBOL_BITS;
+
+    // Writes the symbols of `key` into `symbols`, which holds exactly
+    // `SYMBOLS_PER_BYTE` of them for every byte
+    fn expand_into(key: &[u8], symbols: &mut [u8]) {
+        debug_assert_eq!(symbols.len(), key.len() * Self::SYMBOLS_PER_BYTE);
+        let mask = (Self::SYMBOLS - 1) as u8;
+
+        for (&byte, byte_symbols) in key.iter().zip(symbols.chunks_mut(Self::SYMBOLS_PER_BYTE)) {
+            for (idx, symbol) in byte_symbols.iter_mut().enumerate() {
+                *symbol = (byte >> (8 - Self::SYMBOL_BITS * (idx + 1))) & mask;
+            }
+        }
+    }
+
+    // The symbols of `key`, expanded on the stack when they fit and in
+    // `alloc` when they do not, so probing a tree takes nothing from the
+    // global allocator
+    fn symbols_in<'a, A: Allocator>(key: &'a [u8], alloc: A) -> Symbols<'a, A> {
+        let len = key.len() * Self::SYMBOLS_PER_BYTE;
+
+        if len <= INLINE_SYMBOLS {
+            let mut symbols = [0; INLINE_SYMBOLS];
+            Self::expand_into(key, &mut symbols[..len]);
+
+            Symbols(SymbolsRepr::Inline(symbols, len))
+        } else {
+            Symbols(SymbolsRepr::Shared(SharedBytes::fill_in(len, alloc, |symbols| {
+                Self::expand_into(key, symbols)
+            })))
+        }
+    }
+
+    fn symbols<'a>(key: &'a [u8]) -> Symbols<'a> {
+        Self::symbols_in(key, Global)
+    }
+
+    // Appends the bytes spelled by `symbols`, which must come to a whole
+    // number of bytes
+    fn pack_into(symbols: &[u8], key: &mut Vec<u8>) {
+        debug_assert!(symbols.len().is_multiple_of(Self::SYMBOLS_PER_BYTE));
+
+        key.extend(
+            symbols
+                .chunks(Self::SYMBOLS_PER_BYTE)
+                .map(|byte_symbols| byte_symbols.iter().fold(0, |byte, &symbol| (byte << Self::SYMBOL_BITS) | symbol)),
+        );
+    }
+}
+
+// Keys expanding to at most this many symbols are expanded on the stack
+const INLINE_SYMBOLS: usize = 128;
+
+// The symbols of a key, borrowed from the key itself when the alphabet is
+// its bytes
+pub struct Symbols<'a, A: Allocator = Global>(SymbolsRepr<'a, A>);
+
+enum SymbolsRepr<'a, A: Allocator> {
+    Borrowed(&'a [u8]),
+    Inline([u8; INLINE_SYMBOLS], usize),
+    Shared(SharedBytes<A>),
+}
+
+impl<'a, A: Allocator> Deref for Symbols<'a, A> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self.0 {
+            SymbolsRepr::Borrowed(symbols) => symbols,
+            SymbolsRepr::Inline(ref symbols, len) => &symbols[..len],
+            SymbolsRepr::Shared(ref symbols) => symbols,
+        }
+    }
+}
+
+// Branches on whole bytes, the alphabet of a tree unless it names another
+#[derive(Debug)]
+pub enum ByteAlphabet {}
+
+impl KeyAlphabet for ByteAlphabet {
+    const SYMBOL_BITS: usize = 8;
+
+    fn expand_into(key: &[u8], symbols: &mut [u8]) {
+        symbols.copy_from_slice(key);
+    }
+
+    fn symbols_in<'a, A: Allocator>(key: &'a [u8], _alloc: A) -> Symbols<'a, A> {
+        Symbols(SymbolsRepr::Borrowed(key))
+    }
+
+    fn pack_into(symbols: &[u8], key: &mut Vec<u8>) {
+        key.extend_from_slice(symbols);
+    }
+}
+
+// Branches on the high and then the low half of each byte, as in a hex trie
+// like the Merkle Patricia tree of Ethereum
+#[derive(Debug)]
+pub enum NibbleAlphabet {}
+
+impl KeyAlphabet for NibbleAlphabet {
+    const SYMBOL_BITS: usize = 4;
+}
+
+// Branches on single bits, most significant first, as in a crit-bit or
+// PATRICIA tree
+#[derive(Debug)]
+pub enum BitAlphabet {}
+
+impl KeyAlphabet for BitAlphabet {
+    const SYMBOL_BITS: usize = 1;
+}
+
+#[cfg(test)]
+mod key_alphabet_tests {
+    use super::*;
+
+    #[test]
+    fn expand_and_pack() {
+        assert_eq!(&*ByteAlphabet::symbols(b"\xa5\x01"), b"\xa5\x01");
+        assert_eq!(&*NibbleAlphabet::symbols(b"\xa5\x01"), &[0xa, 0x5, 0x0, 0x1]);
+        assert_eq!(&*BitAlphabet::symbols(b"\xa5"), &[1, 0, 1, 0, 0, 1, 0, 1]);
+
+        // The last expands past the stack buffer for nibbles and bits
+        for key in [&b""[..], b"\x00", b"\xff\x7f", b"hello", &[0xa5; 40]].iter() {
+            let mut packed = Vec::new();
+            NibbleAlphabet::pack_into(&NibbleAlphabet::symbols(key), &mut packed);
+            BitAlphabet::pack_into(&BitAlphabet::symbols(key), &mut packed);
+            ByteAlphabet::pack_into(&ByteAlphabet::symbols(key), &mut packed);
+
+            assert_eq!(packed, [*key, *key, *key].concat());
+        }
+    }
+
+    #[test]
+    fn expansion_keeps_key_order() {
+        let mut keys = vec![&b"b"[..], b"", b"ab", b"\xff", b"a", b"\x00\x01", b"\x7f"];
+        let mut by_nibbles = keys.clone();
+        let mut by_bits = keys.clone();
+
+        keys.sort();
+        by_nibbles.sort_by_key(|key| NibbleAlphabet::symbols(key).to_vec());
+        by_bits.sort_by_key(|key| BitAlphabet::symbols(key).to_vec());
+
+        assert_eq!(by_nibbles, keys);
+        assert_eq!(by_bits, keys);
+    }
+}
@@ -0,0 +1,581 @@
+use alloc::vec::Vec;
+use core::mem;
+use core::borrow::Borrow;
+use core::num::NonZeroU32;
+
+use super::shared::SharedBytes;
+use super::key::{KeyMatchResult, KeyPrefix, KeyProbe, TreeKey};
+use super::entry::KeyValue;
+use super::children::NodeChildren;
+
+// Position of a node in the arena, offset by one so that an `Option<NodeId>`
+// is still only four bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct NodeId(NonZeroU32);
+
+impl NodeId {
+    fn from_index(idx: usize) -> Self {
+        assert!(idx < u32::MAX as usize, "radix tree arena is full");
+
+        NodeId(NonZeroU32::new(idx as u32 + 1).expect(concat!(file!(), ": ", line!())))
+    }
+
+    fn index(&self) -> usize {
+        (self.0.get() - 1) as usize
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ArenaNode<K: TreeKey, V> {
+    // Unlike the boxed tree the entry is stored inline, the arena slot is
+    // the only allocation a leaf needs
+    Leaf {
+        entry: KeyValue<K, V>,
+        remaining_key: KeyPrefix,
+    },
+    Interior {
+        prefix: KeyPrefix,
+        children: NodeChildren<NodeId>,
+    },
+}
+
+impl<K: TreeKey, V> ArenaNode<K, V> {
+    fn prefix_mut(&mut self) -> &mut KeyPrefix {
+        match *self {
+            ArenaNode::Leaf { ref mut remaining_key, .. } => remaining_key,
+            ArenaNode::Interior { ref mut prefix, .. } => prefix,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Slot<K: TreeKey, V> {
+    Occupied(ArenaNode<K, V>),
+    // Vacant slots form a linked list of the slots free for reuse
+    Vacant(Option<NodeId>),
+}
+
+// A radix tree which keeps all of its nodes in a single slab, addressed by
+// u32 indices instead of pointers. Nodes freed by a removal are reused by
+// later insertions, and clearing the tree releases every node at once.
+#[derive(Debug, Clone)]
+pub struct ArenaRadixTree<K: TreeKey, V> {
+    size: usize,
+    root: Option<NodeId>,
+    nodes: Vec<Slot<K, V>>,
+    free_head: Option<NodeId>,
+}
+
+impl<K: TreeKey, V> ArenaRadixTree<K, V> {
+    pub fn new() -> Self {
+        ArenaRadixTree {
+            size: 0,
+            root: None,
+            nodes: Vec::new(),
+            free_head: None,
+        }
+    }
+
+    // Reserves room for `capacity` nodes. A tree of n keys uses at most
+    // 2n - 1 nodes.
+    pub fn with_capacity(capacity: usize) -> Self {
+        ArenaRadixTree {
+            size: 0,
+            root: None,
+            nodes: Vec::with_capacity(capacity),
+            free_head: None,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.free_head = None;
+        self.root = None;
+        self.size = 0;
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: TreeKey + AsRef<[u8]>,
+    {
+        self.get(key).is_some()
+    }
+
+    pub fn get<'k, 'v, Q>(&'v self, key: &'k Q) -> Option<&'v V>
+    where
+        K: Borrow<Q>,
+        Q: TreeKey + AsRef<[u8]>,
+    {
+        self.find_leaf(KeyProbe::new(&key)).map(|leaf| match *self.node(leaf) {
+            ArenaNode::Leaf { ref entry, .. } => entry.value(),
+            _ => unreachable!(),
+        })
+    }
+
+    pub fn get_mut<'k, 'v, Q>(&'v mut self, key: &'k Q) -> Option<&'v mut V>
+    where
+        K: Borrow<Q>,
+        Q: TreeKey + AsRef<[u8]>,
+    {
+        match self.find_leaf(KeyProbe::new(&key)) {
+            Some(leaf) => match *self.node_mut(leaf) {
+                ArenaNode::Leaf { ref mut entry, .. } => Some(entry.value_mut()),
+                _ => unreachable!(),
+            },
+            None => None,
+        }
+    }
+
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let mut current = match self.root {
+            Some(root) => root,
+            None => {
+                let new_leaf = ArenaNode::Leaf {
+                    remaining_key: KeyPrefix::new(key.as_bytes()),
+                    entry: KeyValue::new(key.clone(), value),
+                };
+                self.root = Some(self.allocate(new_leaf));
+                self.size += 1;
+
+                return None;
+            }
+        };
+        let mut parent = None;
+        let shared_key = SharedBytes::copy_from_slice(key.as_bytes());
+        let mut probe = KeyProbe::shared(&shared_key);
+        let new_entry = KeyValue::new(key.clone(), value);
+
+        loop {
+            let (is_leaf, matched) = match *self.node(current) {
+                ArenaNode::Leaf { ref remaining_key, .. } => (true, remaining_key.match_with(probe)),
+                ArenaNode::Interior { ref prefix, .. } => (false, prefix.match_with(probe)),
+            };
+
+            let split_node = match matched {
+                KeyMatchResult::Complete if is_leaf => match *self.node_mut(current) {
+                    ArenaNode::Leaf { ref mut entry, .. } => {
+                        return Some(entry.swap_value(new_entry.take_value()));
+                    }
+                    _ => unreachable!(),
+                },
+                KeyMatchResult::Complete => {
+                    match self.interior(current).get_child(None).cloned() {
+                        Some(empty_child) => {
+                            parent = Some((current, None));
+                            current = empty_child;
+                            probe = KeyProbe::empty();
+
+                            continue;
+                        }
+                        None => {
+                            let new_leaf = self.allocate(ArenaNode::Leaf {
+                                remaining_key: KeyPrefix::empty(),
+                                entry: new_entry,
+                            });
+                            self.interior_mut(current).insert_child(None, new_leaf);
+
+                            break;
+                        }
+                    }
+                }
+                // The leaf key is a strict prefix of the new key, so the
+                // leaf becomes the empty child of a new interior node
+                KeyMatchResult::Partial(remaining_probe) if is_leaf => {
+                    let split_index = self.node_mut(current).prefix_mut().len();
+
+                    (split_index, remaining_probe)
+                }
+                KeyMatchResult::Partial(mut remaining_probe) => {
+                    let next_char = remaining_probe
+                        .pop()
+                        .expect(concat!(file!(), ": ", line!()));
+
+                    match self.interior(current).get_child(Some(next_char)).cloned() {
+                        Some(child) => {
+                            parent = Some((current, Some(next_char)));
+                            current = child;
+                            probe = remaining_probe;
+
+                            continue;
+                        }
+                        None => {
+                            let new_leaf = self.allocate(ArenaNode::Leaf {
+                                remaining_key: From::from(remaining_probe),
+                                entry: new_entry,
+                            });
+                            self.interior_mut(current).insert_child(Some(next_char), new_leaf);
+
+                            break;
+                        }
+                    }
+                }
+                KeyMatchResult::LongerPrefix(split_index) => (split_index, KeyProbe::empty()),
+                KeyMatchResult::Incomplete(split_index, remaining_probe) => (split_index, remaining_probe),
+            };
+
+            let (split_index, remaining_probe) = split_node;
+            let new_interior = self.split(current, split_index, remaining_probe, new_entry);
+            self.replace_child(parent, new_interior);
+
+            break;
+        }
+
+        self.size += 1;
+        None
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: TreeKey,
+    {
+        let mut current = self.root?;
+        let mut parent = None;
+        let mut grandparent = None;
+        let mut probe = KeyProbe::new(key);
+
+        loop {
+            let next = match *self.node(current) {
+                ArenaNode::Leaf { ref remaining_key, .. } => match remaining_key.match_with(probe) {
+                    KeyMatchResult::Complete => break,
+                    _ => return None,
+                },
+                ArenaNode::Interior { ref prefix, ref children } => match prefix.match_with(probe) {
+                    KeyMatchResult::Complete => children.get_child(None).map(|child| (None, *child, KeyProbe::empty())),
+                    KeyMatchResult::Partial(mut remaining_probe) => {
+                        let next_char = remaining_probe
+                            .pop()
+                            .expect(concat!(file!(), ": ", line!()));
+
+                        children
+                            .get_child(Some(next_char))
+                            .map(|child| (Some(next_char), *child, remaining_probe))
+                    }
+                    _ => None,
+                },
+            };
+
+            match next {
+                Some((branch, child, remaining_probe)) => {
+                    grandparent = parent;
+                    parent = Some((current, branch));
+                    current = child;
+                    probe = remaining_probe;
+                }
+                None => return None,
+            }
+        }
+
+        let removed_value = match self.free(current) {
+            ArenaNode::Leaf { entry, .. } => entry.take_value(),
+            _ => unreachable!(),
+        };
+        self.size -= 1;
+
+        match parent {
+            Some((parent_id, branch)) => {
+                self.interior_mut(parent_id).remove_child(branch);
+                self.compress(parent_id, grandparent);
+            }
+            None => self.root = None,
+        }
+
+        Some(removed_value)
+    }
+
+    fn node(&self, id: NodeId) -> &ArenaNode<K, V> {
+        match self.nodes[id.index()] {
+            Slot::Occupied(ref node) => node,
+            Slot::Vacant(_) => panic!("radix tree arena slot {} is vacant", id.index()),
+        }
+    }
+
+    fn node_mut(&mut self, id: NodeId) -> &mut ArenaNode<K, V> {
+        match self.nodes[id.index()] {
+            Slot::Occupied(ref mut node) => node,
+            Slot::Vacant(_) => panic!("radix tree arena slot {} is vacant", id.index()),
+        }
+    }
+
+    fn interior(&self, id: NodeId) -> &NodeChildren<NodeId> {
+        match *self.node(id) {
+            ArenaNode::Interior { ref children, .. } => children,
+            _ => panic!("radix tree arena slot {} is not an interior node", id.index()),
+        }
+    }
+
+    fn interior_mut(&mut self, id: NodeId) -> &mut NodeChildren<NodeId> {
+        match *self.node_mut(id) {
+            ArenaNode::Interior { ref mut children, .. } => children,
+            _ => panic!("radix tree arena slot {} is not an interior node", id.index()),
+        }
+    }
+
+    fn allocate(&mut self, node: ArenaNode<K, V>) -> NodeId {
+        match self.free_head {
+            Some(id) => {
+                match mem::replace(&mut self.nodes[id.index()], Slot::Occupied(node)) {
+                    Slot::Vacant(next_free) => self.free_head = next_free,
+                    Slot::Occupied(_) => panic!("radix tree arena free list is corrupt"),
+                }
+
+                id
+            }
+            None => {
+                let id = NodeId::from_index(self.nodes.len());
+                self.nodes.push(Slot::Occupied(node));
+
+                id
+            }
+        }
+    }
+
+    fn free(&mut self, id: NodeId) -> ArenaNode<K, V> {
+        let vacant = Slot::Vacant(self.free_head);
+        self.free_head = Some(id);
+
+        match mem::replace(&mut self.nodes[id.index()], vacant) {
+            Slot::Occupied(node) => node,
+            Slot::Vacant(_) => panic!("radix tree arena slot {} freed twice", id.index()),
+        }
+    }
+
+    fn find_leaf(&self, mut probe: KeyProbe) -> Option<NodeId> {
+        let mut current = self.root?;
+
+        loop {
+            match *self.node(current) {
+                ArenaNode::Leaf { ref remaining_key, .. } => {
+                    return match remaining_key.match_with(probe) {
+                        KeyMatchResult::Complete => Some(current),
+                        _ => None,
+                    };
+                }
+                ArenaNode::Interior { ref prefix, ref children } => match prefix.match_with(probe) {
+                    KeyMatchResult::Complete => return children.get_child(None).cloned(),
+                    KeyMatchResult::Partial(mut remaining_probe) => {
+                        let next_char = remaining_probe
+                            .pop()
+                            .expect(concat!(file!(), ": ", line!()));
+
+                        match children.get_child(Some(next_char)) {
+                            Some(child) => {
+                                current = *child;
+                                probe = remaining_probe;
+                            }
+                            None => return None,
+                        }
+                    }
+                    _ => return None,
+                },
+            }
+        }
+    }
+
+    // Splits the prefix of `current` at `split_index`, moving `current` below
+    // a new interior node holding the common part, then adds the new entry
+    // beside it. Returns the new interior node.
+    fn split(
+        &mut self,
+        current: NodeId,
+        split_index: usize,
+        mut remaining_probe: KeyProbe,
+        new_entry: KeyValue<K, V>,
+    ) -> NodeId {
+        let (common, mut difference) = {
+            let prefix = self.node_mut(current).prefix_mut();
+            mem::replace(prefix, KeyPrefix::empty()).split_at(split_index)
+        };
+
+        let next_char_old = difference.pop();
+        debug_assert!(next_char_old.is_some() || self.is_leaf(current));
+        *self.node_mut(current).prefix_mut() = difference;
+
+        let next_char_new = remaining_probe.pop();
+        let new_leaf = self.allocate(ArenaNode::Leaf {
+            remaining_key: From::from(remaining_probe),
+            entry: new_entry,
+        });
+
+        let mut children = NodeChildren::new();
+        children.insert_child(next_char_old, current);
+        children.insert_child(next_char_new, new_leaf);
+
+        self.allocate(ArenaNode::Interior {
+            prefix: common,
+            children,
+        })
+    }
+
+    // Folds an interior node left with a single child into that child, so
+    // that every interior node keeps at least two children
+    fn compress(&mut self, current: NodeId, parent: Option<(NodeId, Option<u8>)>) {
+        let only_child = {
+            let children = self.interior(current);
+
+            match (children.len(), children.get_child(None)) {
+                (0, Some(child)) => (None, *child),
+                (1, None) => {
+                    let (branch, child) = children
+                        .iter()
+                        .next()
+                        .expect(concat!(file!(), ": ", line!()));
+
+                    (Some(branch), *child)
+                }
+                _ => return,
+            }
+        };
+
+        let prefix = match self.free(current) {
+            ArenaNode::Interior { prefix, .. } => prefix,
+            _ => unreachable!(),
+        };
+
+        let (branch, child) = only_child;
+        let joined = prefix.join(branch, self.node_mut(child).prefix_mut());
+        *self.node_mut(child).prefix_mut() = joined;
+
+        self.replace_child(parent, child);
+    }
+
+    fn replace_child(&mut self, parent: Option<(NodeId, Option<u8>)>, child: NodeId) {
+        match parent {
+            Some((parent_id, branch)) => {
+                self.interior_mut(parent_id).insert_child(branch, child);
+            }
+            None => self.root = Some(child),
+        }
+    }
+
+    fn is_leaf(&self, id: NodeId) -> bool {
+        matches!(*self.node(id), ArenaNode::Leaf { .. })
+    }
+}
+
+#[cfg(test)]
+mod arena_tree_tests {
+    use super::*;
+
+    fn occupied_slots<K: TreeKey, V>(rax: &ArenaRadixTree<K, V>) -> usize {
+        rax.nodes
+            .iter()
+            .filter(|slot| match **slot {
+                Slot::Occupied(_) => true,
+                Slot::Vacant(_) => false,
+            })
+            .count()
+    }
+
+    #[test]
+    fn node_id_is_compact() {
+        assert_eq!(mem::size_of::<Option<NodeId>>(), 4);
+    }
+
+    #[test]
+    fn insert_retrieve_overlapping() {
+        let mut rax = ArenaRadixTree::<&str, usize>::new();
+
+        assert_eq!(rax.insert("hello", 1), None);
+        assert_eq!(rax.insert("hel", 2), None);
+        assert_eq!(rax.insert("help", 3), None);
+        assert_eq!(rax.insert("goodbye", 4), None);
+        assert_eq!(rax.insert("", 5), None);
+        assert_eq!(rax.insert("hel", 6), Some(2));
+
+        assert_eq!(rax.len(), 5);
+        assert_eq!(rax.get(&"hello"), Some(&1));
+        assert_eq!(rax.get(&"hel"), Some(&6));
+        assert_eq!(rax.get(&"help"), Some(&3));
+        assert_eq!(rax.get(&"goodbye"), Some(&4));
+        assert_eq!(rax.get(&""), Some(&5));
+        assert_eq!(rax.get(&"he"), None);
+        assert_eq!(rax.get(&"helping"), None);
+
+        *rax.get_mut(&"help").unwrap() += 10;
+        assert_eq!(rax.get(&"help"), Some(&13));
+    }
+
+    #[test]
+    fn remove_compresses_interior_nodes() {
+        let mut rax = ArenaRadixTree::<&str, usize>::new();
+
+        rax.insert("romane", 1);
+        rax.insert("romanus", 2);
+        rax.insert("romulus", 3);
+        rax.insert("rom", 4);
+        assert_eq!(occupied_slots(&rax), 6);
+
+        assert_eq!(rax.remove(&"romanus"), Some(2));
+        assert_eq!(rax.remove(&"romanus"), None);
+        assert_eq!(occupied_slots(&rax), 4);
+
+        assert_eq!(rax.remove(&"rom"), Some(4));
+        assert_eq!(occupied_slots(&rax), 3);
+        assert_eq!(rax.get(&"romane"), Some(&1));
+        assert_eq!(rax.get(&"romulus"), Some(&3));
+
+        assert_eq!(rax.remove(&"romulus"), Some(3));
+        assert_eq!(occupied_slots(&rax), 1);
+        assert_eq!(rax.get(&"romane"), Some(&1));
+
+        assert_eq!(rax.remove(&"romane"), Some(1));
+        assert!(rax.is_empty());
+        assert_eq!(occupied_slots(&rax), 0);
+    }
+
+    #[test]
+    fn freed_slots_are_reused() {
+        let mut rax = ArenaRadixTree::<String, usize>::new();
+
+        for idx in 0..100 {
+            rax.insert(format!("key{}", idx), idx);
+        }
+        let slots = rax.nodes.len();
+
+        for idx in 0..50 {
+            assert_eq!(rax.remove(&format!("key{}", idx)), Some(idx));
+        }
+        assert!(rax.free_head.is_some());
+
+        for idx in 0..50 {
+            rax.insert(format!("key{}", idx), idx + 100);
+        }
+
+        assert_eq!(rax.len(), 100);
+        assert_eq!(rax.nodes.len(), slots);
+        for idx in 0..100 {
+            let expected = if idx < 50 { idx + 100 } else { idx };
+            assert_eq!(rax.get(&format!("key{}", idx)), Some(&expected));
+        }
+    }
+
+    #[test]
+    fn clear_drops_arena() {
+        let mut rax = ArenaRadixTree::<String, usize>::with_capacity(16);
+
+        for idx in 0..10 {
+            rax.insert(format!("key{}", idx), idx);
+        }
+        rax.remove(&String::from("key3"));
+        rax.clear();
+
+        assert!(rax.is_empty());
+        assert_eq!(rax.nodes.len(), 0);
+        assert_eq!(rax.free_head, None);
+        assert_eq!(rax.get(&String::from("key4")), None);
+
+        rax.insert(String::from("key4"), 4);
+        assert_eq!(rax.len(), 1);
+        assert_eq!(rax.get(&String::from("key4")), Some(&4));
+    }
+}
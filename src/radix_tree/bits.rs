@@ -0,0 +1,224 @@
+use alloc::vec::Vec;
+use core::mem;
+
+// Bits per rank block. Every block keeps the count of ones before it, which
+// costs 32 bits for every 512.
+const BLOCK_WORDS: usize = 8;
+const BLOCK_BITS: usize = BLOCK_WORDS * 64;
+
+// Appends bits one at a time, then freezes them into a `BitVector`
+#[derive(Debug, Default)]
+pub struct BitBuilder {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl BitBuilder {
+    pub fn new() -> Self {
+        BitBuilder::default()
+    }
+
+    pub fn push(&mut self, bit: bool) {
+        if self.len.is_multiple_of(64) {
+            self.words.push(0);
+        }
+
+        if bit {
+            let last = self.words.len() - 1;
+            self.words[last] |= 1 << (self.len % 64);
+        }
+
+        self.len += 1;
+    }
+
+    pub fn finish(mut self) -> BitVector {
+        self.words.shrink_to_fit();
+
+        let mut block_ranks = Vec::with_capacity(self.words.len() / BLOCK_WORDS + 1);
+        let mut ones = 0;
+        for block in self.words.chunks(BLOCK_WORDS) {
+            block_ranks.push(ones as u32);
+            ones += block.iter().map(|word| word.count_ones() as usize).sum::<usize>();
+        }
+
+        BitVector {
+            words: self.words,
+            len: self.len,
+            ones,
+            block_ranks,
+        }
+    }
+}
+
+// An immutable bit vector answering rank in constant time and select with a
+// binary search over the rank blocks
+#[derive(Debug, Clone)]
+pub struct BitVector {
+    words: Vec<u64>,
+    len: usize,
+    ones: usize,
+    block_ranks: Vec<u32>,
+}
+
+// Position of the `k`th set bit of `word`, counting from zero
+fn select_in_word(mut word: u64, k: usize) -> usize {
+    for _ in 0..k {
+        word &= word - 1;
+    }
+
+    word.trailing_zeros() as usize
+}
+
+impl BitVector {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn count_ones(&self) -> usize {
+        self.ones
+    }
+
+    pub fn get(&self, pos: usize) -> bool {
+        assert!(pos < self.len, "bit {} out of {}", pos, self.len);
+
+        self.words[pos / 64] & (1 << (pos % 64)) != 0
+    }
+
+    // Number of set bits before `pos`
+    pub fn rank1(&self, pos: usize) -> usize {
+        assert!(pos <= self.len, "rank {} out of {}", pos, self.len);
+
+        let word = pos / 64;
+        // Only a length that fills its last word reaches past the words
+        if word == self.words.len() {
+            return self.ones;
+        }
+
+        let block_start = word - word % BLOCK_WORDS;
+        self.block_ranks[word / BLOCK_WORDS] as usize
+            + self.words[block_start..word]
+                .iter()
+                .map(|word| word.count_ones() as usize)
+                .sum::<usize>()
+            + (self.words[word] & ((1 << (pos % 64)) - 1)).count_ones() as usize
+    }
+
+    pub fn rank0(&self, pos: usize) -> usize {
+        pos - self.rank1(pos)
+    }
+
+    // Position of the `k`th set bit, counting from zero
+    pub fn select1(&self, k: usize) -> Option<usize> {
+        self.select(k, self.ones, |block| self.block_ranks[block] as usize, |word| word)
+    }
+
+    // Position of the `k`th clear bit, counting from zero
+    pub fn select0(&self, k: usize) -> Option<usize> {
+        self.select(
+            k,
+            self.len() - self.ones,
+            |block| block * BLOCK_BITS - self.block_ranks[block] as usize,
+            |word| !word,
+        )
+    }
+
+    fn select<R, W>(&self, k: usize, total: usize, rank_before: R, word_bits: W) -> Option<usize>
+    where
+        R: Fn(usize) -> usize,
+        W: Fn(u64) -> u64,
+    {
+        if k >= total {
+            return None;
+        }
+
+        // The last block starting with at most `k` bits before it
+        let (mut low, mut high) = (0, self.block_ranks.len());
+        while high - low > 1 {
+            let middle = (low + high) / 2;
+
+            if rank_before(middle) <= k {
+                low = middle;
+            } else {
+                high = middle;
+            }
+        }
+
+        let mut remaining = k - rank_before(low);
+        for (idx, &word) in self.words[low * BLOCK_WORDS..].iter().enumerate() {
+            let word = word_bits(word);
+            let count = word.count_ones() as usize;
+
+            if remaining < count {
+                return Some((low * BLOCK_WORDS + idx) * 64 + select_in_word(word, remaining));
+            }
+
+            remaining -= count;
+        }
+
+        unreachable!("{}: {}", file!(), line!())
+    }
+
+    pub fn heap_bytes(&self) -> usize {
+        self.words.capacity() * mem::size_of::<u64>() + self.block_ranks.capacity() * mem::size_of::<u32>()
+    }
+}
+
+#[cfg(test)]
+mod bit_vector_tests {
+    use super::*;
+
+    fn build(bits: &[bool]) -> BitVector {
+        let mut builder = BitBuilder::new();
+        for &bit in bits {
+            builder.push(bit);
+        }
+
+        builder.finish()
+    }
+
+    #[test]
+    fn rank_and_select_match_naive() {
+        // A xorshift sequence, sparse and dense stretches across many blocks
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let bits = (0..5000)
+            .map(|idx| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+
+                if idx < 2000 { state.is_multiple_of(7) } else { !state.is_multiple_of(3) }
+            })
+            .collect::<Vec<_>>();
+        let vector = build(&bits);
+
+        let ones = bits.iter().enumerate().filter(|&(_, &bit)| bit).map(|(pos, _)| pos).collect::<Vec<_>>();
+        let zeros = bits.iter().enumerate().filter(|&(_, &bit)| !bit).map(|(pos, _)| pos).collect::<Vec<_>>();
+
+        assert_eq!(vector.len(), bits.len());
+        assert_eq!(vector.count_ones(), ones.len());
+        for pos in 0..bits.len() + 1 {
+            assert_eq!(vector.rank1(pos), bits[..pos].iter().filter(|&&bit| bit).count());
+        }
+        for (k, &pos) in ones.iter().enumerate() {
+            assert_eq!(vector.select1(k), Some(pos));
+            assert!(vector.get(pos));
+        }
+        for (k, &pos) in zeros.iter().enumerate() {
+            assert_eq!(vector.select0(k), Some(pos));
+        }
+        assert_eq!(vector.select1(ones.len()), None);
+        assert_eq!(vector.select0(zeros.len()), None);
+    }
+
+    #[test]
+    fn empty_and_block_edges() {
+        let empty = build(&[]);
+        assert_eq!(empty.rank1(0), 0);
+        assert_eq!(empty.select0(0), None);
+
+        let full = build(&[true; BLOCK_BITS]);
+        assert_eq!(full.rank1(BLOCK_BITS), BLOCK_BITS);
+        assert_eq!(full.select1(BLOCK_BITS - 1), Some(BLOCK_BITS - 1));
+        assert_eq!(full.rank0(BLOCK_BITS), 0);
+    }
+}
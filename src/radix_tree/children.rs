@@ -0,0 +1,715 @@
+use core::mem;
+use core::fmt;
+use core::iter;
+use core::marker::PhantomData;
+
+use allocator_api2::alloc::{Allocator, Global};
+use allocator_api2::boxed::Box;
+use allocator_api2::vec::Vec;
+
+use super::validate::ViolationKind;
+
+// Up to `capacity` children kept in two parallel arrays sorted by the
+// branching byte. Used for the two smallest layouts.
+#[derive(Debug, Clone)]
+struct SortedChildren<C, Keys, Children> {
+    len: u8,
+    keys: Keys,
+    children: Children,
+    child_type: PhantomData<C>,
+}
+
+type Node4Children<C> = SortedChildren<C, [u8; 4], [Option<C>; 4]>;
+type Node16Children<C> = SortedChildren<C, [u8; 16], [Option<C>; 16]>;
+
+impl<C, Keys, Children> SortedChildren<C, Keys, Children>
+where
+    Keys: AsRef<[u8]> + AsMut<[u8]> + Default,
+    Children: AsRef<[Option<C>]> + AsMut<[Option<C>]> + Default,
+{
+    fn new() -> Self {
+        SortedChildren {
+            len: 0,
+            keys: Default::default(),
+            children: Default::default(),
+            child_type: PhantomData,
+        }
+    }
+
+    fn capacity(&self) -> usize {
+        self.keys.as_ref().len()
+    }
+
+    fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    fn is_full(&self) -> bool {
+        self.len() == self.capacity()
+    }
+
+    // At most 16 keys, so a linear scan beats a binary search
+    fn search(&self, prefix: u8) -> Result<usize, usize> {
+        let keys = &self.keys.as_ref()[..self.len()];
+
+        match keys.iter().position(|&key| key >= prefix) {
+            Some(idx) if keys[idx] == prefix => Ok(idx),
+            Some(idx) => Err(idx),
+            None => Err(keys.len()),
+        }
+    }
+
+    fn get(&self, idx: usize) -> Option<(u8, &C)> {
+        if idx < self.len() {
+            let child = self.children.as_ref()[idx]
+                .as_ref()
+                .expect(concat!(file!(), ": ", line!()));
+
+            Some((self.keys.as_ref()[idx], child))
+        } else {
+            None
+        }
+    }
+
+    fn get_mut(&mut self, idx: usize) -> &mut C {
+        self.children.as_mut()[idx]
+            .as_mut()
+            .expect(concat!(file!(), ": ", line!()))
+    }
+
+    // The caller must check that the layout is not full
+    fn insert(&mut self, idx: usize, prefix: u8, child: C) {
+        debug_assert!(!self.is_full());
+        let len = self.len();
+
+        self.keys.as_mut()[len] = prefix;
+        self.keys.as_mut()[idx..len + 1].rotate_right(1);
+        self.children.as_mut()[len] = Some(child);
+        self.children.as_mut()[idx..len + 1].rotate_right(1);
+
+        self.len += 1;
+    }
+
+    fn remove(&mut self, idx: usize) -> C {
+        let len = self.len();
+        let child = self.children.as_mut()[idx]
+            .take()
+            .expect(concat!(file!(), ": ", line!()));
+
+        self.keys.as_mut()[idx..len].rotate_left(1);
+        self.children.as_mut()[idx..len].rotate_left(1);
+
+        self.len -= 1;
+        child
+    }
+
+    fn occupied(&self) -> usize {
+        self.children.as_ref().iter().filter(|child| child.is_some()).count()
+    }
+
+    fn drain_into<F>(&mut self, mut insert: F)
+    where
+        F: FnMut(u8, C),
+    {
+        for idx in 0..self.len() {
+            let child = self.children.as_mut()[idx]
+                .take()
+                .expect(concat!(file!(), ": ", line!()));
+
+            insert(self.keys.as_ref()[idx], child);
+        }
+
+        self.len = 0;
+    }
+}
+
+// Up to 48 children in no particular order, with a 256 entry index from the
+// branching byte to one plus the position of the child.
+#[derive(Debug, Clone)]
+struct Node48Children<C, A: Allocator> {
+    index: [u8; 256],
+    children: Vec<C, A>,
+}
+
+impl<C, A: Allocator> Node48Children<C, A> {
+    fn new_in(alloc: A) -> Self {
+        Node48Children {
+            index: [0; 256],
+            children: Vec::with_capacity_in(48, alloc),
+        }
+    }
+
+    fn is_full(&self) -> bool {
+        self.children.len() == 48
+    }
+
+    fn position(&self, prefix: u8) -> Option<usize> {
+        match self.index[prefix as usize] {
+            0 => None,
+            slot => Some(slot as usize - 1),
+        }
+    }
+
+    fn insert(&mut self, prefix: u8, child: C) {
+        debug_assert!(!self.is_full());
+
+        self.children.push(child);
+        self.index[prefix as usize] = self.children.len() as u8;
+    }
+
+    fn remove(&mut self, prefix: u8) -> Option<C> {
+        self.position(prefix).map(|position| {
+            let last_slot = self.children.len() as u8;
+            let child = self.children.swap_remove(position);
+            self.index[prefix as usize] = 0;
+
+            // The last child was moved into the freed position
+            if position < self.children.len() {
+                let moved_slot = self.index
+                    .iter_mut()
+                    .find(|slot| **slot == last_slot)
+                    .expect(concat!(file!(), ": ", line!()));
+                *moved_slot = position as u8 + 1;
+            }
+
+            child
+        })
+    }
+}
+
+// One slot for every possible branching byte
+#[derive(Debug, Clone)]
+struct Node256Children<C, A: Allocator> {
+    len: usize,
+    children: Vec<Option<C>, A>,
+}
+
+impl<C, A: Allocator> Node256Children<C, A> {
+    fn new_in(alloc: A) -> Self {
+        let mut children = Vec::with_capacity_in(256, alloc);
+        children.resize_with(256, || None);
+
+        Node256Children { len: 0, children }
+    }
+}
+
+// The layout is picked by the number of children and changes as children are
+// added and removed, as in "The Adaptive Radix Tree: ARTful Indexing for
+// Main-Memory Databases" (Leis et al, 2013). Layouts shrink at a lower count
+// than they grow at so that a node on the boundary does not keep converting.
+// A tree over a smaller alphabet only ever branches on its first few
+// values, so a node of a nibble tree stops at `Node16` and one of a bit tree
+// at `Node4`.
+#[derive(Debug, Clone)]
+enum ChildLayout<C, A: Allocator> {
+    Node4(Box<Node4Children<C>, A>),
+    Node16(Box<Node16Children<C>, A>),
+    Node48(Box<Node48Children<C, A>, A>),
+    Node256(Box<Node256Children<C, A>, A>),
+}
+
+const NODE16_SHRINK_LEN: usize = 3;
+const NODE48_SHRINK_LEN: usize = 12;
+const NODE256_SHRINK_LEN: usize = 36;
+
+// Every layout is allocated from `A`, as is the array of a `Node48` or
+// `Node256` layout
+#[derive(Clone)]
+pub struct NodeChildren<C, A: Allocator = Global> {
+    layout: ChildLayout<C, A>,
+    empty_child: Option<C>,
+}
+
+impl<C> NodeChildren<C> {
+    pub fn new() -> Self {
+        NodeChildren::new_in(Global)
+    }
+}
+
+impl<C, A: Allocator + Clone> NodeChildren<C, A> {
+    pub fn new_in(alloc: A) -> Self {
+        NodeChildren {
+            layout: ChildLayout::Node4(Box::new_in(SortedChildren::new(), alloc)),
+            empty_child: None,
+        }
+    }
+
+    // The allocator the layout came from, which the next layout comes from
+    // too
+    pub fn allocator(&self) -> &A {
+        match self.layout {
+            ChildLayout::Node4(ref node) => Box::allocator(node),
+            ChildLayout::Node16(ref node) => Box::allocator(node),
+            ChildLayout::Node48(ref node) => Box::allocator(node),
+            ChildLayout::Node256(ref node) => Box::allocator(node),
+        }
+    }
+
+    // Number of children reached through a branching byte, not counting
+    // the empty child
+    pub fn len(&self) -> usize {
+        match self.layout {
+            ChildLayout::Node4(ref node) => node.len(),
+            ChildLayout::Node16(ref node) => node.len(),
+            ChildLayout::Node48(ref node) => node.children.len(),
+            ChildLayout::Node256(ref node) => node.len,
+        }
+    }
+
+    pub fn contains_child(&self, prefix: u8) -> bool {
+        self.get_child(Some(prefix)).is_some()
+    }
+
+    pub fn contains_empty(&self) -> bool {
+        self.empty_child.is_some()
+    }
+
+    pub fn get_child(&self, possible_prefix: Option<u8>) -> Option<&C> {
+        if let Some(prefix) = possible_prefix {
+            match self.layout {
+                ChildLayout::Node4(ref node) => node.search(prefix)
+                    .ok()
+                    .and_then(|idx| node.get(idx))
+                    .map(|(_, child)| child),
+                ChildLayout::Node16(ref node) => node.search(prefix)
+                    .ok()
+                    .and_then(|idx| node.get(idx))
+                    .map(|(_, child)| child),
+                ChildLayout::Node48(ref node) => node.position(prefix)
+                    .map(|position| &node.children[position]),
+                ChildLayout::Node256(ref node) => node.children[prefix as usize].as_ref(),
+            }
+        } else {
+            self.empty_child.as_ref()
+        }
+    }
+
+    pub fn get_child_mut(
+        &mut self,
+        possible_prefix: Option<u8>,
+    ) -> Option<&mut C> {
+        if let Some(prefix) = possible_prefix {
+            match self.layout {
+                ChildLayout::Node4(ref mut node) => match node.search(prefix) {
+                    Ok(idx) => Some(node.get_mut(idx)),
+                    Err(_) => None,
+                },
+                ChildLayout::Node16(ref mut node) => match node.search(prefix) {
+                    Ok(idx) => Some(node.get_mut(idx)),
+                    Err(_) => None,
+                },
+                ChildLayout::Node48(ref mut node) => match node.position(prefix) {
+                    Some(position) => Some(&mut node.children[position]),
+                    None => None,
+                },
+                ChildLayout::Node256(ref mut node) => node.children[prefix as usize].as_mut(),
+            }
+        } else {
+            self.empty_child.as_mut()
+        }
+    }
+
+    pub fn insert_child(
+        &mut self,
+        possible_prefix: Option<u8>,
+        new_child: C,
+    ) -> Option<C> {
+        if let Some(prefix) = possible_prefix {
+            if let Some(old_child) = self.get_child_mut(Some(prefix)) {
+                return Some(mem::replace(old_child, new_child));
+            }
+
+            self.grow_if_full();
+
+            match self.layout {
+                ChildLayout::Node4(ref mut node) => {
+                    let insert_index = node.search(prefix).unwrap_err();
+                    node.insert(insert_index, prefix, new_child);
+                }
+                ChildLayout::Node16(ref mut node) => {
+                    let insert_index = node.search(prefix).unwrap_err();
+                    node.insert(insert_index, prefix, new_child);
+                }
+                ChildLayout::Node48(ref mut node) => node.insert(prefix, new_child),
+                ChildLayout::Node256(ref mut node) => {
+                    node.children[prefix as usize] = Some(new_child);
+                    node.len += 1;
+                }
+            }
+
+            None
+        } else {
+            self.empty_child.replace(new_child)
+        }
+    }
+
+    pub fn remove_child(&mut self, possible_prefix: Option<u8>) -> Option<C> {
+        if let Some(prefix) = possible_prefix {
+            let removed = match self.layout {
+                ChildLayout::Node4(ref mut node) => match node.search(prefix) {
+                    Ok(idx) => Some(node.remove(idx)),
+                    Err(_) => None,
+                },
+                ChildLayout::Node16(ref mut node) => match node.search(prefix) {
+                    Ok(idx) => Some(node.remove(idx)),
+                    Err(_) => None,
+                },
+                ChildLayout::Node48(ref mut node) => node.remove(prefix),
+                ChildLayout::Node256(ref mut node) => {
+                    let removed = node.children[prefix as usize].take();
+                    if removed.is_some() {
+                        node.len -= 1;
+                    }
+
+                    removed
+                }
+            };
+
+            if removed.is_some() {
+                self.shrink_if_sparse();
+            }
+
+            removed
+        } else {
+            self.empty_child.take()
+        }
+    }
+
+    fn grow_if_full(&mut self) {
+        let alloc = self.allocator().clone();
+        let grown = match self.layout {
+            ChildLayout::Node4(ref mut node) if node.is_full() => {
+                let mut grown = Box::new_in(SortedChildren::new(), alloc);
+                node.drain_into(|prefix, child| {
+                    let insert_index = grown.len();
+                    grown.insert(insert_index, prefix, child)
+                });
+
+                ChildLayout::Node16(grown)
+            }
+            ChildLayout::Node16(ref mut node) if node.is_full() => {
+                let mut grown = Box::new_in(Node48Children::new_in(alloc.clone()), alloc);
+                node.drain_into(|prefix, child| grown.insert(prefix, child));
+
+                ChildLayout::Node48(grown)
+            }
+            ChildLayout::Node48(ref mut node) if node.is_full() => {
+                let mut grown = Box::new_in(Node256Children::new_in(alloc.clone()), alloc);
+                for prefix in 0..256 {
+                    if let Some(child) = node.remove(prefix as u8) {
+                        grown.children[prefix as usize] = Some(child);
+                        grown.len += 1;
+                    }
+                }
+
+                ChildLayout::Node256(grown)
+            }
+            _ => return,
+        };
+
+        self.layout = grown;
+    }
+
+    fn shrink_if_sparse(&mut self) {
+        let alloc = self.allocator().clone();
+        let shrunk = match self.layout {
+            ChildLayout::Node16(ref mut node) if node.len() <= NODE16_SHRINK_LEN => {
+                let mut shrunk = Box::new_in(SortedChildren::new(), alloc);
+                node.drain_into(|prefix, child| {
+                    let insert_index = shrunk.len();
+                    shrunk.insert(insert_index, prefix, child)
+                });
+
+                ChildLayout::Node4(shrunk)
+            }
+            ChildLayout::Node48(ref mut node) if node.children.len() <= NODE48_SHRINK_LEN => {
+                let mut shrunk = Box::new_in(SortedChildren::new(), alloc);
+                for prefix in 0..256 {
+                    if let Some(child) = node.remove(prefix as u8) {
+                        let insert_index = shrunk.len();
+                        shrunk.insert(insert_index, prefix as u8, child);
+                    }
+                }
+
+                ChildLayout::Node16(shrunk)
+            }
+            ChildLayout::Node256(ref mut node) if node.len <= NODE256_SHRINK_LEN => {
+                let mut shrunk = Box::new_in(Node48Children::new_in(alloc.clone()), alloc);
+                for (prefix, slot) in node.children.iter_mut().enumerate() {
+                    if let Some(child) = slot.take() {
+                        shrunk.insert(prefix as u8, child);
+                    }
+                }
+
+                ChildLayout::Node48(shrunk)
+            }
+            _ => return,
+        };
+
+        self.layout = shrunk;
+    }
+
+    // Iterates over the children reached through a branching byte, in
+    // increasing order of that byte
+    pub fn iter<'a>(&'a self) -> ChildrenIter<'a, C, A> {
+        ChildrenIter {
+            layout: &self.layout,
+            position: 0,
+        }
+    }
+
+    // Bytes allocated for the layout itself, not counting what the children
+    // own
+    pub fn heap_bytes(&self) -> usize {
+        match self.layout {
+            ChildLayout::Node4(_) => mem::size_of::<Node4Children<C>>(),
+            ChildLayout::Node16(_) => mem::size_of::<Node16Children<C>>(),
+            ChildLayout::Node48(ref node) => {
+                mem::size_of::<Node48Children<C, A>>() + node.children.capacity() * mem::size_of::<C>()
+            }
+            ChildLayout::Node256(ref node) => {
+                mem::size_of::<Node256Children<C, A>>() + node.children.capacity() * mem::size_of::<Option<C>>()
+            }
+        }
+    }
+
+    // Checks that the layout agrees with itself and that the children are
+    // kept in ascending order of their branching bytes
+    pub fn check_layout(&self) -> Result<(), ViolationKind> {
+        let (expected, found) = match self.layout {
+            ChildLayout::Node4(ref node) => (node.len(), node.occupied()),
+            ChildLayout::Node16(ref node) => (node.len(), node.occupied()),
+            ChildLayout::Node48(ref node) => {
+                let mut referenced = vec![false; node.children.len()];
+
+                for &slot in node.index.iter().filter(|&&slot| slot != 0) {
+                    match referenced.get_mut(slot as usize - 1) {
+                        Some(seen) if !*seen => *seen = true,
+                        _ => {
+                            return Err(ViolationKind::InconsistentChildren {
+                                expected: node.children.len(),
+                                found: node.index.iter().filter(|&&slot| slot != 0).count(),
+                            })
+                        }
+                    }
+                }
+
+                (node.children.len(), referenced.iter().filter(|&&seen| seen).count())
+            }
+            ChildLayout::Node256(ref node) => (node.len, node.children.iter().filter(|child| child.is_some()).count()),
+        };
+
+        if expected != found {
+            return Err(ViolationKind::InconsistentChildren { expected, found });
+        }
+
+        let branches = self.iter().map(|(branch, _)| branch).collect::<Vec<_>>();
+        match branches.windows(2).find(|pair| pair[0] >= pair[1]) {
+            Some(pair) => Err(ViolationKind::UnsortedChildren {
+                previous: pair[0],
+                next: pair[1],
+            }),
+            None => Ok(()),
+        }
+    }
+
+    // Takes every child, including the empty child, in no particular order
+    pub fn into_children(self) -> Vec<C, A> {
+        let mut children = Vec::with_capacity_in(self.len() + 1, self.allocator().clone());
+        children.extend(self.empty_child);
+
+        match self.layout {
+            ChildLayout::Node4(mut node) => node.drain_into(|_, child| children.push(child)),
+            ChildLayout::Node16(mut node) => node.drain_into(|_, child| children.push(child)),
+            ChildLayout::Node48(node) => children.extend(Box::into_inner(node).children),
+            ChildLayout::Node256(node) => children.extend(Box::into_inner(node).children.into_iter().flatten()),
+        }
+
+        children
+    }
+}
+
+impl<C: PartialEq, A: Allocator + Clone> PartialEq for NodeChildren<C, A> {
+    fn eq(&self, other: &Self) -> bool {
+        self.empty_child == other.empty_child && self.iter().eq(other.iter())
+    }
+}
+
+impl<C: Eq, A: Allocator + Clone> Eq for NodeChildren<C, A> {}
+
+impl<C: fmt::Debug, A: Allocator + Clone> fmt::Debug for NodeChildren<C, A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("NodeChildren")
+            .field("children", &self.iter().collect::<Vec<_>>())
+            .field("empty_child", &self.empty_child)
+            .finish()
+    }
+}
+
+pub struct ChildrenIter<'a, C: 'a, A: 'a + Allocator> {
+    layout: &'a ChildLayout<C, A>,
+    position: usize,
+}
+
+impl<'a, C: 'a, A: 'a + Allocator> iter::Iterator for ChildrenIter<'a, C, A> {
+    type Item = (u8, &'a C);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match *self.layout {
+            ChildLayout::Node4(ref node) => {
+                self.position += 1;
+                node.get(self.position - 1)
+            }
+            ChildLayout::Node16(ref node) => {
+                self.position += 1;
+                node.get(self.position - 1)
+            }
+            ChildLayout::Node48(ref node) => {
+                while self.position < 256 {
+                    let prefix = self.position as u8;
+                    self.position += 1;
+
+                    if let Some(position) = node.position(prefix) {
+                        return Some((prefix, &node.children[position]));
+                    }
+                }
+
+                None
+            }
+            ChildLayout::Node256(ref node) => {
+                while self.position < 256 {
+                    let prefix = self.position as u8;
+                    self.position += 1;
+
+                    if let Some(ref child) = node.children[prefix as usize] {
+                        return Some((prefix, child));
+                    }
+                }
+
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod node_children_tests {
+    use super::*;
+
+    fn layout_name<C>(children: &NodeChildren<C>) -> &'static str {
+        match children.layout {
+            ChildLayout::Node4(_) => "Node4",
+            ChildLayout::Node16(_) => "Node16",
+            ChildLayout::Node48(_) => "Node48",
+            ChildLayout::Node256(_) => "Node256",
+        }
+    }
+
+    // Visits every byte exactly once, in a scrambled order
+    fn scrambled_bytes() -> Vec<u8> {
+        (0..256).map(|idx| ((idx * 167 + 13) % 256) as u8).collect()
+    }
+
+    #[test]
+    fn grow_through_layouts() {
+        let mut children = NodeChildren::new();
+
+        for (count, prefix) in scrambled_bytes().into_iter().enumerate() {
+            let old_child = children.insert_child(Some(prefix), prefix);
+            assert!(old_child.is_none());
+
+            let expected_layout = match count + 1 {
+                0..=4 => "Node4",
+                5..=16 => "Node16",
+                17..=48 => "Node48",
+                _ => "Node256",
+            };
+            assert_eq!(layout_name(&children), expected_layout);
+            assert_eq!(children.len(), count + 1);
+        }
+
+        for prefix in 0..256 {
+            let child = children.get_child(Some(prefix as u8)).unwrap();
+            assert_eq!(child, &(prefix as u8));
+        }
+
+        let branches = children.iter().map(|(branch, _)| branch as usize).collect::<Vec<_>>();
+        assert_eq!(branches, (0..256).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn shrink_through_layouts() {
+        let mut children = NodeChildren::new();
+
+        for prefix in scrambled_bytes() {
+            children.insert_child(Some(prefix), prefix);
+        }
+
+        for (count, prefix) in scrambled_bytes().into_iter().enumerate() {
+            let removed = children.remove_child(Some(prefix)).unwrap();
+            assert_eq!(removed, prefix);
+            assert!(!children.contains_child(prefix));
+
+            let remaining = 255 - count;
+            let expected_layout = match remaining {
+                0..=3 => "Node4",
+                4..=12 => "Node16",
+                13..=36 => "Node48",
+                _ => "Node256",
+            };
+            assert_eq!(layout_name(&children), expected_layout);
+            assert_eq!(children.len(), remaining);
+
+            let branches = children.iter().map(|(branch, _)| branch).collect::<Vec<_>>();
+            let mut sorted = branches.clone();
+            sorted.sort();
+            assert_eq!(branches, sorted);
+            assert_eq!(branches.len(), remaining);
+        }
+    }
+
+    #[test]
+    fn into_children_takes_every_layout() {
+        for &count in [3usize, 10, 40, 200].iter() {
+            let mut children = NodeChildren::new();
+
+            children.insert_child(None, 1000);
+            for prefix in scrambled_bytes().into_iter().take(count) {
+                children.insert_child(Some(prefix), prefix as usize);
+            }
+
+            let mut taken = children.into_children();
+            taken.sort();
+
+            let mut expected = scrambled_bytes()
+                .into_iter()
+                .take(count)
+                .map(|prefix| prefix as usize)
+                .collect::<Vec<_>>();
+            expected.push(1000);
+            expected.sort();
+
+            assert_eq!(taken, expected);
+        }
+    }
+
+    #[test]
+    fn equality_ignores_layout() {
+        let mut grown = NodeChildren::new();
+        let mut small = NodeChildren::new();
+
+        for prefix in 0..10u8 {
+            grown.insert_child(Some(prefix), prefix);
+        }
+        for prefix in 4..10u8 {
+            grown.remove_child(Some(prefix));
+        }
+        for prefix in 0..4u8 {
+            small.insert_child(Some(prefix), prefix);
+        }
+
+        assert_eq!(layout_name(&grown), "Node16");
+        assert_eq!(layout_name(&small), "Node4");
+        assert_eq!(grown, small);
+    }
+}
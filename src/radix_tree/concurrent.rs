@@ -0,0 +1,798 @@
+use std::borrow::Borrow;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use crossbeam_epoch::{self as epoch, Atomic, Guard, Owned, Shared};
+
+use super::shared::SharedBytes;
+use super::key::{KeyMatchResult, KeyPrefix, KeyProbe, TreeKey};
+use super::entry::KeyValue;
+
+// Returned whenever an optimistic read or a lock upgrade observes that a
+// node changed underneath it. The whole operation is retried from the root.
+#[derive(Debug)]
+struct Restart;
+
+const OBSOLETE_BIT: usize = 0b01;
+const LOCKED_BIT: usize = 0b10;
+
+// Version counter used for optimistic lock coupling, as described in
+// "The ART of Practical Synchronization" (Leis et al, 2016).
+// Bit 0 marks the node as obsolete (it has been unlinked from the tree),
+// bit 1 marks the node as write locked and the remaining bits count the
+// number of completed writes.
+#[derive(Debug)]
+struct VersionLock {
+    version: AtomicUsize,
+}
+
+impl VersionLock {
+    fn new() -> Self {
+        VersionLock {
+            version: AtomicUsize::new(0),
+        }
+    }
+
+    fn read_lock(&self) -> Result<usize, Restart> {
+        loop {
+            let version = self.version.load(Ordering::Acquire);
+
+            if version & LOCKED_BIT != 0 {
+                thread::yield_now();
+            } else if version & OBSOLETE_BIT != 0 {
+                return Err(Restart);
+            } else {
+                return Ok(version);
+            }
+        }
+    }
+
+    fn check(&self, version: usize) -> Result<(), Restart> {
+        if self.version.load(Ordering::Acquire) == version {
+            Ok(())
+        } else {
+            Err(Restart)
+        }
+    }
+
+    fn upgrade(&self, version: usize) -> Result<(), Restart> {
+        self.version
+            .compare_exchange(
+                version,
+                version + LOCKED_BIT,
+                Ordering::AcqRel,
+                Ordering::Relaxed,
+            )
+            .map(|_| ())
+            .map_err(|_| Restart)
+    }
+
+    // Adding the locked bit again clears it and carries into the counter
+    fn write_unlock(&self) {
+        self.version.fetch_add(LOCKED_BIT, Ordering::Release);
+    }
+
+    fn write_unlock_obsolete(&self) {
+        self.version
+            .fetch_add(LOCKED_BIT | OBSOLETE_BIT, Ordering::Release);
+    }
+}
+
+// Leaves are immutable once published, a new value is installed by swapping
+// in a new leaf while holding the lock of the node owning the slot.
+#[derive(Debug)]
+struct LeafNode<K: TreeKey, V> {
+    entry: KeyValue<K, V>,
+    remaining_key: KeyPrefix,
+}
+
+// The prefix and the set of branching bytes of an interior node never change
+// after it is published, only the child pointers do. Adding or removing a
+// branch replaces the whole node with a modified copy.
+#[derive(Debug)]
+struct InteriorNode<K: TreeKey, V> {
+    lock: VersionLock,
+    prefix: KeyPrefix,
+    children: Vec<(u8, Atomic<Node<K, V>>)>,
+    empty_child: Atomic<Node<K, V>>,
+}
+
+#[derive(Debug)]
+enum Node<K: TreeKey, V> {
+    Leaf(LeafNode<K, V>),
+    Interior(InteriorNode<K, V>),
+}
+
+impl<K: TreeKey, V> InteriorNode<K, V> {
+    fn new(prefix: KeyPrefix) -> Self {
+        InteriorNode {
+            lock: VersionLock::new(),
+            prefix,
+            children: Vec::new(),
+            empty_child: Atomic::null(),
+        }
+    }
+
+    fn get_child(&self, possible_prefix: Option<u8>) -> Option<&Atomic<Node<K, V>>> {
+        if let Some(prefix) = possible_prefix {
+            self.children
+                .binary_search_by(|&(ref value, _)| value.cmp(&prefix))
+                .ok()
+                .map(|found_index| &self.children[found_index].1)
+        } else {
+            Some(&self.empty_child)
+        }
+    }
+
+    // Must only be called with the lock held, or on a node that has not been
+    // published yet.
+    fn add_child<'g>(&mut self, possible_prefix: Option<u8>, child: Shared<'g, Node<K, V>>) {
+        if let Some(prefix) = possible_prefix {
+            let search_result = self.children
+                .binary_search_by(|&(ref value, _)| value.cmp(&prefix));
+
+            match search_result {
+                Ok(found_index) => self.children[found_index].1 = Atomic::from(child),
+                Err(insert_index) => self.children
+                    .insert(insert_index, (prefix, Atomic::from(child))),
+            }
+        } else {
+            self.empty_child = Atomic::from(child);
+        }
+    }
+
+    fn num_entries<'g>(&self, guard: &'g Guard) -> usize {
+        let empty_entries = if self.empty_child.load(Ordering::Acquire, guard).is_null() {
+            0
+        } else {
+            1
+        };
+
+        self.children.len() + empty_entries
+    }
+
+    // Copies the node with a new prefix, skipping the given child. Must only
+    // be called with the lock held.
+    fn copy_with<'g>(
+        &self,
+        prefix: KeyPrefix,
+        skip: Option<Option<u8>>,
+        guard: &'g Guard,
+    ) -> InteriorNode<K, V> {
+        let mut copy = InteriorNode::new(prefix);
+
+        if skip != Some(None) {
+            copy.empty_child = Atomic::from(self.empty_child.load(Ordering::Acquire, guard));
+        }
+
+        for &(branch, ref child) in self.children.iter() {
+            if skip != Some(Some(branch)) {
+                copy.children
+                    .push((branch, Atomic::from(child.load(Ordering::Acquire, guard))));
+            }
+        }
+
+        copy
+    }
+}
+
+impl<K: TreeKey, V: Clone> Node<K, V> {
+    fn new_leaf(remaining_key: KeyPrefix, entry: KeyValue<K, V>) -> Self {
+        Node::Leaf(LeafNode {
+            entry,
+            remaining_key,
+        })
+    }
+
+    fn prefix(&self) -> &KeyPrefix {
+        match *self {
+            Node::Leaf(ref leaf) => &leaf.remaining_key,
+            Node::Interior(ref interior) => &interior.prefix,
+        }
+    }
+
+    fn lock(&self) -> Option<&VersionLock> {
+        match *self {
+            Node::Leaf(_) => None,
+            Node::Interior(ref interior) => Some(&interior.lock),
+        }
+    }
+
+    // Interior nodes must be locked by the caller
+    fn copy_with_prefix<'g>(&self, prefix: KeyPrefix, guard: &'g Guard) -> Self {
+        match *self {
+            Node::Leaf(ref leaf) => Node::new_leaf(prefix, leaf.entry.clone()),
+            Node::Interior(ref interior) => Node::Interior(interior.copy_with(prefix, None, guard)),
+        }
+    }
+}
+
+// A child pointer together with the lock of the node that owns it and the
+// version of that lock that was current when the pointer was read.
+struct Slot<'g, K: 'g + TreeKey, V: 'g> {
+    atomic: &'g Atomic<Node<K, V>>,
+    lock: &'g VersionLock,
+    version: usize,
+}
+
+impl<'g, K: 'g + TreeKey, V: 'g> Slot<'g, K, V> {
+    fn load(&self, guard: &'g Guard) -> Result<Shared<'g, Node<K, V>>, Restart> {
+        let current = self.atomic.load(Ordering::Acquire, guard);
+        self.lock.check(self.version)?;
+
+        Ok(current)
+    }
+}
+
+// Acquires the write locks in order, releasing the ones already held if any
+// upgrade fails. Locks must be passed from the top of the tree downwards so
+// that writers never wait on each other in a cycle.
+fn upgrade_all(locks: &[(&VersionLock, usize)]) -> Result<(), Restart> {
+    for (idx, &(lock, version)) in locks.iter().enumerate() {
+        if let Err(restart) = lock.upgrade(version) {
+            for &(held, _) in locks[..idx].iter() {
+                held.write_unlock();
+            }
+
+            return Err(restart);
+        }
+    }
+
+    Ok(())
+}
+
+// A radix tree read and modified from many threads at once. Writers walk
+// down optimistically and only lock the nodes they change, validating the
+// version counters they read on the way. Readers never lock. Unlinked nodes
+// are reclaimed once no thread can still be looking at them.
+#[derive(Debug)]
+pub struct ConcurrentRadixTree<K: TreeKey, V> {
+    size: AtomicUsize,
+    root_lock: VersionLock,
+    root: Atomic<Node<K, V>>,
+}
+
+impl<K, V> ConcurrentRadixTree<K, V>
+where
+    K: TreeKey + Send + Sync + 'static,
+    V: Clone + Send + Sync + 'static,
+{
+    pub fn new() -> Self {
+        ConcurrentRadixTree {
+            size: AtomicUsize::new(0),
+            root_lock: VersionLock::new(),
+            root: Atomic::null(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.size.load(Ordering::Acquire)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: TreeKey + AsRef<[u8]>,
+    {
+        self.get(key).is_some()
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: TreeKey + AsRef<[u8]>,
+    {
+        let guard = &epoch::pin();
+
+        loop {
+            if let Ok(found) = self.try_get(&key, guard) {
+                return found;
+            }
+        }
+    }
+
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
+        let guard = &epoch::pin();
+        let mut value = Some(value);
+        let shared_key = SharedBytes::copy_from_slice(key.as_bytes());
+
+        loop {
+            if let Ok(old_value) = self.try_insert(&key, &shared_key, &mut value, guard) {
+                if old_value.is_none() {
+                    self.size.fetch_add(1, Ordering::AcqRel);
+                }
+
+                return old_value;
+            }
+        }
+    }
+
+    pub fn remove<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: TreeKey,
+    {
+        let guard = &epoch::pin();
+
+        loop {
+            if let Ok(old_value) = self.try_remove(key, guard) {
+                if old_value.is_some() {
+                    self.size.fetch_sub(1, Ordering::AcqRel);
+                }
+
+                return old_value;
+            }
+        }
+    }
+
+    fn root_slot<'g>(&'g self) -> Result<Slot<'g, K, V>, Restart> {
+        Ok(Slot {
+            atomic: &self.root,
+            lock: &self.root_lock,
+            version: self.root_lock.read_lock()?,
+        })
+    }
+
+    fn try_get<'g, Q: TreeKey>(&'g self, key: &Q, guard: &'g Guard) -> Result<Option<V>, Restart> {
+        let mut probe = KeyProbe::new(key);
+        let mut current = self.root_slot()?.load(guard)?;
+
+        loop {
+            let interior = match unsafe { current.as_ref() } {
+                None => return Ok(None),
+                Some(&Node::Leaf(ref leaf)) => {
+                    return Ok(match leaf.remaining_key.match_with(probe) {
+                        KeyMatchResult::Complete => Some(leaf.entry.value().clone()),
+                        _ => None,
+                    })
+                }
+                Some(&Node::Interior(ref interior)) => interior,
+            };
+
+            let version = interior.lock.read_lock()?;
+            let branch = match interior.prefix.match_with(probe) {
+                KeyMatchResult::Complete => {
+                    probe = KeyProbe::empty();
+                    None
+                }
+                KeyMatchResult::Partial(mut remaining_probe) => {
+                    let next_char = remaining_probe
+                        .pop()
+                        .expect(concat!(file!(), ": ", line!()));
+                    probe = remaining_probe;
+                    Some(next_char)
+                }
+                _ => return Ok(None),
+            };
+
+            current = match interior.get_child(branch) {
+                Some(child) => child.load(Ordering::Acquire, guard),
+                None => Shared::null(),
+            };
+            interior.lock.check(version)?;
+        }
+    }
+
+    fn try_insert<'g>(
+        &'g self,
+        key: &K,
+        shared_key: &SharedBytes,
+        value: &mut Option<V>,
+        guard: &'g Guard,
+    ) -> Result<Option<V>, Restart> {
+        let mut probe = KeyProbe::shared(shared_key);
+        let mut slot = self.root_slot()?;
+        let mut current = slot.load(guard)?;
+
+        // Only called once every lock that is needed is held, so that the
+        // value is never consumed by an attempt that will be restarted.
+        let mut new_entry = || {
+            KeyValue::new(
+                key.clone(),
+                value.take().expect(concat!(file!(), ": ", line!())),
+            )
+        };
+
+        loop {
+            let node = match unsafe { current.as_ref() } {
+                Some(node) => node,
+                None => {
+                    slot.lock.upgrade(slot.version)?;
+                    slot.atomic.store(
+                        Owned::new(Node::new_leaf(From::from(probe), new_entry())),
+                        Ordering::Release,
+                    );
+                    slot.lock.write_unlock();
+
+                    return Ok(None);
+                }
+            };
+
+            let node_version = match node.lock() {
+                Some(lock) => Some((lock, lock.read_lock()?)),
+                None => None,
+            };
+
+            let (replacement, old_value) = match node.prefix().match_with(probe) {
+                KeyMatchResult::Complete => match *node {
+                    Node::Leaf(ref leaf) => {
+                        upgrade_all(&[(slot.lock, slot.version)])?;
+
+                        let replacement = Node::new_leaf(
+                            leaf.remaining_key.clone(),
+                            KeyValue::new(leaf.entry.key().clone(), new_entry().take_value()),
+                        );
+
+                        (replacement, Some(leaf.entry.value().clone()))
+                    }
+                    Node::Interior(ref interior) => {
+                        let (lock, version) = node_version.unwrap();
+                        let empty_child = interior.empty_child.load(Ordering::Acquire, guard);
+                        lock.check(version)?;
+
+                        slot = Slot {
+                            atomic: &interior.empty_child,
+                            lock,
+                            version,
+                        };
+                        current = empty_child;
+                        probe = KeyProbe::empty();
+                        continue;
+                    }
+                },
+                KeyMatchResult::Partial(mut remaining_probe) => {
+                    let next_char = remaining_probe
+                        .pop()
+                        .expect(concat!(file!(), ": ", line!()));
+
+                    match *node {
+                        Node::Leaf(ref leaf) => {
+                            upgrade_all(&[(slot.lock, slot.version)])?;
+
+                            let mut new_interior = InteriorNode::new(leaf.remaining_key.clone());
+                            let old_leaf = node.copy_with_prefix(KeyPrefix::empty(), guard);
+                            let new_leaf = Node::new_leaf(From::from(remaining_probe), new_entry());
+                            new_interior.add_child(None, Owned::new(old_leaf).into_shared(guard));
+                            new_interior.add_child(Some(next_char), Owned::new(new_leaf).into_shared(guard));
+
+                            (Node::Interior(new_interior), None)
+                        }
+                        Node::Interior(ref interior) => {
+                            let (lock, version) = node_version.unwrap();
+
+                            if let Some(child) = interior.get_child(Some(next_char)) {
+                                let next = child.load(Ordering::Acquire, guard);
+                                lock.check(version)?;
+
+                                slot = Slot {
+                                    atomic: child,
+                                    lock,
+                                    version,
+                                };
+                                current = next;
+                                probe = remaining_probe;
+                                continue;
+                            }
+
+                            upgrade_all(&[(slot.lock, slot.version), (lock, version)])?;
+
+                            let mut new_interior = interior.copy_with(interior.prefix.clone(), None, guard);
+                            let new_leaf = Node::new_leaf(From::from(remaining_probe), new_entry());
+                            new_interior.add_child(Some(next_char), Owned::new(new_leaf).into_shared(guard));
+
+                            (Node::Interior(new_interior), None)
+                        }
+                    }
+                }
+                KeyMatchResult::LongerPrefix(split_index) => {
+                    match node_version {
+                        Some((lock, version)) => upgrade_all(&[(slot.lock, slot.version), (lock, version)])?,
+                        None => upgrade_all(&[(slot.lock, slot.version)])?,
+                    }
+
+                    let (common, mut difference) = node.prefix().clone().split_at(split_index);
+                    let next_char_old = difference
+                        .pop()
+                        .expect(concat!(file!(), ": ", line!()));
+
+                    let mut new_interior = InteriorNode::new(common);
+                    let old_node = node.copy_with_prefix(difference, guard);
+                    let new_leaf = Node::new_leaf(KeyPrefix::empty(), new_entry());
+                    new_interior.add_child(None, Owned::new(new_leaf).into_shared(guard));
+                    new_interior.add_child(Some(next_char_old), Owned::new(old_node).into_shared(guard));
+
+                    (Node::Interior(new_interior), None)
+                }
+                KeyMatchResult::Incomplete(split_index, mut remaining_probe) => {
+                    match node_version {
+                        Some((lock, version)) => upgrade_all(&[(slot.lock, slot.version), (lock, version)])?,
+                        None => upgrade_all(&[(slot.lock, slot.version)])?,
+                    }
+
+                    let (common, mut difference) = node.prefix().clone().split_at(split_index);
+                    let next_char_old = difference
+                        .pop()
+                        .expect(concat!(file!(), ": ", line!()));
+                    let next_char_new = remaining_probe
+                        .pop()
+                        .expect(concat!(file!(), ": ", line!()));
+
+                    let mut new_interior = InteriorNode::new(common);
+                    let old_node = node.copy_with_prefix(difference, guard);
+                    let new_leaf = Node::new_leaf(From::from(remaining_probe), new_entry());
+                    new_interior.add_child(Some(next_char_old), Owned::new(old_node).into_shared(guard));
+                    new_interior.add_child(Some(next_char_new), Owned::new(new_leaf).into_shared(guard));
+
+                    (Node::Interior(new_interior), None)
+                }
+            };
+
+            // Every path that reaches this point holds the lock of the slot
+            // and, if the replaced node is an interior node, its lock as well.
+            slot.atomic.store(Owned::new(replacement), Ordering::Release);
+            if let Some(lock) = node.lock() {
+                lock.write_unlock_obsolete();
+            }
+            slot.lock.write_unlock();
+
+            unsafe {
+                guard.defer_destroy(current);
+            }
+
+            return Ok(old_value);
+        }
+    }
+
+    fn try_remove<'g, Q>(&'g self, key: &Q, guard: &'g Guard) -> Result<Option<V>, Restart>
+    where
+        Q: TreeKey,
+    {
+        let mut probe = KeyProbe::new(key);
+        let mut slot = self.root_slot()?;
+        let mut current = slot.load(guard)?;
+        let mut parent = None;
+
+        loop {
+            let interior = match unsafe { current.as_ref() } {
+                None => return Ok(None),
+                Some(&Node::Leaf(ref leaf)) => {
+                    if leaf.remaining_key.match_with(probe) != KeyMatchResult::Complete {
+                        return Ok(None);
+                    }
+
+                    match parent {
+                        Some((parent_slot, parent_node, parent_version, branch)) => self.remove_from_parent(
+                            parent_slot,
+                            parent_node,
+                            parent_version,
+                            branch,
+                            current,
+                            guard,
+                        )?,
+                        None => {
+                            upgrade_all(&[(slot.lock, slot.version)])?;
+                            slot.atomic.store(Shared::null(), Ordering::Release);
+                            slot.lock.write_unlock();
+
+                            unsafe {
+                                guard.defer_destroy(current);
+                            }
+                        }
+                    }
+
+                    return Ok(Some(leaf.entry.value().clone()));
+                }
+                Some(&Node::Interior(ref interior)) => interior,
+            };
+
+            let version = interior.lock.read_lock()?;
+            let branch = match interior.prefix.match_with(probe) {
+                KeyMatchResult::Complete => {
+                    probe = KeyProbe::empty();
+                    None
+                }
+                KeyMatchResult::Partial(mut remaining_probe) => {
+                    let next_char = remaining_probe
+                        .pop()
+                        .expect(concat!(file!(), ": ", line!()));
+                    probe = remaining_probe;
+                    Some(next_char)
+                }
+                _ => return Ok(None),
+            };
+
+            let child = match interior.get_child(branch) {
+                Some(child) => child,
+                None => return Ok(None),
+            };
+            let next = child.load(Ordering::Acquire, guard);
+            interior.lock.check(version)?;
+
+            parent = Some((slot, current, version, branch));
+            slot = Slot {
+                atomic: child,
+                lock: &interior.lock,
+                version,
+            };
+            current = next;
+        }
+    }
+
+    // Unlinks a leaf from its parent. Interior nodes always keep at least two
+    // entries, so when only one would remain the parent is merged into it.
+    fn remove_from_parent<'g>(
+        &'g self,
+        parent_slot: Slot<'g, K, V>,
+        parent_node: Shared<'g, Node<K, V>>,
+        parent_version: usize,
+        branch: Option<u8>,
+        leaf: Shared<'g, Node<K, V>>,
+        guard: &'g Guard,
+    ) -> Result<(), Restart> {
+        let parent = match *unsafe { parent_node.deref() } {
+            Node::Interior(ref interior) => interior,
+            Node::Leaf(_) => unreachable!(),
+        };
+
+        let num_entries = parent.num_entries(guard);
+        debug_assert!(num_entries >= 2);
+
+        if num_entries > 2 {
+            upgrade_all(&[
+                (parent_slot.lock, parent_slot.version),
+                (&parent.lock, parent_version),
+            ])?;
+
+            let replacement = parent.copy_with(parent.prefix.clone(), Some(branch), guard);
+            parent_slot.atomic.store(Owned::new(Node::Interior(replacement)), Ordering::Release);
+
+            parent.lock.write_unlock_obsolete();
+            parent_slot.lock.write_unlock();
+
+            unsafe {
+                guard.defer_destroy(parent_node);
+                guard.defer_destroy(leaf);
+            }
+
+            return Ok(());
+        }
+
+        let sibling_branch = if branch.is_some() && !parent.empty_child.load(Ordering::Acquire, guard).is_null() {
+            None
+        } else {
+            parent
+                .children
+                .iter()
+                .map(|&(branch, _)| Some(branch))
+                .find(|&other| other != branch)
+                .expect(concat!(file!(), ": ", line!()))
+        };
+        let sibling = parent
+            .get_child(sibling_branch)
+            .expect(concat!(file!(), ": ", line!()))
+            .load(Ordering::Acquire, guard);
+        parent.lock.check(parent_version)?;
+
+        let sibling_node = unsafe { sibling.deref() };
+        match sibling_node.lock() {
+            Some(sibling_lock) => {
+                let sibling_version = sibling_lock.read_lock()?;
+                upgrade_all(&[
+                    (parent_slot.lock, parent_slot.version),
+                    (&parent.lock, parent_version),
+                    (sibling_lock, sibling_version),
+                ])?;
+            }
+            None => upgrade_all(&[
+                (parent_slot.lock, parent_slot.version),
+                (&parent.lock, parent_version),
+            ])?,
+        }
+
+        let merged_prefix = parent.prefix.join(sibling_branch, sibling_node.prefix());
+        let replacement = sibling_node.copy_with_prefix(merged_prefix, guard);
+        parent_slot.atomic.store(Owned::new(replacement), Ordering::Release);
+
+        if let Some(sibling_lock) = sibling_node.lock() {
+            sibling_lock.write_unlock_obsolete();
+        }
+        parent.lock.write_unlock_obsolete();
+        parent_slot.lock.write_unlock();
+
+        unsafe {
+            guard.defer_destroy(sibling);
+            guard.defer_destroy(parent_node);
+            guard.defer_destroy(leaf);
+        }
+
+        Ok(())
+    }
+}
+
+impl<K: TreeKey, V> Drop for ConcurrentRadixTree<K, V> {
+    fn drop(&mut self) {
+        unsafe {
+            let guard = epoch::unprotected();
+            destroy_subtree(self.root.load(Ordering::Relaxed, guard), guard);
+        }
+    }
+}
+
+// Only safe to call once no other thread can access the tree. Walks with an
+// explicit stack, so a tree as deep as its longest key is freed without
+// recursing per level.
+unsafe fn destroy_subtree<'g, K: TreeKey, V>(root: Shared<'g, Node<K, V>>, guard: &'g Guard) {
+    let mut pending = vec![root];
+
+    while let Some(current) = pending.pop() {
+        if current.is_null() {
+            continue;
+        }
+
+        if let Node::Interior(ref interior) = *current.deref() {
+            pending.push(interior.empty_child.load(Ordering::Relaxed, guard));
+            pending.extend(interior.children.iter().map(|&(_, ref child)| child.load(Ordering::Relaxed, guard)));
+        }
+
+        drop(current.into_owned());
+    }
+}
+
+#[cfg(test)]
+mod concurrent_tree_tests {
+    use super::*;
+
+    #[test]
+    fn create_tree() {
+        let rax = ConcurrentRadixTree::<String, ()>::new();
+
+        assert!(rax.is_empty());
+    }
+
+    #[test]
+    fn insert_retrieve_overlapping() {
+        let rax = ConcurrentRadixTree::<&str, usize>::new();
+
+        assert_eq!(rax.insert("hello", 1), None);
+        assert_eq!(rax.insert("he", 2), None);
+        assert_eq!(rax.insert("help", 3), None);
+        assert_eq!(rax.insert("hallo", 4), None);
+        assert_eq!(rax.insert("hello", 5), Some(1));
+
+        assert_eq!(rax.len(), 4);
+        assert_eq!(rax.get(&"hello"), Some(5));
+        assert_eq!(rax.get(&"he"), Some(2));
+        assert_eq!(rax.get(&"help"), Some(3));
+        assert_eq!(rax.get(&"hallo"), Some(4));
+        assert_eq!(rax.get(&"h"), None);
+        assert_eq!(rax.get(&"hel"), None);
+        assert_eq!(rax.get(&"helpful"), None);
+    }
+
+    #[test]
+    fn remove_merges_nodes() {
+        let rax = ConcurrentRadixTree::<&str, usize>::new();
+
+        rax.insert("hello", 1);
+        rax.insert("he", 2);
+        rax.insert("help", 3);
+
+        assert_eq!(rax.remove(&"help"), Some(3));
+        assert_eq!(rax.remove(&"help"), None);
+        assert_eq!(rax.remove(&"he"), Some(2));
+        assert_eq!(rax.len(), 1);
+        assert_eq!(rax.get(&"hello"), Some(1));
+
+        let guard = &epoch::pin();
+        let root = unsafe { rax.root.load(Ordering::Acquire, guard).deref() };
+        assert_eq!(root.prefix().bytes(), b"hello");
+
+        assert_eq!(rax.remove(&"hello"), Some(1));
+        assert!(rax.is_empty());
+        assert_eq!(rax.get(&"hello"), None);
+    }
+}
@@ -0,0 +1,144 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+use core::str;
+
+use allocator_api2::alloc::Allocator;
+use allocator_api2::boxed::Box;
+
+use super::node::RadixNode;
+use super::entry::LeafEntry;
+
+// Key bytes as label text. Control characters are hex escaped, and when the
+// bytes are not UTF-8 so is every byte outside printable ascii.
+fn bytes_label(bytes: &[u8]) -> String {
+    match str::from_utf8(bytes) {
+        Ok(text) => {
+            let mut label = String::with_capacity(text.len());
+
+            for character in text.chars() {
+                if character.is_control() {
+                    write!(label, "\\u{{{:x}}}", character as u32).expect(concat!(file!(), ": ", line!()));
+                } else {
+                    label.push(character);
+                }
+            }
+
+            label
+        }
+        Err(_) => {
+            let mut label = String::with_capacity(bytes.len());
+
+            for &byte in bytes {
+                if byte.is_ascii_graphic() || byte == b' ' {
+                    label.push(byte as char);
+                } else {
+                    write!(label, "\\x{:02x}", byte).expect(concat!(file!(), ": ", line!()));
+                }
+            }
+
+            label
+        }
+    }
+}
+
+fn branch_label(branch: Option<u8>) -> String {
+    match branch {
+        Some(byte) => bytes_label(&[byte]),
+        None => String::from("∅"),
+    }
+}
+
+// Quotes text for use as a DOT string
+fn quoted(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+
+    for character in text.chars() {
+        match character {
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(character);
+            }
+            '\n' => quoted.push_str("\\n"),
+            _ => quoted.push(character),
+        }
+    }
+
+    quoted.push('"');
+    quoted
+}
+
+// Writes the tree as a Graphviz digraph. Interior nodes are boxes labeled by
+// their prefix, leaves are ellipses labeled by their remaining key and the
+// formatted value, and edges are labeled by the branching byte.
+pub fn tree_to_dot<E, A, F>(root: Option<&Box<RadixNode<E, A>, A>>, format_value: F) -> String
+where
+    E: LeafEntry,
+    A: Allocator + Clone,
+    F: Fn(&E::Value) -> String,
+{
+    let mut dot = String::from("digraph radix_tree {\n    node [fontname=\"monospace\"];\n");
+    let mut pending = root.into_iter().map(|root| (&**root, None)).collect::<Vec<_>>();
+    let mut next_id = 0;
+
+    while let Some((node, parent)) = pending.pop() {
+        let id = next_id;
+        next_id += 1;
+
+        let label = match *node {
+            RadixNode::Leaf(ref leaf) => {
+                let value = format_value(leaf.entry().value());
+
+                format!(
+                    "[shape=ellipse, label={}]",
+                    quoted(&format!("{}\n{}", bytes_label(leaf.remaining_key().bytes()), value))
+                )
+            }
+            RadixNode::Interior(ref interior) => {
+                let children = interior.children();
+
+                // Pushed in reverse so nodes are numbered in key order
+                let first_child = pending.len();
+                pending.extend(children.get_child(None).map(|child| (&**child, Some((id, None)))));
+                pending.extend(
+                    children
+                        .iter()
+                        .map(|(branch, child)| (&**child, Some((id, Some(branch))))),
+                );
+                pending[first_child..].reverse();
+
+                format!("[shape=box, label={}]", quoted(&bytes_label(interior.prefix().bytes())))
+            }
+        };
+
+        writeln!(dot, "    n{} {};", id, label).expect(concat!(file!(), ": ", line!()));
+
+        if let Some((parent_id, branch)) = parent {
+            writeln!(
+                dot,
+                "    n{} -> n{} [label={}];",
+                parent_id,
+                id,
+                quoted(&branch_label(branch))
+            ).expect(concat!(file!(), ": ", line!()));
+        }
+    }
+
+    dot.push_str("}\n");
+    dot
+}
+
+#[cfg(test)]
+mod dot_tests {
+    use super::*;
+
+    #[test]
+    fn labels_escape_bytes() {
+        assert_eq!(bytes_label(b"rom"), "rom");
+        assert_eq!(bytes_label(&[b'a', 0xff, b' ', 0x00]), "a\\xff \\x00");
+        assert_eq!(bytes_label("é\u{1}".as_bytes()), "é\\u{1}");
+        assert_eq!(branch_label(None), "∅");
+        assert_eq!(quoted("say \"hi\"\\"), "\"say \\\"hi\\\"\\\\\"");
+    }
+}
@@ -0,0 +1,592 @@
+use std::borrow::Borrow;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use error::{Error, Result};
+use super::tree::{Iter, RadixTree};
+use super::key::TreeKey;
+use super::frozen::{FrozenRadixTree, FrozenValue, crc32, read_fixed, read_varint, write_fixed, write_varint};
+
+// An in-memory radix tree made durable by a write-ahead log. Every insert and
+// remove is appended to the log and synced before it returns, and every so
+// many records the whole tree is written as a snapshot, in the frozen layout,
+// and the log starts over. Opening the tree loads the snapshot and replays
+// the log on top of it.
+//
+// Each record is a CRC-32, the payload length as a u32 and the payload, the
+// CRC covering the length and the payload. A payload is the operation, the
+// key length as a varint, the key and, for an insert, the encoded value.
+// Replay stops at a last record that is cut short or does not match its CRC,
+// which is where a crash tore the last write, and the log is truncated there.
+// Any other bad record has acknowledged records behind it or was written
+// whole, so the log is reported corrupt and left as it is. A write that fails partway is cut back off the log before the next
+// one, so nothing acknowledged ever follows a torn record.
+//
+// A snapshot is written to a temporary file and renamed over the last one
+// before the log is emptied. A crash between the two replays the log over a
+// snapshot that already holds it, which ends in the same tree since every
+// record sets or clears its key whatever was there.
+
+const SNAPSHOT_FILE: &str = "snapshot";
+const SNAPSHOT_TEMP_FILE: &str = "snapshot.tmp";
+const LOG_FILE: &str = "wal";
+
+const RECORD_HEADER_LEN: usize = 8;
+const INSERT: u8 = 1;
+const REMOVE: u8 = 2;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Record<V> {
+    Insert(Vec<u8>, V),
+    Remove(Vec<u8>),
+}
+
+fn encode_record<V: FrozenValue>(operation: u8, key: &[u8], value: Option<&V>) -> Vec<u8> {
+    let mut payload = vec![operation];
+    write_varint(&mut payload, key.len() as u64);
+    payload.extend_from_slice(key);
+    if let Some(value) = value {
+        value.encode_value(&mut payload);
+    }
+
+    let mut record = Vec::with_capacity(RECORD_HEADER_LEN + payload.len());
+    write_fixed(&mut record, 0, 4);
+    write_fixed(&mut record, payload.len() as u64, 4);
+    record.extend_from_slice(&payload);
+
+    let checksum = crc32(&record[4..]);
+    record[..4].copy_from_slice(&[checksum as u8, (checksum >> 8) as u8, (checksum >> 16) as u8, (checksum >> 24) as u8]);
+    record
+}
+
+// The whole records in `log` and the length they take, which is short of the
+// log only when its last record was torn
+fn decode_records<V: FrozenValue>(log: &[u8]) -> Result<(Vec<Record<V>>, usize)> {
+    let mut records = Vec::new();
+    let mut valid_len = 0;
+
+    while log.len() - valid_len >= RECORD_HEADER_LEN {
+        let header = &log[valid_len..valid_len + RECORD_HEADER_LEN];
+        let payload_len = read_fixed(&header[4..]) as usize;
+        let record_end = valid_len + RECORD_HEADER_LEN + payload_len;
+
+        if record_end > log.len() {
+            break;
+        }
+        if crc32(&log[valid_len + 4..record_end]) != read_fixed(&header[..4]) as u32 {
+            if record_end == log.len() {
+                break;
+            }
+
+            corrupt!("durable tree log record at offset {} does not match its checksum", valid_len);
+        }
+
+        match decode_payload(&log[valid_len + RECORD_HEADER_LEN..record_end]) {
+            Some(record) => records.push(record),
+            None => corrupt!("durable tree log record at offset {} does not decode", valid_len),
+        }
+        valid_len = record_end;
+    }
+
+    Ok((records, valid_len))
+}
+
+fn decode_payload<V: FrozenValue>(payload: &[u8]) -> Option<Record<V>> {
+    let (&operation, mut input) = payload.split_first()?;
+    let key_len = read_varint(&mut input)? as usize;
+    if input.len() < key_len {
+        return None;
+    }
+
+    let (key, value) = input.split_at(key_len);
+    match operation {
+        INSERT => V::decode_value(value).ok().map(|value| Record::Insert(key.to_vec(), value)),
+        REMOVE if value.is_empty() => Some(Record::Remove(key.to_vec())),
+        _ => None,
+    }
+}
+
+pub struct DurableRadixTree<V> {
+    tree: RadixTree<Vec<u8>, V>,
+    dir: PathBuf,
+    log: File,
+    // The length of the whole records in the log
+    log_len: u64,
+    // Whether the log may hold bytes past `log_len`, left by a failed write
+    log_torn: bool,
+    log_records: usize,
+    snapshot_every: usize,
+    // Makes the next append fail after writing this many bytes
+    #[cfg(test)]
+    torn_write_at: Option<usize>,
+}
+
+impl<V: FrozenValue> DurableRadixTree<V> {
+    // Opens the tree kept in the directory `dir`, creating it if needed. A
+    // snapshot is written once the log holds `snapshot_every` records.
+    pub fn open<P: AsRef<Path>>(dir: P, snapshot_every: usize) -> Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir).map_err(|err| Error::io("could not create durable tree directory", err))?;
+
+        let mut tree = match File::open(dir.join(SNAPSHOT_FILE)) {
+            Ok(mut file) => {
+                let mut bytes = Vec::new();
+                file.read_to_end(&mut bytes).map_err(|err| Error::io("could not read durable tree snapshot", err))?;
+
+                FrozenRadixTree::<V>::new(&bytes)
+                    .and_then(|snapshot| snapshot.iter().collect::<Result<_>>())
+                    .map_err(|err| Error::Corrupt(format!("durable tree snapshot is corrupt: {}", err)))?
+            }
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => RadixTree::new(),
+            Err(err) => return Err(Error::io("could not open durable tree snapshot", err)),
+        };
+
+        let mut log = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(dir.join(LOG_FILE))
+            .map_err(|err| Error::io("could not open durable tree log", err))?;
+        let mut bytes = Vec::new();
+        log.read_to_end(&mut bytes).map_err(|err| Error::io("could not read durable tree log", err))?;
+
+        let (records, valid_len) = decode_records::<V>(&bytes)?;
+        let log_records = records.len();
+        for record in records {
+            match record {
+                Record::Insert(key, value) => {
+                    tree.insert(key, value);
+                }
+                Record::Remove(key) => {
+                    tree.remove(&key);
+                }
+            }
+        }
+
+        // Drops the torn record, so new records follow the last whole one
+        if valid_len < bytes.len() {
+            log.set_len(valid_len as u64)
+                .and_then(|_| log.sync_data())
+                .map_err(|err| Error::io("could not truncate durable tree log", err))?;
+        }
+        log.seek(SeekFrom::Start(valid_len as u64))
+            .map_err(|err| Error::io("could not seek durable tree log", err))?;
+
+        Ok(DurableRadixTree {
+            tree,
+            dir,
+            log,
+            log_len: valid_len as u64,
+            log_torn: false,
+            log_records,
+            snapshot_every,
+            #[cfg(test)]
+            torn_write_at: None,
+        })
+    }
+
+    // The tree as of the last operation, for reads
+    pub fn tree(&self) -> &RadixTree<Vec<u8>, V> {
+        &self.tree
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        Vec<u8>: Borrow<Q>,
+        Q: TreeKey + AsRef<[u8]>,
+    {
+        self.tree.get(key)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        Vec<u8>: Borrow<Q>,
+        Q: TreeKey + AsRef<[u8]>,
+    {
+        self.tree.contains_key(key)
+    }
+
+    pub fn iter<'a>(&'a self) -> Iter<'a, Vec<u8>, V> {
+        self.tree.iter()
+    }
+
+    pub fn prefix_iter<'a>(&'a self, prefix: &[u8]) -> Iter<'a, Vec<u8>, V> {
+        self.tree.prefix_iter(prefix)
+    }
+
+    // The record is synced before the tree changes, so a failed write leaves
+    // the tree as it was. Once the record is in the log the change has gone
+    // through, and a snapshot falling due that cannot be written is left to
+    // the next write to retry, or to `snapshot` to report.
+    pub fn insert(&mut self, key: Vec<u8>, value: V) -> Result<Option<V>> {
+        self.append(encode_record(INSERT, &key, Some(&value)))?;
+
+        let old_value = self.tree.insert(key, value);
+        self.snapshot_if_due();
+        Ok(old_value)
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Result<Option<V>>
+    where
+        Vec<u8>: Borrow<Q>,
+        Q: TreeKey + AsRef<[u8]>,
+    {
+        if self.tree.get(key).is_none() {
+            return Ok(None);
+        }
+
+        self.append(encode_record::<V>(REMOVE, key.as_bytes(), None))?;
+
+        let old_value = self.tree.remove(key);
+        self.snapshot_if_due();
+        Ok(old_value)
+    }
+
+    // Writes the whole tree as a snapshot and empties the log
+    pub fn snapshot(&mut self) -> Result<()> {
+        let bytes = self.tree.to_frozen()?;
+        let temp_path = self.dir.join(SNAPSHOT_TEMP_FILE);
+
+        File::create(&temp_path)
+            .and_then(|mut file| file.write_all(&bytes).and_then(|_| file.sync_all()))
+            .map_err(|err| Error::io("could not write durable tree snapshot", err))?;
+        fs::rename(&temp_path, self.dir.join(SNAPSHOT_FILE))
+            .map_err(|err| Error::io("could not replace durable tree snapshot", err))?;
+        self.sync_dir()?;
+
+        self.log
+            .set_len(0)
+            .and_then(|_| self.log.seek(SeekFrom::Start(0)))
+            .and_then(|_| self.log.sync_data())
+            .map_err(|err| {
+                // The snapshot already holds every record, so the log is
+                // emptied before the next append if this got partway
+                self.log_torn = true;
+                Error::io("could not empty durable tree log", err)
+            })?;
+        self.log_len = 0;
+        self.log_records = 0;
+
+        Ok(())
+    }
+
+    fn append(&mut self, record: Vec<u8>) -> Result<()> {
+        if self.log_torn {
+            self.rewind_log()?;
+        }
+
+        if let Err(err) = self.write_record(&record).and_then(|_| self.log.sync_data()) {
+            // Some of the record may have reached the file, and anything
+            // appended after it would be lost on replay
+            self.log_torn = true;
+            let _ = self.rewind_log();
+
+            return Err(Error::io("could not append to durable tree log", err));
+        }
+        self.log_len += record.len() as u64;
+        self.log_records += 1;
+
+        Ok(())
+    }
+
+    // Cuts the log back to its whole records, leaving the position after them
+    fn rewind_log(&mut self) -> Result<()> {
+        let log_len = self.log_len;
+        self.log
+            .set_len(log_len)
+            .and_then(|_| self.log.seek(SeekFrom::Start(log_len)))
+            .and_then(|_| self.log.sync_data())
+            .map_err(|err| Error::io("could not cut torn record from durable tree log", err))?;
+        self.log_torn = false;
+
+        Ok(())
+    }
+
+    #[cfg(not(test))]
+    fn write_record(&mut self, record: &[u8]) -> io::Result<()> {
+        self.log.write_all(record)
+    }
+
+    #[cfg(test)]
+    fn write_record(&mut self, record: &[u8]) -> io::Result<()> {
+        match self.torn_write_at.take() {
+            Some(cut) => {
+                self.log.write_all(&record[..cut.min(record.len())])?;
+                Err(io::Error::other("injected torn write"))
+            }
+            None => self.log.write_all(record),
+        }
+    }
+
+    // A failed snapshot leaves the log whole, so the records stay durable and
+    // the snapshot is still due on the next write
+    fn snapshot_if_due(&mut self) {
+        if self.log_records >= self.snapshot_every {
+            let _ = self.snapshot();
+        }
+    }
+
+    // Makes the rename of the snapshot durable
+    #[cfg(unix)]
+    fn sync_dir(&self) -> Result<()> {
+        File::open(&self.dir)
+            .and_then(|dir| dir.sync_all())
+            .map_err(|err| Error::io("could not sync durable tree directory", err))
+    }
+
+    #[cfg(not(unix))]
+    fn sync_dir(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod durable_tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use std::env;
+    use std::process;
+
+    // A directory in the temporary directory, removed when dropped
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = env::temp_dir().join(format!("durable_radix_tree_{}_{}", name, process::id()));
+            let _ = fs::remove_dir_all(&path);
+
+            TempDir(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn contents(tree: &DurableRadixTree<u64>) -> BTreeMap<Vec<u8>, u64> {
+        tree.iter().map(|(key, &value)| (key.clone(), value)).collect()
+    }
+
+    fn operations() -> Vec<Record<u64>> {
+        let keys: &[&[u8]] = &[b"romane", b"romanus", b"rom", b"", b"romulus", b"rubens"];
+
+        (0..24u64)
+            .map(|step| {
+                let key = keys[(step * 7 % 6) as usize].to_vec();
+
+                if step % 4 == 3 {
+                    Record::Remove(key)
+                } else {
+                    Record::Insert(key, step * 1000)
+                }
+            })
+            .collect()
+    }
+
+    // Applies `operations`, returning the log length and expected contents
+    // after each of them, starting with the state before the first
+    fn apply(tree: &mut DurableRadixTree<u64>, operations: &[Record<u64>]) -> Vec<(u64, BTreeMap<Vec<u8>, u64>)> {
+        let mut states = vec![(tree.log.metadata().unwrap().len(), contents(tree))];
+
+        for operation in operations {
+            match *operation {
+                Record::Insert(ref key, value) => {
+                    tree.insert(key.clone(), value).unwrap();
+                }
+                Record::Remove(ref key) => {
+                    tree.remove(key).unwrap();
+                }
+            }
+
+            states.push((tree.log.metadata().unwrap().len(), contents(tree)));
+        }
+
+        states
+    }
+
+    #[test]
+    fn record_round_trip() {
+        let record = encode_record(INSERT, b"rom", Some(&7u64));
+        let (records, valid_len) = decode_records::<u64>(&record).unwrap();
+
+        assert_eq!(records, vec![Record::Insert(b"rom".to_vec(), 7)]);
+        assert_eq!(valid_len, record.len());
+
+        let mut corrupt = record.clone();
+        corrupt[RECORD_HEADER_LEN] ^= 0x01;
+        assert_eq!(decode_records::<u64>(&corrupt).unwrap(), (Vec::new(), 0));
+
+        // Checksummed but not a `u32` value
+        assert!(decode_records::<u32>(&record).is_err());
+    }
+
+    #[test]
+    fn recovers_from_log_truncated_at_every_offset() {
+        let dir = TempDir::new("truncated");
+        let mut tree = DurableRadixTree::open(&dir.0, usize::MAX).unwrap();
+
+        // Some state in a snapshot first, so replay starts from it
+        tree.insert(b"ruber".to_vec(), 1).unwrap();
+        tree.insert(b"rom".to_vec(), 2).unwrap();
+        tree.snapshot().unwrap();
+
+        let states = apply(&mut tree, &operations());
+        drop(tree);
+
+        let log_path = dir.0.join(LOG_FILE);
+        let mut log = Vec::new();
+        File::open(&log_path).unwrap().read_to_end(&mut log).unwrap();
+
+        for cut in 0..log.len() + 1 {
+            File::create(&log_path).unwrap().write_all(&log[..cut]).unwrap();
+
+            let (valid_len, ref expected) = *states.iter().rev().find(|&&(len, _)| len <= cut as u64).unwrap();
+            let mut tree = DurableRadixTree::<u64>::open(&dir.0, usize::MAX).unwrap();
+            assert_eq!(&contents(&tree), expected, "log cut at {}", cut);
+            assert_eq!(tree.log.metadata().unwrap().len(), valid_len);
+
+            // New records follow the last whole one
+            tree.insert(b"after".to_vec(), 99).unwrap();
+            drop(tree);
+
+            let tree = DurableRadixTree::<u64>::open(&dir.0, usize::MAX).unwrap();
+            let mut expected = expected.clone();
+            expected.insert(b"after".to_vec(), 99);
+            assert_eq!(contents(&tree), expected, "log cut at {} then appended", cut);
+        }
+    }
+
+    #[test]
+    fn torn_append_is_cut_before_the_next() {
+        let dir = TempDir::new("torn_append");
+        let mut tree = DurableRadixTree::<u64>::open(&dir.0, usize::MAX).unwrap();
+        tree.insert(b"rom".to_vec(), 1).unwrap();
+        let whole_len = tree.log_len;
+
+        tree.torn_write_at = Some(5);
+        assert!(tree.insert(b"romane".to_vec(), 2).is_err());
+        assert_eq!(tree.log.metadata().unwrap().len(), whole_len);
+
+        tree.insert(b"romanus".to_vec(), 3).unwrap();
+        drop(tree);
+
+        let tree = DurableRadixTree::<u64>::open(&dir.0, usize::MAX).unwrap();
+        let expected = vec![(b"rom".to_vec(), 1), (b"romanus".to_vec(), 3)];
+        assert_eq!(contents(&tree), expected.into_iter().collect());
+    }
+
+    #[test]
+    fn damaged_record_before_others_is_an_error() {
+        let dir = TempDir::new("damaged_middle");
+        let mut tree = DurableRadixTree::<u64>::open(&dir.0, usize::MAX).unwrap();
+        apply(&mut tree, &operations());
+        drop(tree);
+
+        let log_path = dir.0.join(LOG_FILE);
+        let mut log = Vec::new();
+        File::open(&log_path).unwrap().read_to_end(&mut log).unwrap();
+        // The operation byte of the record holding the middle of the log
+        let mut record_start = 0;
+        loop {
+            let record_end = record_start + RECORD_HEADER_LEN + read_fixed(&log[record_start + 4..record_start + 8]) as usize;
+            if record_end > log.len() / 2 {
+                break;
+            }
+
+            record_start = record_end;
+        }
+        let mut damaged = log.clone();
+        damaged[record_start + RECORD_HEADER_LEN] ^= 0x10;
+        File::create(&log_path).unwrap().write_all(&damaged).unwrap();
+
+        match DurableRadixTree::<u64>::open(&dir.0, usize::MAX) {
+            Err(Error::Corrupt(_)) => {}
+            other => panic!("unexpected {:?}", other.map(|tree| tree.len())),
+        }
+        assert_eq!(fs::metadata(&log_path).unwrap().len(), log.len() as u64);
+    }
+
+    #[test]
+    fn periodic_snapshots() {
+        let dir = TempDir::new("snapshots");
+        let mut tree = DurableRadixTree::open(&dir.0, 5).unwrap();
+        let states = apply(&mut tree, &operations());
+
+        assert!(tree.log_records < 5);
+        assert!(dir.0.join(SNAPSHOT_FILE).exists());
+        drop(tree);
+
+        let tree = DurableRadixTree::<u64>::open(&dir.0, 5).unwrap();
+        assert_eq!(&contents(&tree), &states.last().unwrap().1);
+    }
+
+    #[test]
+    fn failed_snapshot_does_not_fail_the_write() {
+        let dir = TempDir::new("failed_snapshot");
+        let mut tree = DurableRadixTree::<u64>::open(&dir.0, 2).unwrap();
+        tree.insert(b"rom".to_vec(), 1).unwrap();
+
+        // A directory in the way of the temporary file stops the snapshot
+        fs::create_dir(dir.0.join(SNAPSHOT_TEMP_FILE)).unwrap();
+        assert_eq!(tree.insert(b"romane".to_vec(), 2).unwrap(), None);
+        assert_eq!(tree.get(&b"romane".to_vec()), Some(&2));
+        assert_eq!(tree.log_records, 2);
+        assert!(tree.snapshot().is_err());
+
+        fs::remove_dir(dir.0.join(SNAPSHOT_TEMP_FILE)).unwrap();
+        tree.insert(b"romanus".to_vec(), 3).unwrap();
+        assert_eq!(tree.log_records, 0);
+        drop(tree);
+
+        let tree = DurableRadixTree::<u64>::open(&dir.0, 2).unwrap();
+        assert_eq!(tree.len(), 3);
+    }
+
+    #[test]
+    fn snapshot_of_another_value_type_is_an_error() {
+        let dir = TempDir::new("snapshot_type");
+        let mut tree = DurableRadixTree::<u64>::open(&dir.0, usize::MAX).unwrap();
+        tree.insert(b"rom".to_vec(), 1).unwrap();
+        tree.snapshot().unwrap();
+        drop(tree);
+
+        match DurableRadixTree::<u32>::open(&dir.0, usize::MAX) {
+            Err(Error::Corrupt(message)) => {
+                assert_eq!(message, "durable tree snapshot is corrupt: u32 value needs 4 bytes, found 8")
+            }
+            other => panic!("unexpected {:?}", other.map(|tree| tree.len())),
+        }
+    }
+
+    #[test]
+    fn crash_between_snapshot_and_log_reset() {
+        let dir = TempDir::new("replayed");
+        let mut tree = DurableRadixTree::open(&dir.0, usize::MAX).unwrap();
+        let states = apply(&mut tree, &operations());
+        drop(tree);
+
+        // The snapshot is in place but the log was never emptied
+        let mut log = Vec::new();
+        File::open(dir.0.join(LOG_FILE)).unwrap().read_to_end(&mut log).unwrap();
+        DurableRadixTree::<u64>::open(&dir.0, usize::MAX)
+            .unwrap()
+            .snapshot()
+            .unwrap();
+        File::create(dir.0.join(LOG_FILE)).unwrap().write_all(&log).unwrap();
+
+        let tree = DurableRadixTree::<u64>::open(&dir.0, usize::MAX).unwrap();
+        assert_eq!(&contents(&tree), &states.last().unwrap().1);
+    }
+}
@@ -1,6 +1,10 @@
-use std::mem;
-use std::fmt;
-use super::key::TreeKey;
+use core::mem;
+use core::fmt;
+
+use allocator_api2::alloc::{Allocator, Global};
+use allocator_api2::boxed::Box;
+
+use super::key::{RebuildableKey, TreeKey};
 
 #[derive(Clone, PartialEq, Eq)]
 pub struct KeyValue<K: TreeKey, V> {
@@ -49,3 +53,179 @@ impl<K: TreeKey + fmt::Debug, V: fmt::Debug> fmt::Debug for KeyValue<K, V> {
         write!(f, "KeyValue {{ key: {:?}, value: {:?}}}", self.key, self.value)
     }
 }
+
+// The entry kept in a leaf, gives the tree algorithms access to the value
+// whether or not the key is stored beside it
+pub trait LeafEntry {
+    type Value;
+
+    fn value(&self) -> &Self::Value;
+
+    fn value_mut(&mut self) -> &mut Self::Value;
+
+    fn take_value(self) -> Self::Value;
+
+    fn swap_value(&mut self, new_value: Self::Value) -> Self::Value;
+
+    // Bytes the entry allocates itself, not counting allocations owned by
+    // the key or value
+    fn heap_bytes(&self) -> usize {
+        0
+    }
+
+    // The bytes of the key, for entries which keep their key
+    fn key_bytes(&self) -> Option<&[u8]> {
+        None
+    }
+}
+
+impl<K: TreeKey, V> LeafEntry for KeyValue<K, V> {
+    type Value = V;
+
+    fn value(&self) -> &V {
+        &self.value
+    }
+
+    fn value_mut(&mut self) -> &mut V {
+        &mut self.value
+    }
+
+    fn take_value(self) -> V {
+        self.value
+    }
+
+    fn swap_value(&mut self, new_value: V) -> V {
+        mem::replace(&mut self.value, new_value)
+    }
+
+    fn key_bytes(&self) -> Option<&[u8]> {
+        Some(self.key.as_bytes())
+    }
+}
+
+impl<E: LeafEntry, A: Allocator> LeafEntry for Box<E, A> {
+    type Value = E::Value;
+
+    fn value(&self) -> &E::Value {
+        (**self).value()
+    }
+
+    fn value_mut(&mut self) -> &mut E::Value {
+        (**self).value_mut()
+    }
+
+    fn take_value(self) -> E::Value {
+        Box::into_inner(self).take_value()
+    }
+
+    fn swap_value(&mut self, new_value: E::Value) -> E::Value {
+        (**self).swap_value(new_value)
+    }
+
+    fn heap_bytes(&self) -> usize {
+        mem::size_of::<E>() + (**self).heap_bytes()
+    }
+
+    fn key_bytes(&self) -> Option<&[u8]> {
+        (**self).key_bytes()
+    }
+}
+
+// Entry of a tree which rebuilds keys from the path to the leaf, so only
+// the value is kept
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BareValue<V> {
+    value: V,
+}
+
+impl<V> LeafEntry for BareValue<V> {
+    type Value = V;
+
+    fn value(&self) -> &V {
+        &self.value
+    }
+
+    fn value_mut(&mut self) -> &mut V {
+        &mut self.value
+    }
+
+    fn take_value(self) -> V {
+        self.value
+    }
+
+    fn swap_value(&mut self, new_value: V) -> V {
+        mem::replace(&mut self.value, new_value)
+    }
+}
+
+// Entry of a set, only the key is kept. The unit value takes no space, it is
+// only there so the tree algorithms have a value to hand out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyOnly<K> {
+    key: K,
+    unit: (),
+}
+
+impl<K> KeyOnly<K> {
+    pub fn new(key: K) -> Self {
+        KeyOnly { key, unit: () }
+    }
+
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+}
+
+impl<K: TreeKey> LeafEntry for KeyOnly<K> {
+    type Value = ();
+
+    fn value(&self) -> &() {
+        &self.unit
+    }
+
+    fn value_mut(&mut self) -> &mut () {
+        &mut self.unit
+    }
+
+    fn take_value(self) {}
+
+    fn swap_value(&mut self, _new_value: ()) {}
+
+    fn key_bytes(&self) -> Option<&[u8]> {
+        Some(self.key.as_bytes())
+    }
+}
+
+// Decides what a `RadixTree` keeps in its leaves. Entries which need memory
+// of their own take it from the allocator of the tree.
+pub trait KeyStorage<K: TreeKey, V, A: Allocator + Clone = Global> {
+    type Entry: LeafEntry<Value = V>;
+
+    fn new_entry(key: K, value: V, alloc: &A) -> Self::Entry;
+}
+
+// Every leaf keeps a clone of its key, which works for any key type and lets
+// iteration hand out references to the keys
+#[derive(Debug)]
+pub enum StoredKeys {}
+
+impl<K: TreeKey, V, A: Allocator + Clone> KeyStorage<K, V, A> for StoredKeys {
+    type Entry = Box<KeyValue<K, V>, A>;
+
+    fn new_entry(key: K, value: V, alloc: &A) -> Self::Entry {
+        Box::new_in(KeyValue::new(key, value), alloc.clone())
+    }
+}
+
+// Leaves keep only the value, keys are rebuilt from the bytes along the
+// path to the leaf when the tree is iterated
+#[derive(Debug)]
+pub enum RebuiltKeys {}
+
+impl<K: RebuildableKey, V, A: Allocator + Clone> KeyStorage<K, V, A> for RebuiltKeys {
+    type Entry = BareValue<V>;
+
+    fn new_entry(_key: K, value: V, _alloc: &A) -> Self::Entry {
+        BareValue { value }
+    }
+}
@@ -0,0 +1,818 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cmp;
+use core::iter;
+use core::marker::PhantomData;
+use core::ops::{Bound, RangeBounds};
+use core::str;
+
+use allocator_api2::alloc::Allocator;
+use allocator_api2::boxed::Box;
+
+use error::{Error, Result};
+use super::node::{RadixInteriorNode, RadixNode};
+use super::entry::LeafEntry;
+use super::key::{owned_bound, prefix_upper, within_upper};
+
+// A read only radix tree laid out in one byte buffer, so it can be written
+// to a file once and used straight from a mapping of that file.
+//
+// The buffer starts with a fixed header, all integers little endian:
+//
+//     magic        4 bytes, "RDXF"
+//     version      u16
+//     reserved     u16, zero
+//     len          u64, number of keys
+//     root         u32, offset of the root node, u32::MAX for an empty tree
+//     body_len     u32, bytes of nodes following the header
+//     body_crc     u32, CRC-32 of the nodes
+//     header_crc   u32, CRC-32 of the header bytes before it
+//
+// followed by the nodes, with offsets counted from the end of the header.
+// Lengths are LEB128 varints and child offsets are u32. A leaf is
+//
+//     LEAF, remaining key length, remaining key, value length, value
+//
+// and an interior node is
+//
+//     INTERIOR | HAS_EMPTY?, prefix length, prefix, child count,
+//     empty child offset if HAS_EMPTY, branching bytes, child offsets
+//
+// with the branching bytes ascending, so a lookup binary searches them. Nodes
+// are written children first, which leaves the root at the end.
+
+const MAGIC: &[u8; 4] = b"RDXF";
+const VERSION: u16 = 1;
+const HEADER_LEN: usize = 32;
+const NO_ROOT: u32 = u32::MAX;
+
+const LEAF: u8 = 0;
+const INTERIOR: u8 = 1;
+const HAS_EMPTY: u8 = 2;
+
+// Values stored in a frozen tree. They are encoded as the tree is written and
+// decoded on every read, the reader only ever hands out owned values.
+pub trait FrozenValue: Sized {
+    fn encode_value(&self, buffer: &mut Vec<u8>);
+
+    fn decode_value(bytes: &[u8]) -> Result<Self>;
+}
+
+macro_rules! frozen_int_value {
+    ($value:ty, $width:expr) => {
+        impl FrozenValue for $value {
+            fn encode_value(&self, buffer: &mut Vec<u8>) {
+                write_fixed(buffer, *self as u64, $width);
+            }
+
+            fn decode_value(bytes: &[u8]) -> Result<Self> {
+                if bytes.len() != $width {
+                    corrupt!("{} value needs {} bytes, found {}", stringify!($value), $width, bytes.len());
+                }
+
+                Ok(read_fixed(bytes) as $value)
+            }
+        }
+    };
+}
+
+frozen_int_value!(u8, 1);
+frozen_int_value!(u16, 2);
+frozen_int_value!(u32, 4);
+frozen_int_value!(u64, 8);
+frozen_int_value!(usize, 8);
+frozen_int_value!(i8, 1);
+frozen_int_value!(i16, 2);
+frozen_int_value!(i32, 4);
+frozen_int_value!(i64, 8);
+
+impl FrozenValue for () {
+    fn encode_value(&self, _buffer: &mut Vec<u8>) {}
+
+    fn decode_value(bytes: &[u8]) -> Result<Self> {
+        if !bytes.is_empty() {
+            corrupt!("unit value should be empty, found {} bytes", bytes.len());
+        }
+
+        Ok(())
+    }
+}
+
+impl FrozenValue for Vec<u8> {
+    fn encode_value(&self, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(self);
+    }
+
+    fn decode_value(bytes: &[u8]) -> Result<Self> {
+        Ok(bytes.to_vec())
+    }
+}
+
+impl FrozenValue for String {
+    fn encode_value(&self, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(self.as_bytes());
+    }
+
+    fn decode_value(bytes: &[u8]) -> Result<Self> {
+        match str::from_utf8(bytes) {
+            Ok(text) => Ok(String::from(text)),
+            Err(err) => corrupt!("string value is not utf-8: {}", err),
+        }
+    }
+}
+
+pub fn write_fixed(buffer: &mut Vec<u8>, value: u64, width: usize) {
+    buffer.extend((0..width).map(|idx| (value >> (8 * idx)) as u8));
+}
+
+pub fn read_fixed(bytes: &[u8]) -> u64 {
+    bytes
+        .iter()
+        .enumerate()
+        .fold(0, |value, (idx, &byte)| value | (byte as u64) << (8 * idx))
+}
+
+pub fn write_varint(buffer: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buffer.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+
+    buffer.push(value as u8);
+}
+
+// Reads a varint from the front of `input`, advancing it past the varint
+pub fn read_varint(input: &mut &[u8]) -> Option<u64> {
+    let mut value = 0;
+
+    for (idx, &byte) in input.iter().enumerate().take(10) {
+        value |= ((byte & 0x7f) as u64) << (7 * idx);
+
+        if byte & 0x80 == 0 {
+            *input = &input[idx + 1..];
+            return Some(value);
+        }
+    }
+
+    None
+}
+
+// CRC-32 as used by zip and png, computed a bit at a time since it only runs
+// over a buffer once when it is written or opened
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+
+    for &byte in bytes {
+        crc ^= byte as u32;
+
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & 0u32.wrapping_sub(crc & 1));
+        }
+    }
+
+    !crc
+}
+
+pub fn write_length_prefixed(buffer: &mut Vec<u8>, bytes: &[u8]) {
+    write_varint(buffer, bytes.len() as u64);
+    buffer.extend_from_slice(bytes);
+}
+
+fn offset_of(body: &[u8]) -> Result<u32> {
+    if body.len() >= NO_ROOT as usize {
+        return Err(Error::CapacityExceeded {
+            what: "frozen tree node bytes",
+            limit: NO_ROOT as usize,
+            needed: body.len() + 1,
+        });
+    }
+
+    Ok(body.len() as u32)
+}
+
+enum WriteStep<'a, E: 'a, A: 'a + Allocator + Clone> {
+    Visit(&'a RadixNode<E, A>),
+    // Written once the offsets of all the children are known
+    Interior(&'a RadixInteriorNode<E, A>),
+}
+
+// Writes a tree in the frozen layout, walking it with an explicit stack. The
+// offsets of written children are kept on a second stack in key order, where
+// each interior node takes its own off the top.
+pub fn write_frozen<E, A>(root: Option<&Box<RadixNode<E, A>, A>>, len: usize) -> Result<Vec<u8>>
+where
+    E: LeafEntry,
+    E::Value: FrozenValue,
+    A: Allocator + Clone,
+{
+    let mut body = Vec::new();
+    let mut value_buffer = Vec::new();
+    let mut written = Vec::new();
+    let mut pending = root.into_iter().map(|root| WriteStep::Visit(&**root)).collect::<Vec<_>>();
+
+    while let Some(step) = pending.pop() {
+        match step {
+            WriteStep::Visit(&RadixNode::Leaf(ref leaf)) => {
+                written.push(offset_of(&body)?);
+
+                value_buffer.clear();
+                leaf.entry().value().encode_value(&mut value_buffer);
+
+                body.push(LEAF);
+                write_length_prefixed(&mut body, leaf.remaining_key().bytes());
+                write_length_prefixed(&mut body, &value_buffer);
+            }
+            WriteStep::Visit(&RadixNode::Interior(ref interior)) => {
+                let children = interior.children();
+
+                pending.push(WriteStep::Interior(interior));
+                let first_child = pending.len();
+                pending.extend(children.get_child(None).map(|child| WriteStep::Visit(&**child)));
+                pending.extend(children.iter().map(|(_, child)| WriteStep::Visit(&**child)));
+                pending[first_child..].reverse();
+            }
+            WriteStep::Interior(interior) => {
+                let children = interior.children();
+                let has_empty = children.contains_empty();
+                let child_count = children.len() + has_empty as usize;
+                let first_child = written.len() - child_count;
+                let offset = offset_of(&body)?;
+
+                body.push(if has_empty { INTERIOR | HAS_EMPTY } else { INTERIOR });
+                write_length_prefixed(&mut body, interior.prefix().bytes());
+                write_varint(&mut body, children.len() as u64);
+                if has_empty {
+                    write_fixed(&mut body, written[first_child] as u64, 4);
+                }
+                body.extend(children.iter().map(|(branch, _)| branch));
+                for &child_offset in &written[first_child + has_empty as usize..] {
+                    write_fixed(&mut body, child_offset as u64, 4);
+                }
+
+                written.truncate(first_child);
+                written.push(offset);
+            }
+        }
+    }
+
+    let root = written.pop().unwrap_or(NO_ROOT);
+    let mut frozen = Vec::with_capacity(HEADER_LEN + body.len());
+    frozen.extend_from_slice(MAGIC);
+    write_fixed(&mut frozen, VERSION as u64, 2);
+    write_fixed(&mut frozen, 0, 2);
+    write_fixed(&mut frozen, len as u64, 8);
+    write_fixed(&mut frozen, root as u64, 4);
+    write_fixed(&mut frozen, offset_of(&body)? as u64, 4);
+    write_fixed(&mut frozen, crc32(&body) as u64, 4);
+    let header_crc = crc32(&frozen);
+    write_fixed(&mut frozen, header_crc as u64, 4);
+
+    frozen.extend_from_slice(&body);
+    Ok(frozen)
+}
+
+enum FrozenNode<'a> {
+    Leaf {
+        remaining_key: &'a [u8],
+        value: &'a [u8],
+    },
+    Interior {
+        prefix: &'a [u8],
+        empty_child: Option<u32>,
+        branches: &'a [u8],
+        offsets: &'a [u8],
+    },
+}
+
+impl<'a> FrozenNode<'a> {
+    // A checksum only proves the nodes are the ones written, so a node that
+    // does not parse, or names a child that does not come before it, is read
+    // as none at all. Lookups then miss and iteration reports it and ends,
+    // where following the node could panic or go round forever.
+    fn try_read(body: &'a [u8], offset: u32) -> Option<Self> {
+        let mut input = body.get(offset as usize..)?;
+        let (&tag, rest) = input.split_first()?;
+        input = rest;
+
+        let take = |input: &mut &'a [u8], len: usize| {
+            if input.len() < len {
+                return None;
+            }
+
+            let (taken, rest) = input.split_at(len);
+            *input = rest;
+            Some(taken)
+        };
+
+        let first = read_varint(&mut input)? as usize;
+        let first = take(&mut input, first)?;
+
+        match tag {
+            LEAF => {
+                let value = read_varint(&mut input)? as usize;
+
+                Some(FrozenNode::Leaf {
+                    remaining_key: first,
+                    value: take(&mut input, value)?,
+                })
+            }
+            _ if tag & !HAS_EMPTY == INTERIOR => {
+                let child_count = read_varint(&mut input)? as usize;
+                let empty_child = if tag & HAS_EMPTY != 0 {
+                    Some(read_fixed(take(&mut input, 4)?) as u32)
+                } else {
+                    None
+                };
+
+                let branches = take(&mut input, child_count)?;
+                let offsets = take(&mut input, child_count * 4)?;
+
+                // Children are written first, so every walk moves back through
+                // the buffer
+                if empty_child.is_some_and(|child| child >= offset)
+                    || (0..child_count).any(|idx| child_offset(offsets, idx) >= offset)
+                {
+                    return None;
+                }
+
+                Some(FrozenNode::Interior {
+                    prefix: first,
+                    empty_child,
+                    branches,
+                    offsets,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+fn child_offset(offsets: &[u8], idx: usize) -> u32 {
+    read_fixed(&offsets[idx * 4..idx * 4 + 4]) as u32
+}
+
+// A tree in the frozen layout, read in place from `bytes`. Opening it only
+// checks the header and the checksum, nothing is copied or decoded until it
+// is looked up.
+pub struct FrozenRadixTree<'a, V> {
+    body: &'a [u8],
+    root: Option<u32>,
+    len: usize,
+    value_type: PhantomData<fn() -> V>,
+}
+
+impl<'a, V: FrozenValue> FrozenRadixTree<'a, V> {
+    pub fn new(bytes: &'a [u8]) -> Result<Self> {
+        let tree = FrozenRadixTree::new_unverified(bytes)?;
+
+        if crc32(tree.body) != read_fixed(&bytes[24..28]) as u32 {
+            corrupt!("frozen tree checksum does not match its nodes");
+        }
+
+        Ok(tree)
+    }
+
+    // Skips the checksum of the nodes, which would read the whole buffer.
+    // Damaged nodes are then found as they are read, and end the lookup or
+    // iteration that reached them.
+    //
+    // Values are decoded as they are read too, and one that does not decode
+    // as `V`, from a damaged buffer or one written with another type, is an
+    // error from `get` or the iterators.
+    pub fn new_unverified(bytes: &'a [u8]) -> Result<Self> {
+        if bytes.len() < HEADER_LEN {
+            corrupt!("frozen tree needs a {} byte header, found {} bytes", HEADER_LEN, bytes.len());
+        }
+
+        let (header, body) = bytes.split_at(HEADER_LEN);
+        if header[..4] != MAGIC[..] {
+            corrupt!("frozen tree magic is missing");
+        }
+        if crc32(&header[..28]) != read_fixed(&header[28..32]) as u32 {
+            corrupt!("frozen tree header checksum does not match");
+        }
+
+        let version = read_fixed(&header[4..6]) as u16;
+        if version != VERSION {
+            corrupt!("frozen tree version {} is not supported, expected {}", version, VERSION);
+        }
+
+        let body_len = read_fixed(&header[20..24]) as usize;
+        if body_len != body.len() {
+            corrupt!("frozen tree header expects {} bytes of nodes, found {}", body_len, body.len());
+        }
+
+        let len = read_fixed(&header[8..16]);
+        let root = read_fixed(&header[16..20]) as u32;
+        if (root == NO_ROOT) != (len == 0) || (root != NO_ROOT && root as usize >= body.len()) {
+            corrupt!("frozen tree root offset {} does not fit {} keys", root, len);
+        }
+
+        Ok(FrozenRadixTree {
+            body,
+            root: if root == NO_ROOT { None } else { Some(root) },
+            len: len as usize,
+            value_type: PhantomData,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // The encoded value for `key`, borrowed from the buffer
+    pub fn get_bytes(&self, key: &[u8]) -> Option<&'a [u8]> {
+        let mut offset = self.root?;
+        let mut remaining = key;
+
+        loop {
+            match FrozenNode::try_read(self.body, offset)? {
+                FrozenNode::Leaf {
+                    remaining_key,
+                    value,
+                } => return if remaining_key == remaining { Some(value) } else { None },
+                FrozenNode::Interior {
+                    prefix,
+                    empty_child,
+                    branches,
+                    offsets,
+                } => {
+                    if !remaining.starts_with(prefix) {
+                        return None;
+                    }
+
+                    remaining = &remaining[prefix.len()..];
+                    match remaining.split_first() {
+                        Some((next_char, rest)) => {
+                            let idx = branches.binary_search(next_char).ok()?;
+                            offset = child_offset(offsets, idx);
+                            remaining = rest;
+                        }
+                        None => offset = empty_child?,
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn get(&self, key: &[u8]) -> Result<Option<V>> {
+        match self.get_bytes(key) {
+            Some(bytes) => V::decode_value(bytes).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    pub fn contains_key(&self, key: &[u8]) -> bool {
+        self.get_bytes(key).is_some()
+    }
+
+    pub fn iter(&self) -> FrozenIter<'a, V> {
+        FrozenIter::seek(self.body, self.root, Bound::Unbounded, Bound::Unbounded)
+    }
+
+    pub fn range<K: AsRef<[u8]>, R: RangeBounds<K>>(&self, range: R) -> FrozenIter<'a, V> {
+        let upper = owned_bound(bytes_bound(range.end_bound()));
+
+        FrozenIter::seek(self.body, self.root, bytes_bound(range.start_bound()), upper)
+    }
+
+    pub fn prefix_iter(&self, prefix: &[u8]) -> FrozenIter<'a, V> {
+        FrozenIter::seek(self.body, self.root, Bound::Included(prefix), prefix_upper(prefix))
+    }
+}
+
+fn bytes_bound<K: AsRef<[u8]>>(bound: Bound<&K>) -> Bound<&[u8]> {
+    match bound {
+        Bound::Included(key) => Bound::Included(key.as_ref()),
+        Bound::Excluded(key) => Bound::Excluded(key.as_ref()),
+        Bound::Unbounded => Bound::Unbounded,
+    }
+}
+
+// Yields keys in order, rebuilt from the path through the buffer, with their
+// values or the error decoding them
+pub struct FrozenIter<'a, V> {
+    body: &'a [u8],
+    pending: Vec<(u32, usize, Option<u8>)>,
+    path: Vec<u8>,
+    upper: Bound<Vec<u8>>,
+    value_type: PhantomData<fn() -> V>,
+}
+
+impl<'a, V: FrozenValue> FrozenIter<'a, V> {
+    // Walks down towards `lower`, leaving every node that sorts after it on
+    // the stack, the same as the walker over boxed nodes
+    fn seek(body: &'a [u8], root: Option<u32>, lower: Bound<&[u8]>, upper: Bound<Vec<u8>>) -> Self {
+        let mut iter = FrozenIter {
+            body,
+            pending: Vec::new(),
+            path: Vec::new(),
+            upper,
+            value_type: PhantomData,
+        };
+        let (mut remaining, inclusive) = match lower {
+            Bound::Included(lower) => (lower, true),
+            Bound::Excluded(lower) => (lower, false),
+            Bound::Unbounded => {
+                iter.pending.extend(root.map(|root| (root, 0, None)));
+                return iter;
+            }
+        };
+        let mut current = match root {
+            Some(root) => root,
+            None => return iter,
+        };
+
+        loop {
+            let path_len = iter.path.len();
+
+            // A damaged node is left for `next` to report
+            let node = match FrozenNode::try_read(body, current) {
+                Some(node) => node,
+                None => {
+                    iter.pending.push((current, path_len, None));
+                    break;
+                }
+            };
+
+            match node {
+                FrozenNode::Leaf { remaining_key, .. } => {
+                    if remaining_key > remaining || (inclusive && remaining_key == remaining) {
+                        iter.pending.push((current, path_len, None));
+                    }
+
+                    break;
+                }
+                FrozenNode::Interior {
+                    prefix,
+                    empty_child,
+                    branches,
+                    offsets,
+                } => {
+                    let common = cmp::min(prefix.len(), remaining.len());
+
+                    match prefix[..common].cmp(&remaining[..common]) {
+                        cmp::Ordering::Less => break,
+                        cmp::Ordering::Greater => {
+                            iter.pending.push((current, path_len, None));
+                            break;
+                        }
+                        cmp::Ordering::Equal if prefix.len() > remaining.len() => {
+                            iter.pending.push((current, path_len, None));
+                            break;
+                        }
+                        cmp::Ordering::Equal => {}
+                    }
+
+                    iter.path.extend_from_slice(prefix);
+                    remaining = &remaining[prefix.len()..];
+                    let path_len = iter.path.len();
+
+                    match remaining.split_first() {
+                        Some((&next_char, rest)) => {
+                            iter.push_children(branches, offsets, path_len, Some(next_char));
+
+                            match branches.binary_search(&next_char) {
+                                Ok(idx) => {
+                                    iter.path.push(next_char);
+                                    current = child_offset(offsets, idx);
+                                    remaining = rest;
+                                }
+                                Err(_) => break,
+                            }
+                        }
+                        None => {
+                            iter.push_children(branches, offsets, path_len, None);
+
+                            if inclusive {
+                                iter.pending.extend(empty_child.map(|empty_child| (empty_child, path_len, None)));
+                            }
+
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+
+        iter
+    }
+
+    fn push_children(&mut self, branches: &[u8], offsets: &[u8], path_len: usize, after: Option<u8>) {
+        let first_child = branches.iter().position(|&branch| after.is_none_or(|after| branch > after));
+
+        if let Some(first_child) = first_child {
+            self.pending.extend(
+                (first_child..branches.len())
+                    .rev()
+                    .map(|idx| (child_offset(offsets, idx), path_len, Some(branches[idx]))),
+            );
+        }
+    }
+}
+
+impl<'a, V: FrozenValue> iter::Iterator for FrozenIter<'a, V> {
+    type Item = Result<(Vec<u8>, V)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((offset, path_len, branch)) = self.pending.pop() {
+            self.path.truncate(path_len);
+            self.path.extend(branch);
+
+            let node = match FrozenNode::try_read(self.body, offset) {
+                Some(node) => node,
+                None => {
+                    self.pending.clear();
+                    return Some(Err(Error::Corrupt(format!("frozen tree node at offset {} is corrupt", offset))));
+                }
+            };
+
+            match node {
+                FrozenNode::Leaf {
+                    remaining_key,
+                    value,
+                } => {
+                    self.path.extend_from_slice(remaining_key);
+
+                    if !within_upper(&self.path, &self.upper) {
+                        self.pending.clear();
+                        return None;
+                    }
+
+                    return Some(V::decode_value(value).map(|value| (self.path.clone(), value)));
+                }
+                FrozenNode::Interior {
+                    prefix,
+                    empty_child,
+                    branches,
+                    offsets,
+                } => {
+                    self.path.extend_from_slice(prefix);
+
+                    let path_len = self.path.len();
+                    self.push_children(branches, offsets, path_len, None);
+                    self.pending.extend(empty_child.map(|empty_child| (empty_child, path_len, None)));
+                }
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod frozen_tests {
+    use super::*;
+    use super::super::tree::RadixTree;
+
+    fn sample_tree() -> RadixTree<Vec<u8>, u32> {
+        ["romane", "romanus", "romulus", "rubens", "ruber", "rubicon", "rubicundus", "rom", "r"]
+            .iter()
+            .enumerate()
+            .map(|(idx, key)| (key.as_bytes().to_vec(), idx as u32))
+            .collect()
+    }
+
+    fn keys<V: FrozenValue>(iter: FrozenIter<V>) -> Vec<String> {
+        iter.map(|entry| String::from_utf8(entry.unwrap().0).unwrap()).collect()
+    }
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn varint_round_trip() {
+        for &value in [0, 1, 0x7f, 0x80, 300, u32::MAX as u64, u64::MAX].iter() {
+            let mut buffer = Vec::new();
+            write_varint(&mut buffer, value);
+
+            let mut input = &buffer[..];
+            assert_eq!(read_varint(&mut input), Some(value));
+            assert!(input.is_empty());
+        }
+
+        assert_eq!(read_varint(&mut &[0x80, 0x80][..]), None);
+    }
+
+    #[test]
+    fn lookups_match_tree() {
+        let tree = sample_tree();
+        let bytes = tree.to_frozen().unwrap();
+        let frozen = FrozenRadixTree::<u32>::new(&bytes).unwrap();
+
+        assert_eq!(frozen.len(), tree.len());
+        for (key, value) in tree.iter() {
+            assert_eq!(frozen.get(key).unwrap(), Some(*value));
+        }
+        for key in ["", "ro", "roman", "romanes", "rubicundusx", "s"].iter() {
+            assert_eq!(frozen.get(key.as_bytes()).unwrap(), None);
+        }
+
+        let entries = frozen.iter().collect::<Result<Vec<_>>>().unwrap();
+        let expected = tree.iter().map(|(key, value)| (key.clone(), *value)).collect::<Vec<_>>();
+        assert_eq!(entries, expected);
+    }
+
+    #[test]
+    fn prefix_and_range_scans() {
+        let bytes = sample_tree().to_frozen().unwrap();
+        let frozen = FrozenRadixTree::<u32>::new(&bytes).unwrap();
+
+        assert_eq!(keys(frozen.prefix_iter(b"rom")), ["rom", "romane", "romanus", "romulus"]);
+        assert_eq!(keys(frozen.prefix_iter(b"rubi")), ["rubicon", "rubicundus"]);
+        assert!(keys(frozen.prefix_iter(b"x")).is_empty());
+        assert_eq!(
+            keys(frozen.range(&b"roman"[..]..&b"rubens"[..])),
+            ["romane", "romanus", "romulus"]
+        );
+        assert_eq!(
+            keys(frozen.range((Bound::Excluded(b"rubens".to_vec()), Bound::Included(b"rubicon".to_vec())))),
+            ["ruber", "rubicon"]
+        );
+        assert_eq!(keys(frozen.range(..&b"rom"[..])), ["r"]);
+    }
+
+    #[test]
+    fn empty_tree() {
+        let bytes = RadixTree::<Vec<u8>, String>::new().to_frozen().unwrap();
+        let frozen = FrozenRadixTree::<String>::new(&bytes).unwrap();
+
+        assert_eq!(bytes.len(), HEADER_LEN);
+        assert!(frozen.is_empty());
+        assert_eq!(frozen.get(b"").unwrap(), None);
+        assert_eq!(frozen.iter().count(), 0);
+    }
+
+    #[test]
+    fn damaged_buffers_are_rejected() {
+        let bytes = sample_tree().to_frozen().unwrap();
+
+        for idx in 0..bytes.len() {
+            let mut damaged = bytes.clone();
+            damaged[idx] ^= 0x10;
+
+            assert!(FrozenRadixTree::<u32>::new(&damaged).is_err(), "flipped byte {}", idx);
+        }
+
+        assert!(FrozenRadixTree::<u32>::new(&bytes[..bytes.len() - 1]).is_err());
+        assert!(FrozenRadixTree::<u32>::new(&bytes[..HEADER_LEN - 1]).is_err());
+    }
+
+    // A tree whose checksums hold but whose only node is an interior node with
+    // itself as its empty child
+    #[test]
+    fn cyclic_nodes_are_not_followed() {
+        let body = vec![INTERIOR | HAS_EMPTY, 0, 0, 0, 0, 0, 0];
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(MAGIC);
+        write_fixed(&mut bytes, VERSION as u64, 2);
+        write_fixed(&mut bytes, 0, 2);
+        write_fixed(&mut bytes, 1, 8);
+        write_fixed(&mut bytes, 0, 4);
+        write_fixed(&mut bytes, body.len() as u64, 4);
+        write_fixed(&mut bytes, crc32(&body) as u64, 4);
+        let header_crc = crc32(&bytes);
+        write_fixed(&mut bytes, header_crc as u64, 4);
+        bytes.extend_from_slice(&body);
+
+        let frozen = FrozenRadixTree::<u32>::new(&bytes).unwrap();
+        assert_eq!(frozen.get(b"").unwrap(), None);
+        assert!(frozen.iter().next().unwrap().is_err());
+        assert!(frozen.iter().nth(1).is_none());
+        assert!(frozen.prefix_iter(b"").next().unwrap().is_err());
+    }
+
+    #[test]
+    fn other_versions_are_rejected() {
+        let mut bytes = sample_tree().to_frozen().unwrap();
+        bytes[4] = 2;
+        let header_crc = crc32(&bytes[..28]);
+        bytes[28..32].copy_from_slice(&[header_crc as u8, (header_crc >> 8) as u8, (header_crc >> 16) as u8, (header_crc >> 24) as u8]);
+
+        let err = FrozenRadixTree::<u32>::new(&bytes).err().unwrap();
+        assert_eq!(err.to_string(), "frozen tree version 2 is not supported, expected 1");
+    }
+
+    #[test]
+    fn mismatched_values_are_errors() {
+        let bytes = sample_tree().to_frozen().unwrap();
+        let frozen = FrozenRadixTree::<u32>::new(&bytes).unwrap();
+        assert_eq!(frozen.get(b"rubens").unwrap(), Some(3));
+        assert_eq!(frozen.get(b"rub").unwrap(), None);
+
+        let wrong_type = FrozenRadixTree::<u64>::new(&bytes).unwrap();
+        match wrong_type.get(b"rubens") {
+            Err(Error::Corrupt(message)) => assert_eq!(message, "u64 value needs 8 bytes, found 4"),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(wrong_type.iter().all(|entry| entry.is_err()));
+        assert_eq!(wrong_type.prefix_iter(b"rub").count(), 4);
+    }
+}
@@ -1,23 +1,58 @@
-use std::borrow::Borrow;
-use std::cmp;
-use std::fmt;
-use std::str;
-
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct KeyPrefix {
-    prefix: Box<[u8]>
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cmp;
+use core::fmt;
+use core::ops::Bound;
+use core::str;
+
+use allocator_api2::alloc::{Allocator, Global};
+use bytes::Bytes;
+
+use super::shared::SharedBytes;
+
+// Prefixes share the storage of the key they were cut from, so splitting,
+// popping and creating them from a probe over a shared key do not copy
+pub struct KeyPrefix<A: Allocator = Global> {
+    prefix: SharedBytes<A>,
 }
 
-impl KeyPrefix {
+impl KeyPrefix<Global> {
     pub fn new(key_bytes: &[u8]) -> KeyPrefix {
         KeyPrefix {
-            prefix: Box::from(key_bytes),
+            prefix: SharedBytes::copy_from_slice(key_bytes),
         }
     }
 
-    pub fn empty() -> KeyPrefix {
+    pub fn join(&self, branch: Option<u8>, child: &KeyPrefix) -> KeyPrefix {
+        self.join_in(branch, child, Global)
+    }
+}
+
+impl<A: Allocator> KeyPrefix<A> {
+    // The bytes `start..end` of a shared key, without copying them
+    pub fn shared(key: &SharedBytes<A>, start: usize, end: usize) -> KeyPrefix<A> {
+        KeyPrefix {
+            prefix: key.slice(start, end),
+        }
+    }
+
+    // Shares the bytes of a probe over a shared key, or copies them into
+    // `alloc` when the probe has no owner
+    pub fn from_probe_in(probe: KeyProbe<A>, alloc: A) -> KeyPrefix<A> {
+        match probe.owner {
+            Some(owner) => KeyPrefix {
+                prefix: owner.slice_ref(probe.key_portion),
+            },
+            None => KeyPrefix {
+                prefix: SharedBytes::copy_from_slice_in(probe.key_portion, alloc),
+            },
+        }
+    }
+
+    pub fn empty() -> KeyPrefix<A> {
         KeyPrefix {
-            prefix: Box::new([]),
+            prefix: SharedBytes::new(),
         }
     }
 
@@ -25,38 +60,54 @@ impl KeyPrefix {
         self.prefix.len()
     }
 
+    // Bytes of the prefix held on the heap. A prefix may share its buffer
+    // with other prefixes, so this counts each one as if it owned its bytes.
+    pub fn heap_bytes(&self) -> usize {
+        self.prefix.len()
+    }
+
     pub fn is_empty(&self) -> bool {
-        self.prefix.len() == 0
+        self.prefix.is_empty()
     }
 
     pub fn bytes(&self) -> &[u8] {
-        self.prefix.borrow()
+        &self.prefix
     }
 
-    // This operation will copy the data
-    // FUTURE WORK: implement a method that will split without
-    // needing to copy
-    pub fn split_at(self, idx: usize) -> (KeyPrefix, KeyPrefix) {
-        let borrowed: &[u8] = self.prefix.borrow();
-        let (left, right) = borrowed.split_at(idx);
-        (KeyPrefix::new(left), KeyPrefix::new(right))
+    pub fn split_at(self, idx: usize) -> (KeyPrefix<A>, KeyPrefix<A>) {
+        let mut left = self.prefix;
+        let right = left.split_off(idx);
+
+        (KeyPrefix { prefix: left }, KeyPrefix { prefix: right })
     }
 
-    // Also inefficient
     pub fn pop(&mut self) -> Option<u8> {
         if !self.prefix.is_empty() {
-            let mut prefix_vec = self.prefix.to_vec();
-            let first_value = prefix_vec.pop().unwrap();
+            let first_value = self.prefix[0];
 
-            self.prefix = prefix_vec.into_boxed_slice();
+            self.prefix.advance(1);
             Some(first_value)
         } else {
             None
         }
     }
 
-    pub fn match_with<'a>(&self, probe: KeyProbe<'a>) -> KeyMatchResult<'a> {
-        let byte_prefix: &[u8] = self.prefix.borrow();
+    // Builds the prefix formed by following a branch byte from this prefix
+    // into a child with the given prefix, in a buffer taken from `alloc`
+    pub fn join_in(&self, branch: Option<u8>, child: &KeyPrefix<A>, alloc: A) -> KeyPrefix<A> {
+        let branch = branch.map(|branch| [branch]);
+        let branch_bytes: &[u8] = match branch {
+            Some(ref branch) => branch,
+            None => &[],
+        };
+
+        KeyPrefix {
+            prefix: SharedBytes::concat_in(&[self.bytes(), branch_bytes, child.bytes()], alloc),
+        }
+    }
+
+    pub fn match_with<'a>(&self, probe: KeyProbe<'a, A>) -> KeyMatchResult<'a, A> {
+        let byte_prefix: &[u8] = &self.prefix;
         let is_prefix = probe.bytes().starts_with(byte_prefix);
 
         if is_prefix {
@@ -81,38 +132,55 @@ impl KeyPrefix {
         }
     }
 
-    fn diff_index<'a>(&self, probe: &KeyProbe<'a>) -> Option<usize> {
+    fn diff_index<'a>(&self, probe: &KeyProbe<'a, A>) -> Option<usize> {
         let max_len = cmp::max(self.len(), probe.len());
-        let prefix_bytes: &[u8] = self.prefix.borrow();
+        let prefix_bytes: &[u8] = &self.prefix;
         let probe_bytes: &[u8] = probe.bytes();
 
-        for idx in 0..max_len {
-            if idx >= self.len() || idx >= probe.len() || prefix_bytes[idx] != probe_bytes[idx] {
-                return Some(idx);
-            }
-        }
-
-        None
+        (0..max_len).find(|&idx| idx >= self.len() || idx >= probe.len() || prefix_bytes[idx] != probe_bytes[idx])
     }
 }
 
 impl<'a> From<KeyProbe<'a>> for KeyPrefix {
     fn from(src: KeyProbe<'a>) -> Self {
-        KeyPrefix::new(src.bytes())
+        KeyPrefix::from_probe_in(src, Global)
+    }
+}
+
+// Written out rather than derived, which would require the same traits of
+// the allocator
+impl<A: Allocator> Clone for KeyPrefix<A> {
+    fn clone(&self) -> Self {
+        KeyPrefix {
+            prefix: self.prefix.clone(),
+        }
     }
 }
 
-impl fmt::Display for KeyPrefix {
+impl<A: Allocator> PartialEq for KeyPrefix<A> {
+    fn eq(&self, other: &Self) -> bool {
+        self.prefix == other.prefix
+    }
+}
+
+impl<A: Allocator> Eq for KeyPrefix<A> {}
+
+impl<A: Allocator> fmt::Debug for KeyPrefix<A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("KeyPrefix").field("prefix", &self.prefix).finish()
+    }
+}
+
+impl<A: Allocator> fmt::Display for KeyPrefix<A> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match str::from_utf8(self.prefix.borrow()) {
+        match str::from_utf8(&self.prefix) {
             Ok(val) => write!(f, "{}", val),
             Err(_) => Err(fmt::Error),
         }
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub enum KeyMatchResult<'a> {
+pub enum KeyMatchResult<'a, A: Allocator = Global> {
     // Occurs when the prefix matches the start of the probe completely,
     // and the prefix is also shorter than the probe
     // Returns the end of the probe that did not match
@@ -120,7 +188,7 @@ pub enum KeyMatchResult<'a> {
     // Prefix: "ABC"
     // Probe:  "ABCDEF"
     // The result should be Partial("DEF")
-    Partial(KeyProbe<'a>),
+    Partial(KeyProbe<'a, A>),
 
     // Occurs when the prefix and the probe are the same length and are equal
     // Returns nothing as the probe has been completely consumed
@@ -157,47 +225,96 @@ pub enum KeyMatchResult<'a> {
     // Prefix: "ABCDEF"
     // Probe:  "ABZ"
     // The result should be Incomplete(2, "Z")
-    Incomplete(usize, KeyProbe<'a>),
+    Incomplete(usize, KeyProbe<'a, A>),
+}
+
+impl<'a, A: Allocator> fmt::Debug for KeyMatchResult<'a, A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            KeyMatchResult::Partial(ref probe) => f.debug_tuple("Partial").field(probe).finish(),
+            KeyMatchResult::Complete => f.write_str("Complete"),
+            KeyMatchResult::LongerPrefix(split_index) => f.debug_tuple("LongerPrefix").field(&split_index).finish(),
+            KeyMatchResult::Incomplete(split_index, ref probe) => {
+                f.debug_tuple("Incomplete").field(&split_index).field(probe).finish()
+            }
+        }
+    }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct KeyProbe<'a> {
+impl<'a, A: Allocator> PartialEq for KeyMatchResult<'a, A> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (&KeyMatchResult::Partial(ref left), &KeyMatchResult::Partial(ref right)) => left == right,
+            (&KeyMatchResult::Complete, &KeyMatchResult::Complete) => true,
+            (&KeyMatchResult::LongerPrefix(left), &KeyMatchResult::LongerPrefix(right)) => left == right,
+            (&KeyMatchResult::Incomplete(left_index, ref left), &KeyMatchResult::Incomplete(right_index, ref right)) => {
+                left_index == right_index && left == right
+            }
+            _ => false,
+        }
+    }
+}
+
+impl<'a, A: Allocator> Eq for KeyMatchResult<'a, A> {}
+
+pub struct KeyProbe<'a, A: 'a + Allocator = Global> {
     key_portion: &'a [u8],
+    // When set, `key_portion` is a part of these bytes and prefixes made
+    // from the probe point into them instead of copying
+    owner: Option<&'a SharedBytes<A>>,
 }
 
-impl<'a> KeyProbe<'a> {
+impl<'a, A: Allocator> KeyProbe<'a, A> {
     pub fn new<K>(key: &'a K) -> Self
     where
         K: TreeKey,
     {
+        KeyProbe::from_bytes(key.as_bytes())
+    }
+
+    pub fn from_bytes(key_portion: &'a [u8]) -> Self {
         KeyProbe {
-            key_portion: key.as_bytes(),
+            key_portion,
+            owner: None,
+        }
+    }
+
+    pub fn shared(key_bytes: &'a SharedBytes<A>) -> Self {
+        KeyProbe {
+            key_portion: key_bytes,
+            owner: Some(key_bytes),
         }
     }
 
     pub fn empty() -> Self {
-        KeyProbe { key_portion: &[] }
+        KeyProbe {
+            key_portion: &[],
+            owner: None,
+        }
     }
 
     pub fn len(&self) -> usize {
         self.key_portion.len()
     }
 
+    #[cfg(test)]
     pub fn is_empty(&self) -> bool {
-        self.key_portion.len() == 0
+        self.key_portion.is_empty()
     }
 
     pub fn bytes(&self) -> &[u8] {
         self.key_portion
     }
 
-    pub fn split_at(self, idx: usize) -> (KeyProbe<'a>, KeyProbe<'a>) {
+    pub fn split_at(self, idx: usize) -> (KeyProbe<'a, A>, KeyProbe<'a, A>) {
         let (left_bytes, right_bytes) = self.key_portion.split_at(idx);
         let left = KeyProbe {
             key_portion: left_bytes,
+            owner: self.owner,
         };
         let right = KeyProbe {
             key_portion: right_bytes,
+            owner: self.owner,
         };
 
         (left, right)
@@ -213,7 +330,33 @@ impl<'a> KeyProbe<'a> {
     }
 }
 
-impl<'a> fmt::Display for KeyProbe<'a> {
+impl<'a, A: Allocator> fmt::Debug for KeyProbe<'a, A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("KeyProbe")
+            .field("key_portion", &self.key_portion)
+            .field("owner", &self.owner)
+            .finish()
+    }
+}
+
+impl<'a, A: Allocator> Clone for KeyProbe<'a, A> {
+    fn clone(&self) -> Self {
+        KeyProbe {
+            key_portion: self.key_portion,
+            owner: self.owner,
+        }
+    }
+}
+
+impl<'a, A: Allocator> PartialEq for KeyProbe<'a, A> {
+    fn eq(&self, other: &Self) -> bool {
+        self.key_portion == other.key_portion
+    }
+}
+
+impl<'a, A: Allocator> Eq for KeyProbe<'a, A> {}
+
+impl<'a, A: Allocator> fmt::Display for KeyProbe<'a, A> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match str::from_utf8(self.key_portion) {
             Ok(val) => write!(f, "{}", val),
@@ -222,6 +365,45 @@ impl<'a> fmt::Display for KeyProbe<'a> {
     }
 }
 
+// Helpers for range and prefix scans, which compare keys by their bytes
+pub fn key_bound<K: TreeKey>(bound: Bound<&K>) -> Bound<&[u8]> {
+    match bound {
+        Bound::Included(key) => Bound::Included(key.as_bytes()),
+        Bound::Excluded(key) => Bound::Excluded(key.as_bytes()),
+        Bound::Unbounded => Bound::Unbounded,
+    }
+}
+
+pub fn owned_bound(bound: Bound<&[u8]>) -> Bound<Vec<u8>> {
+    match bound {
+        Bound::Included(key_bytes) => Bound::Included(key_bytes.to_vec()),
+        Bound::Excluded(key_bytes) => Bound::Excluded(key_bytes.to_vec()),
+        Bound::Unbounded => Bound::Unbounded,
+    }
+}
+
+// Every key starting with `prefix` is below the prefix with its last byte
+// that can be incremented incremented and the rest dropped
+pub fn prefix_upper(prefix: &[u8]) -> Bound<Vec<u8>> {
+    match prefix.iter().rposition(|&byte| byte != u8::MAX) {
+        Some(last) => {
+            let mut upper = prefix[..last + 1].to_vec();
+            upper[last] += 1;
+
+            Bound::Excluded(upper)
+        }
+        None => Bound::Unbounded,
+    }
+}
+
+pub fn within_upper(key_bytes: &[u8], upper: &Bound<Vec<u8>>) -> bool {
+    match *upper {
+        Bound::Included(ref upper) => key_bytes <= &upper[..],
+        Bound::Excluded(ref upper) => key_bytes < &upper[..],
+        Bound::Unbounded => true,
+    }
+}
+
 #[cfg(test)]
 mod key_prefix_tests {
     use super::*;
@@ -248,10 +430,49 @@ mod key_prefix_tests {
         assert_eq!(right.bytes(), b"DEFG");
     }
 
+    #[test]
+    fn pop_prefix() {
+        let mut prefix = KeyPrefix::new(b"ABC");
+
+        assert_eq!(prefix.pop(), Some(b'A'));
+        assert_eq!(prefix.bytes(), b"BC");
+        assert_eq!(prefix.pop(), Some(b'B'));
+        assert_eq!(prefix.pop(), Some(b'C'));
+        assert!(prefix.is_empty());
+        assert_eq!(prefix.pop(), None);
+    }
+
+    #[test]
+    fn join_prefix() {
+        let parent = KeyPrefix::new(b"AB");
+        let child = KeyPrefix::new(b"DE");
+
+        assert_eq!(parent.join(Some(b'C'), &child).bytes(), b"ABCDE");
+        assert_eq!(parent.join(None, &KeyPrefix::empty()).bytes(), b"AB");
+    }
+
+    #[test]
+    fn prefix_from_shared_probe() {
+        let key = SharedBytes::copy_from_slice(b"a key long enough to share its buffer");
+        let mut probe = KeyProbe::shared(&key);
+
+        assert_eq!(probe.pop(), Some(b'a'));
+        let (_, right) = probe.split_at(5);
+        let prefix = KeyPrefix::from(right);
+
+        assert_eq!(prefix.bytes(), b"long enough to share its buffer");
+        assert_eq!(prefix.bytes().as_ptr(), key[6..].as_ptr());
+
+        let (left, mut right) = prefix.split_at(4);
+        assert_eq!(left.bytes().as_ptr(), key[6..].as_ptr());
+        assert_eq!(right.pop(), Some(b' '));
+        assert_eq!(right.bytes().as_ptr(), key[11..].as_ptr());
+    }
+
     #[test]
     fn prefix_match_empty() {
         let prefix_a = KeyPrefix::new(b"");
-        let probe_a = KeyProbe::new(&"ABC");
+        let probe_a: KeyProbe = KeyProbe::new(&"ABC");
 
         let match_result = prefix_a.match_with(probe_a);
         assert_eq!(match_result, KeyMatchResult::Partial(KeyProbe::new(&"ABC")));
@@ -260,7 +481,7 @@ mod key_prefix_tests {
     #[test]
     fn prefix_match_partial() {
         let prefix_a = KeyPrefix::new(b"ABC");
-        let probe_a = KeyProbe::new(&"ABCDEF");
+        let probe_a: KeyProbe = KeyProbe::new(&"ABCDEF");
 
         let match_result = prefix_a.match_with(probe_a);
         assert_eq!(match_result, KeyMatchResult::Partial(KeyProbe::new(&"DEF")));
@@ -269,7 +490,7 @@ mod key_prefix_tests {
     #[test]
     fn prefix_match_full() {
         let prefix_a = KeyPrefix::new(b"ABCDEF");
-        let probe_a = KeyProbe::new(&"ABCDEF");
+        let probe_a: KeyProbe = KeyProbe::new(&"ABCDEF");
 
         let match_result = prefix_a.match_with(probe_a);
         assert_eq!(match_result, KeyMatchResult::Complete);
@@ -278,7 +499,7 @@ mod key_prefix_tests {
     #[test]
     fn prefix_match_longer_prefix() {
         let prefix_a = KeyPrefix::new(b"ABCDEFGHI");
-        let probe_a = KeyProbe::new(&"ABCDEF");
+        let probe_a: KeyProbe = KeyProbe::new(&"ABCDEF");
 
         let match_result = prefix_a.match_with(probe_a);
         assert_eq!(match_result, KeyMatchResult::LongerPrefix(6));
@@ -287,7 +508,7 @@ mod key_prefix_tests {
     #[test]
     fn prefix_match_incomplete() {
         let prefix_a = KeyPrefix::new(b"ABZDEF");
-        let probe_a = KeyProbe::new(&"ABCDEF");
+        let probe_a: KeyProbe = KeyProbe::new(&"ABCDEF");
 
         let match_result = prefix_a.match_with(probe_a);
         assert_eq!(
@@ -296,7 +517,7 @@ mod key_prefix_tests {
         );
 
         let prefix_b = KeyPrefix::new(b"ABCDEF");
-        let probe_b = KeyProbe::new(&"GHIJKL");
+        let probe_b: KeyProbe = KeyProbe::new(&"GHIJKL");
 
         let match_result = prefix_b.match_with(probe_b);
         assert_eq!(
@@ -321,14 +542,14 @@ mod key_probe_tests {
 
     #[test]
     fn new_probe() {
-        let probe = KeyProbe::new(&"ABC");
+        let probe: KeyProbe = KeyProbe::new(&"ABC");
         assert_eq!(probe.len(), 3);
         assert_eq!(probe.bytes(), b"ABC");
     }
 
     #[test]
     fn empty_probe() {
-        let probe = KeyProbe::new(&"");
+        let probe: KeyProbe = KeyProbe::new(&"");
 
         assert!(probe.is_empty());
         assert_eq!(probe.len(), 0);
@@ -337,7 +558,7 @@ mod key_probe_tests {
 
     #[test]
     fn probe_split() {
-        let probe = KeyProbe::new(&"ABCDEFG");
+        let probe: KeyProbe = KeyProbe::new(&"ABCDEFG");
         assert_eq!(probe.len(), 7);
         assert_eq!(probe.bytes(), b"ABCDEFG");
 
@@ -351,14 +572,14 @@ mod key_probe_tests {
 
     #[test]
     fn probe_split_extrema() {
-        let probe_a = KeyProbe::new(&"ABCDEFG");
+        let probe_a: KeyProbe = KeyProbe::new(&"ABCDEFG");
         let (left_a, right_a) = probe_a.split_at(0);
         assert!(left_a.is_empty());
 
         assert_eq!(right_a.len(), 7);
         assert_eq!(right_a.bytes(), b"ABCDEFG");
 
-        let probe_b = KeyProbe::new(&"ABCDEFG");
+        let probe_b: KeyProbe = KeyProbe::new(&"ABCDEFG");
         let (left_b, right_b) = probe_b.split_at(7);
         assert_eq!(left_b.len(), 7);
         assert_eq!(left_b.bytes(), b"ABCDEFG");
@@ -368,9 +589,9 @@ mod key_probe_tests {
 
     #[test]
     fn probe_pop() {
-        let mut probe = KeyProbe::new(&"ABCDEFG");
+        let mut probe: KeyProbe = KeyProbe::new(&"ABCDEFG");
         let mut reversed = Vec::new();
-        while probe.len() > 0 {
+        while !probe.is_empty() {
             reversed.insert(0, probe.pop().unwrap() as char);
         }
 
@@ -380,7 +601,6 @@ mod key_probe_tests {
 
 pub trait TreeKey: Clone + PartialEq + Eq {
     fn as_bytes(&self) -> &[u8];
-    // fn from_bytes(key_bytes: &[u8]) -> Self;
 }
 
 impl<T> TreeKey for T
@@ -391,3 +611,35 @@ where
         self.as_ref()
     }
 }
+
+// Keys that own their bytes and can be built back from them, which lets a
+// tree drop the stored copy of each key and rebuild it from the path instead
+pub trait RebuildableKey: TreeKey {
+    fn from_bytes(key_bytes: &[u8]) -> Self;
+}
+
+impl RebuildableKey for Vec<u8> {
+    fn from_bytes(key_bytes: &[u8]) -> Self {
+        key_bytes.to_vec()
+    }
+}
+
+impl RebuildableKey for Box<[u8]> {
+    fn from_bytes(key_bytes: &[u8]) -> Self {
+        Box::from(key_bytes)
+    }
+}
+
+impl RebuildableKey for Bytes {
+    fn from_bytes(key_bytes: &[u8]) -> Self {
+        Bytes::copy_from_slice(key_bytes)
+    }
+}
+
+// Only ever called with the bytes of a key that was inserted, so they are
+// known to be valid UTF-8
+impl RebuildableKey for String {
+    fn from_bytes(key_bytes: &[u8]) -> Self {
+        String::from_utf8(key_bytes.to_vec()).expect("Rebuilt key was not valid UTF-8")
+    }
+}
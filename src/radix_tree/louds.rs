@@ -0,0 +1,362 @@
+use alloc::vec::Vec;
+use alloc::collections::VecDeque;
+use core::iter;
+use core::mem;
+
+use allocator_api2::alloc::Allocator;
+use allocator_api2::boxed::Box;
+
+use super::bits::{BitBuilder, BitVector};
+use super::node::RadixNode;
+use super::entry::LeafEntry;
+
+// A static set of keys in a few bit vectors and byte arrays, keeping the shape
+// of a radix tree without any pointers. Every key gets an id in `0..len`,
+// which can index values kept elsewhere.
+//
+// Nodes are numbered breadth first, with the root as 0, and the tree shape is
+// the level order unary degree sequence (LOUDS): "10" for a virtual parent of
+// the root, then for each node a 1 for every child followed by a 0. The
+// children of node `x` are the ones between the `x`th and `x + 1`th zero, and
+// the node for the one at position `p` is `rank1(p)`.
+//
+// Each node but the root is entered by an edge labelled with one byte, kept
+// in `labels`, and the rest of the compressed edge is its tail. Only nodes
+// with a tail are marked in `has_tail`, and tails are packed end to end with
+// their starts marked in `tail_starts`. A node ending a key is marked in
+// `terminal`, and its rank there is the key id.
+#[derive(Debug, Clone)]
+pub struct LoudsTrie {
+    louds: BitVector,
+    labels: Vec<u8>,
+    has_tail: BitVector,
+    tail_starts: BitVector,
+    tails: Vec<u8>,
+    terminal: BitVector,
+}
+
+// Lays out the nodes of a radix tree. An interior node becomes a LOUDS node
+// with its branching children, and its empty child only marks it terminal.
+pub fn build_louds<E: LeafEntry, A: Allocator + Clone>(root: Option<&Box<RadixNode<E, A>, A>>) -> LoudsTrie {
+    let mut louds = BitBuilder::new();
+    let mut labels = Vec::new();
+    let mut has_tail = BitBuilder::new();
+    let mut tail_starts = BitBuilder::new();
+    let mut tails = Vec::new();
+    let mut terminal = BitBuilder::new();
+
+    louds.push(true);
+    louds.push(false);
+
+    let mut pending = root.into_iter().map(|root| (&**root, 0)).collect::<VecDeque<_>>();
+    while let Some((node, label)) = pending.pop_front() {
+        let (tail, is_terminal) = match *node {
+            RadixNode::Leaf(ref leaf) => (leaf.remaining_key().bytes(), true),
+            RadixNode::Interior(ref interior) => {
+                let children = interior.children();
+
+                for (branch, child) in children.iter() {
+                    louds.push(true);
+                    pending.push_back((&**child, branch));
+                }
+
+                (interior.prefix().bytes(), children.contains_empty())
+            }
+        };
+
+        louds.push(false);
+        labels.push(label);
+        has_tail.push(!tail.is_empty());
+        terminal.push(is_terminal);
+
+        for idx in 0..tail.len() {
+            tail_starts.push(idx == 0);
+        }
+        tails.extend_from_slice(tail);
+    }
+
+    // Closes the last tail, so tail `k` always ends at the start of `k + 1`
+    tail_starts.push(true);
+    labels.shrink_to_fit();
+    tails.shrink_to_fit();
+
+    LoudsTrie {
+        louds: louds.finish(),
+        labels,
+        has_tail: has_tail.finish(),
+        tail_starts: tail_starts.finish(),
+        tails,
+        terminal: terminal.finish(),
+    }
+}
+
+impl LoudsTrie {
+    pub fn len(&self) -> usize {
+        self.terminal.count_ones()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn node_count(&self) -> usize {
+        self.labels.len()
+    }
+
+    fn tail(&self, node: usize) -> &[u8] {
+        if !self.has_tail.get(node) {
+            return &[];
+        }
+
+        let tail = self.has_tail.rank1(node);
+        let start = self.tail_starts.select1(tail).expect(concat!(file!(), ": ", line!()));
+        let end = self.tail_starts.select1(tail + 1).expect(concat!(file!(), ": ", line!()));
+
+        &self.tails[start..end]
+    }
+
+    // The first child of `node` and how many children it has
+    fn children(&self, node: usize) -> (usize, usize) {
+        let start = self.louds.select0(node).expect(concat!(file!(), ": ", line!())) + 1;
+        let end = self.louds.select0(node + 1).expect(concat!(file!(), ": ", line!()));
+
+        (start - node - 1, end - start)
+    }
+
+    fn child(&self, node: usize, label: u8) -> Option<usize> {
+        let (first_child, count) = self.children(node);
+
+        self.labels[first_child..first_child + count]
+            .binary_search(&label)
+            .ok()
+            .map(|idx| first_child + idx)
+    }
+
+    fn parent(&self, node: usize) -> usize {
+        let position = self.louds.select1(node).expect(concat!(file!(), ": ", line!()));
+
+        self.louds.rank0(position) - 1
+    }
+
+    fn key_id(&self, node: usize) -> Option<usize> {
+        if self.terminal.get(node) {
+            Some(self.terminal.rank1(node))
+        } else {
+            None
+        }
+    }
+
+    // The id of `key`, if it is in the trie
+    pub fn lookup(&self, key: &[u8]) -> Option<usize> {
+        if self.node_count() == 0 {
+            return None;
+        }
+
+        let mut node = 0;
+        let mut remaining = key;
+
+        loop {
+            let tail = self.tail(node);
+            if !remaining.starts_with(tail) {
+                return None;
+            }
+
+            match remaining[tail.len()..].split_first() {
+                Some((&label, rest)) => {
+                    node = self.child(node, label)?;
+                    remaining = rest;
+                }
+                None => return self.key_id(node),
+            }
+        }
+    }
+
+    pub fn contains(&self, key: &[u8]) -> bool {
+        self.lookup(key).is_some()
+    }
+
+    // The key with id `id`, rebuilt by walking up to the root
+    pub fn key(&self, id: usize) -> Option<Vec<u8>> {
+        let mut node = self.terminal.select1(id)?;
+        let mut key = Vec::new();
+
+        loop {
+            key.extend(self.tail(node).iter().rev());
+
+            if node == 0 {
+                break;
+            }
+
+            key.push(self.labels[node]);
+            node = self.parent(node);
+        }
+
+        key.reverse();
+        Some(key)
+    }
+
+    pub fn iter<'a>(&'a self) -> LoudsIter<'a> {
+        self.prefix_iter(&[])
+    }
+
+    // Every key starting with `prefix` and its id, in key order
+    pub fn prefix_iter<'a>(&'a self, prefix: &[u8]) -> LoudsIter<'a> {
+        let mut iter = LoudsIter {
+            trie: self,
+            pending: Vec::new(),
+            path: Vec::new(),
+        };
+        if self.node_count() == 0 {
+            return iter;
+        }
+
+        let mut node = 0;
+        let mut remaining = prefix;
+
+        loop {
+            let tail = self.tail(node);
+
+            // The prefix ends on the edge into `node`, every key below it
+            // matches
+            if remaining.len() <= tail.len() {
+                if tail.starts_with(remaining) {
+                    iter.path.extend_from_slice(tail);
+                    iter.pending.push((node, iter.path.len(), false));
+                }
+
+                return iter;
+            }
+
+            if !remaining.starts_with(tail) {
+                return iter;
+            }
+
+            let label = remaining[tail.len()];
+            match self.child(node, label) {
+                Some(child) => {
+                    iter.path.extend_from_slice(tail);
+                    iter.path.push(label);
+                    remaining = &remaining[tail.len() + 1..];
+                    node = child;
+                }
+                None => return iter,
+            }
+        }
+    }
+
+    // Bytes held by the bit vectors and arrays
+    pub fn heap_bytes(&self) -> usize {
+        mem::size_of::<Self>() + self.louds.heap_bytes() + self.labels.capacity() + self.has_tail.heap_bytes()
+            + self.tail_starts.heap_bytes() + self.tails.capacity() + self.terminal.heap_bytes()
+    }
+}
+
+// Walks the nodes depth first with an explicit stack, children in label order
+pub struct LoudsIter<'a> {
+    trie: &'a LoudsTrie,
+    // A node, the length of the key before its edge, and whether its edge
+    // still needs to be added to the key
+    pending: Vec<(usize, usize, bool)>,
+    path: Vec<u8>,
+}
+
+impl<'a> iter::Iterator for LoudsIter<'a> {
+    type Item = (Vec<u8>, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((node, path_len, add_edge)) = self.pending.pop() {
+            self.path.truncate(path_len);
+
+            if add_edge {
+                self.path.push(self.trie.labels[node]);
+                self.path.extend_from_slice(self.trie.tail(node));
+            }
+
+            let (first_child, count) = self.trie.children(node);
+            let path_len = self.path.len();
+            self.pending
+                .extend((first_child..first_child + count).rev().map(|child| (child, path_len, true)));
+
+            if let Some(id) = self.trie.key_id(node) {
+                return Some((self.path.clone(), id));
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod louds_tests {
+    use super::super::tree::RadixTree;
+
+    const KEYS: &[&str] = &[
+        "romane", "romanus", "romulus", "rubens", "ruber", "rubicon", "rubicundus", "rom", "r",
+    ];
+
+    fn sample_tree() -> RadixTree<Vec<u8>, ()> {
+        KEYS.iter().map(|key| (key.as_bytes().to_vec(), ())).collect()
+    }
+
+    fn strings<I: Iterator<Item = (Vec<u8>, usize)>>(iter: I) -> Vec<String> {
+        iter.map(|(key, _)| String::from_utf8(key).unwrap()).collect()
+    }
+
+    #[test]
+    fn ids_map_both_ways() {
+        let trie = sample_tree().to_louds();
+        let mut ids = KEYS.iter().map(|key| trie.lookup(key.as_bytes()).unwrap()).collect::<Vec<_>>();
+
+        for (key, &id) in KEYS.iter().zip(ids.iter()) {
+            assert_eq!(trie.key(id), Some(key.as_bytes().to_vec()));
+        }
+
+        ids.sort();
+        assert_eq!(ids, (0..KEYS.len()).collect::<Vec<_>>());
+        assert_eq!(trie.len(), KEYS.len());
+        assert_eq!(trie.key(KEYS.len()), None);
+
+        for key in ["", "ro", "roma", "romanes", "rubicundusx", "s"].iter() {
+            assert_eq!(trie.lookup(key.as_bytes()), None);
+        }
+    }
+
+    #[test]
+    fn prefix_enumeration() {
+        let trie = sample_tree().to_louds();
+
+        let mut sorted = KEYS.to_vec();
+        sorted.sort();
+        assert_eq!(strings(trie.iter()), sorted);
+        assert!(trie.iter().all(|(key, id)| trie.key(id) == Some(key)));
+
+        assert_eq!(strings(trie.prefix_iter(b"rom")), ["rom", "romane", "romanus", "romulus"]);
+        assert_eq!(strings(trie.prefix_iter(b"roma")), ["romane", "romanus"]);
+        assert_eq!(strings(trie.prefix_iter(b"rubicu")), ["rubicundus"]);
+        assert!(strings(trie.prefix_iter(b"rx")).is_empty());
+        assert!(strings(trie.prefix_iter(b"rubicundusx")).is_empty());
+    }
+
+    #[test]
+    fn empty_and_single_key() {
+        let empty = RadixTree::<Vec<u8>, ()>::new().to_louds();
+        assert!(empty.is_empty());
+        assert_eq!(empty.lookup(b""), None);
+        assert_eq!(empty.iter().count(), 0);
+
+        let mut tree = RadixTree::new();
+        tree.insert(b"only".to_vec(), ());
+        let single = tree.to_louds();
+        assert_eq!(single.lookup(b"only"), Some(0));
+        assert_eq!(single.lookup(b"on"), None);
+        assert_eq!(single.key(0), Some(b"only".to_vec()));
+        assert_eq!(strings(single.prefix_iter(b"on")), ["only"]);
+
+        let mut tree = RadixTree::new();
+        tree.insert(Vec::new(), ());
+        tree.insert(b"a".to_vec(), ());
+        let with_empty = tree.to_louds();
+        assert_eq!(with_empty.key(with_empty.lookup(b"").unwrap()), Some(Vec::new()));
+        assert_eq!(strings(with_empty.iter()), ["", "a"]);
+    }
+}
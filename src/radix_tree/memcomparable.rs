@@ -0,0 +1,385 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cmp;
+use core::fmt;
+use core::hash;
+use core::mem;
+#[cfg(feature = "std")]
+use std::time::SystemTime;
+
+use error::Result;
+use super::key::RebuildableKey;
+use super::numeric::{F32Key, F64Key, I32Key, I64Key, U32Key, U64Key};
+#[cfg(feature = "std")]
+use super::numeric::TimestampKey;
+
+// Memcomparable encoding of composite keys. Each part is encoded so that
+// comparing the bytes of two encodings gives the same order as comparing the
+// parts, and the encoding of a tuple is the concatenation of its parts. The
+// encoding of the leading parts of a tuple is then a byte prefix of the whole
+// key, so a prefix scan selects every key that starts with those parts.
+//
+// Integers are fixed width big endian, with the sign bit of signed integers
+// flipped. Strings and byte strings are variable length, so they are escaped
+// and terminated: a 0x00 byte is written as 0x00 0xFF and the end of the
+// string as 0x00 0x01, which sorts a string before any of its extensions.
+
+const ESCAPE: u8 = 0x00;
+const ESCAPED_ZERO: u8 = 0xFF;
+const TERMINATOR: u8 = 0x01;
+
+pub trait KeyPart: Clone + Eq {
+    fn encode_part(&self, buffer: &mut Vec<u8>);
+
+    // Reads one part from the front of `input`, advancing it past the part
+    fn decode_part(input: &mut &[u8]) -> Result<Self>;
+}
+
+fn take_fixed<'a>(input: &mut &'a [u8], width: usize) -> Result<&'a [u8]> {
+    if input.len() < width {
+        corrupt!("key part needs {} bytes, only {} left", width, input.len());
+    }
+
+    let (part, rest) = input.split_at(width);
+    *input = rest;
+
+    Ok(part)
+}
+
+macro_rules! integer_part {
+    ($int:ty, $uint:ty, $flip:expr) => {
+        impl KeyPart for $int {
+            fn encode_part(&self, buffer: &mut Vec<u8>) {
+                buffer.extend_from_slice(&((*self as $uint) ^ $flip).to_be_bytes());
+            }
+
+            fn decode_part(input: &mut &[u8]) -> Result<Self> {
+                let mut bytes = [0; mem::size_of::<$int>()];
+                bytes.copy_from_slice(take_fixed(input, mem::size_of::<$int>())?);
+
+                Ok((<$uint>::from_be_bytes(bytes) ^ $flip) as $int)
+            }
+        }
+    };
+}
+
+integer_part!(u8, u8, 0);
+integer_part!(u16, u16, 0);
+integer_part!(u32, u32, 0);
+integer_part!(u64, u64, 0);
+integer_part!(i8, u8, 1 << 7);
+integer_part!(i16, u16, 1 << 15);
+integer_part!(i32, u32, 1 << 31);
+integer_part!(i64, u64, 1 << 63);
+
+impl KeyPart for bool {
+    fn encode_part(&self, buffer: &mut Vec<u8>) {
+        buffer.push(*self as u8);
+    }
+
+    fn decode_part(input: &mut &[u8]) -> Result<Self> {
+        match take_fixed(input, 1)?[0] {
+            0 => Ok(false),
+            1 => Ok(true),
+            other => corrupt!("invalid bool key part {}", other),
+        }
+    }
+}
+
+// The numeric keys already have an order-preserving fixed width encoding
+macro_rules! numeric_key_part {
+    ($name:ident, $width:expr) => {
+        impl KeyPart for $name {
+            fn encode_part(&self, buffer: &mut Vec<u8>) {
+                buffer.extend_from_slice(self.as_ref());
+            }
+
+            fn decode_part(input: &mut &[u8]) -> Result<Self> {
+                Ok($name::from_bytes(take_fixed(input, $width)?))
+            }
+        }
+    };
+}
+
+numeric_key_part!(U32Key, 4);
+numeric_key_part!(U64Key, 8);
+numeric_key_part!(I32Key, 4);
+numeric_key_part!(I64Key, 8);
+numeric_key_part!(F32Key, 4);
+numeric_key_part!(F64Key, 8);
+#[cfg(feature = "std")]
+numeric_key_part!(TimestampKey, 12);
+
+#[cfg(feature = "std")]
+impl KeyPart for SystemTime {
+    fn encode_part(&self, buffer: &mut Vec<u8>) {
+        TimestampKey::new(*self).encode_part(buffer);
+    }
+
+    fn decode_part(input: &mut &[u8]) -> Result<Self> {
+        TimestampKey::decode_part(input).map(|timestamp| timestamp.value())
+    }
+}
+
+fn encode_escaped(bytes: &[u8], buffer: &mut Vec<u8>) {
+    for &byte in bytes {
+        buffer.push(byte);
+
+        if byte == ESCAPE {
+            buffer.push(ESCAPED_ZERO);
+        }
+    }
+
+    buffer.push(ESCAPE);
+    buffer.push(TERMINATOR);
+}
+
+fn decode_escaped(input: &mut &[u8]) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let mut idx = 0;
+
+    loop {
+        match input.get(idx).cloned() {
+            Some(ESCAPE) => match input.get(idx + 1).cloned() {
+                Some(ESCAPED_ZERO) => bytes.push(ESCAPE),
+                Some(TERMINATOR) => break,
+                Some(other) => corrupt!("invalid escape 0x00 0x{:02x} in key part", other),
+                None => corrupt!("unterminated key part"),
+            },
+            Some(byte) => {
+                bytes.push(byte);
+                idx += 1;
+                continue;
+            }
+            None => corrupt!("unterminated key part"),
+        }
+
+        idx += 2;
+    }
+
+    *input = &input[idx + 2..];
+
+    Ok(bytes)
+}
+
+impl KeyPart for Vec<u8> {
+    fn encode_part(&self, buffer: &mut Vec<u8>) {
+        encode_escaped(self, buffer);
+    }
+
+    fn decode_part(input: &mut &[u8]) -> Result<Self> {
+        decode_escaped(input)
+    }
+}
+
+impl KeyPart for String {
+    fn encode_part(&self, buffer: &mut Vec<u8>) {
+        encode_escaped(self.as_bytes(), buffer);
+    }
+
+    fn decode_part(input: &mut &[u8]) -> Result<Self> {
+        match String::from_utf8(decode_escaped(input)?) {
+            Ok(part) => Ok(part),
+            Err(_) => corrupt!("string key part is not utf8"),
+        }
+    }
+}
+
+pub trait KeyTuple: Clone + Eq {
+    fn encode_tuple(&self, buffer: &mut Vec<u8>);
+
+    fn decode_tuple(input: &mut &[u8]) -> Result<Self>;
+}
+
+macro_rules! key_tuple {
+    ($($part:ident),+) => {
+        impl<$($part: KeyPart),+> KeyTuple for ($($part,)+) {
+            #[allow(non_snake_case)]
+            fn encode_tuple(&self, buffer: &mut Vec<u8>) {
+                let ($(ref $part,)+) = *self;
+
+                $($part.encode_part(buffer);)+
+            }
+
+            fn decode_tuple(input: &mut &[u8]) -> Result<Self> {
+                Ok(($($part::decode_part(input)?,)+))
+            }
+        }
+    };
+}
+
+key_tuple!(A);
+key_tuple!(A, B);
+key_tuple!(A, B, C);
+key_tuple!(A, B, C, D);
+key_tuple!(A, B, C, D, E);
+key_tuple!(A, B, C, D, E, F);
+
+// A tuple together with its encoding. The key of a tree holding these is the
+// encoding, and the encoding of a tuple of just the leading parts is the
+// prefix to scan for every key starting with them.
+#[derive(Clone)]
+pub struct TupleKey<T> {
+    parts: T,
+    bytes: Vec<u8>,
+}
+
+impl<T: KeyTuple> TupleKey<T> {
+    pub fn new(parts: T) -> Self {
+        let mut bytes = Vec::new();
+        parts.encode_tuple(&mut bytes);
+
+        TupleKey { parts, bytes }
+    }
+
+    // Decodes a whole key, failing if any bytes are left over
+    pub fn decode(key_bytes: &[u8]) -> Result<Self> {
+        let mut input = key_bytes;
+        let parts = T::decode_tuple(&mut input)?;
+
+        if !input.is_empty() {
+            corrupt!("{} bytes left over after decoding key", input.len());
+        }
+
+        Ok(TupleKey {
+            parts,
+            bytes: key_bytes.to_vec(),
+        })
+    }
+
+    pub fn parts(&self) -> &T {
+        &self.parts
+    }
+
+    pub fn into_parts(self) -> T {
+        self.parts
+    }
+}
+
+impl<T: KeyTuple> From<T> for TupleKey<T> {
+    fn from(parts: T) -> Self {
+        TupleKey::new(parts)
+    }
+}
+
+impl<T> AsRef<[u8]> for TupleKey<T> {
+    fn as_ref(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+impl<T: KeyTuple> RebuildableKey for TupleKey<T> {
+    fn from_bytes(key_bytes: &[u8]) -> Self {
+        TupleKey::decode(key_bytes).expect(concat!(file!(), ": ", line!()))
+    }
+}
+
+// Equal tuples have equal encodings, and the order is the order of the
+// encodings so that it agrees with the tree
+impl<T> PartialEq for TupleKey<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.bytes == other.bytes
+    }
+}
+
+impl<T> Eq for TupleKey<T> {}
+
+impl<T> PartialOrd for TupleKey<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for TupleKey<T> {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        self.bytes.cmp(&other.bytes)
+    }
+}
+
+impl<T> hash::Hash for TupleKey<T> {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        self.bytes.hash(state);
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for TupleKey<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "TupleKey{:?}", self.parts)
+    }
+}
+
+#[cfg(test)]
+mod memcomparable_tests {
+    use super::*;
+
+    fn encode<T: KeyTuple>(parts: T) -> Vec<u8> {
+        TupleKey::new(parts).as_ref().to_vec()
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn parts_round_trip() {
+        let parts = (
+            -7i32,
+            String::from("a\u{0}b"),
+            vec![0u8, 0xFF, 0, 1],
+            u64::MAX,
+            true,
+            SystemTime::now(),
+        );
+        let key = TupleKey::new(parts.clone());
+
+        assert_eq!(TupleKey::<(i32, String, Vec<u8>, u64, bool, SystemTime)>::decode(key.as_ref()).unwrap(), key);
+        assert_eq!(key.into_parts(), parts);
+        assert_eq!(
+            TupleKey::<(u32, String)>::from_bytes(&encode((3u32, String::from("x")))).parts(),
+            &(3, String::from("x"))
+        );
+    }
+
+    #[test]
+    fn strings_sort_before_extensions() {
+        let ordered = ["", "\u{0}", "\u{0}\u{0}", "\u{1}", "a", "a\u{0}", "a\u{0}b", "ab", "b"];
+
+        for pair in ordered.windows(2) {
+            let (low, high) = (encode((pair[0].to_string(), 0u8)), encode((pair[1].to_string(), 0u8)));
+
+            assert!(low < high, "{:?} !< {:?}", pair[0], pair[1]);
+        }
+    }
+
+    #[test]
+    fn tuples_sort_by_parts() {
+        let ordered = [
+            (-1i64, String::from("zed"), 9u32),
+            (0, String::from(""), 0),
+            (0, String::from("a"), 1),
+            (0, String::from("a"), 2),
+            (0, String::from("ab"), 0),
+            (1, String::from(""), 0),
+        ];
+
+        for pair in ordered.windows(2) {
+            assert!(TupleKey::new(pair[0].clone()) < TupleKey::new(pair[1].clone()));
+        }
+    }
+
+    #[test]
+    fn leading_parts_are_prefixes() {
+        let full = encode((42u32, String::from("user"), 7u64));
+
+        assert!(full.starts_with(&encode((42u32,))));
+        assert!(full.starts_with(&encode((42u32, String::from("user")))));
+        assert!(!full.starts_with(&encode((42u32, String::from("use")))));
+    }
+
+    #[test]
+    fn malformed_keys_fail_to_decode() {
+        assert!(TupleKey::<(u32,)>::decode(&[0, 0, 1]).is_err());
+        assert!(TupleKey::<(u8,)>::decode(&[0, 0]).is_err());
+        assert!(TupleKey::<(String,)>::decode(b"abc").is_err());
+        assert!(TupleKey::<(String,)>::decode(&[b'a', 0, 2]).is_err());
+        assert!(TupleKey::<(String,)>::decode(&[0xC3, 0, 1]).is_err());
+        assert!(TupleKey::<(bool,)>::decode(&[2]).is_err());
+    }
+}
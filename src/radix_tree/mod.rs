@@ -1,9 +1,79 @@
 mod tree;
-pub use self::tree::RadixTree;
+pub use self::tree::{Iter, RadixTree, RebuiltIter};
+
+// The allocator interface trees are generic over, for `RadixTree::new_in`
+pub use allocator_api2::alloc::{AllocError, Allocator, Global};
+
+mod set;
+pub use self::set::{RadixSet, SetIter, SetOperation};
+
+mod multimap;
+pub use self::multimap::{MultiIter, RadixMultiMap};
+
+mod routing;
+pub use self::routing::{Covering, IpNet, IpRoutingTable, Ipv4Net, Ipv6Net, RouteIter};
+
+#[cfg(feature = "std")]
+mod concurrent;
+#[cfg(feature = "std")]
+pub use self::concurrent::ConcurrentRadixTree;
+
+#[cfg(feature = "std")]
+mod rcu;
+#[cfg(feature = "std")]
+pub use self::rcu::{RcuRadixTree, RcuReader, RcuSnapshot, SnapshotIter};
+
+mod arena;
+pub use self::arena::ArenaRadixTree;
+
+mod frozen;
+pub use self::frozen::{FrozenIter, FrozenRadixTree, FrozenValue};
+
+#[cfg(feature = "std")]
+mod paged;
+#[cfg(feature = "std")]
+pub use self::paged::{PagedIter, PagedRadixTree};
+
+#[cfg(feature = "std")]
+mod durable;
+#[cfg(feature = "std")]
+pub use self::durable::DurableRadixTree;
+
+mod louds;
+pub use self::louds::{LoudsIter, LoudsTrie};
+
+mod bits;
+
+mod dot;
+
+mod stats;
+pub use self::stats::TreeStats;
+
+mod validate;
+pub use self::validate::{InvariantViolation, ViolationKind};
 
 mod key;
-pub use self::key::TreeKey;
+pub use self::key::{RebuildableKey, TreeKey};
+
+mod numeric;
+pub use self::numeric::{F32Key, F64Key, I32Key, I64Key, U32Key, U64Key};
+#[cfg(feature = "std")]
+pub use self::numeric::TimestampKey;
+
+mod memcomparable;
+pub use self::memcomparable::{KeyPart, KeyTuple, TupleKey};
 
 mod entry;
+pub use self::entry::{KeyStorage, RebuiltKeys, StoredKeys};
+
+#[cfg(feature = "serde")]
+mod serde_impls;
+
+mod children;
+
+mod shared;
+
+mod alphabet;
+pub use self::alphabet::{BitAlphabet, ByteAlphabet, KeyAlphabet, NibbleAlphabet, Symbols};
 
 mod node;
\ No newline at end of file
@@ -0,0 +1,309 @@
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::fmt;
+use core::iter;
+use core::mem;
+use core::ops::Bound;
+use core::slice;
+
+use allocator_api2::boxed::Box;
+
+use super::shared::SharedBytes;
+use super::key::{KeyPrefix, KeyProbe, TreeKey, prefix_upper, within_upper};
+use super::node::{RadixNode, LeafWalker, iterative_insert, iterative_find, iterative_mut_find, iterative_remove,
+                  iterative_drop};
+use super::entry::{KeyValue, LeafEntry};
+
+// The values of one key. Most keys of an index hold a single value, which is
+// kept inline, a list is only allocated once a second value arrives.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ValueList<V> {
+    One(V),
+    Many(Vec<V>),
+}
+
+impl<V> ValueList<V> {
+    fn as_slice(&self) -> &[V] {
+        match *self {
+            ValueList::One(ref value) => slice::from_ref(value),
+            ValueList::Many(ref values) => values,
+        }
+    }
+
+    fn push(&mut self, value: V) {
+        if let ValueList::Many(ref mut values) = *self {
+            values.push(value);
+            return;
+        }
+
+        if let ValueList::One(first) = mem::replace(self, ValueList::Many(Vec::new())) {
+            *self = ValueList::Many(vec![first, value]);
+        }
+    }
+
+    fn into_vec(self) -> Vec<V> {
+        match self {
+            ValueList::One(value) => vec![value],
+            ValueList::Many(values) => values,
+        }
+    }
+}
+
+type MultiEntry<K, V> = Box<KeyValue<K, ValueList<V>>>;
+
+// Maps each key to a list of values, kept in the order they were inserted.
+// The length counts every value, not the number of keys.
+pub struct RadixMultiMap<K: TreeKey, V> {
+    size: usize,
+    key_count: usize,
+    root: Option<Box<RadixNode<MultiEntry<K, V>>>>,
+}
+
+impl<K: TreeKey, V> RadixMultiMap<K, V> {
+    pub fn new() -> Self {
+        RadixMultiMap {
+            size: 0,
+            key_count: 0,
+            root: None,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    pub fn key_count(&self) -> usize {
+        self.key_count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    pub fn clear(&mut self) {
+        if let Some(root) = self.root.take() {
+            iterative_drop(root);
+        }
+
+        self.size = 0;
+        self.key_count = 0;
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: TreeKey + AsRef<[u8]>,
+    {
+        !self.get_all(key).is_empty()
+    }
+
+    // The values of the key in insertion order, empty if the key is absent
+    pub fn get_all<Q>(&self, key: &Q) -> &[V]
+    where
+        K: Borrow<Q>,
+        Q: TreeKey + AsRef<[u8]>,
+    {
+        match self.root {
+            Some(ref root) => match iterative_find(root, KeyProbe::new(&key)) {
+                Some(values) => values.as_slice(),
+                None => &[],
+            },
+            None => &[],
+        }
+    }
+
+    // Appends the value to the values of the key
+    pub fn insert(&mut self, key: K, value: V) {
+        if let Some(ref mut root) = self.root {
+            if let Some(values) = iterative_mut_find(root, KeyProbe::new(&key)) {
+                values.push(value);
+                self.size += 1;
+                return;
+            }
+        }
+
+        let shared_key = SharedBytes::copy_from_slice(key.as_bytes());
+        let new_entry = Box::new(KeyValue::new(key, ValueList::One(value)));
+
+        if let Some(ref mut root) = self.root {
+            iterative_insert(root, KeyProbe::shared(&shared_key), new_entry);
+        } else {
+            let new_leaf = RadixNode::new_leaf(KeyPrefix::from(KeyProbe::shared(&shared_key)), new_entry);
+            self.root = Some(Box::new(new_leaf));
+        }
+
+        self.size += 1;
+        self.key_count += 1;
+    }
+
+    // Removes the first value of the key equal to `value`, and the key with
+    // it if that was its last value
+    pub fn remove_one<Q>(&mut self, key: &Q, value: &V) -> bool
+    where
+        K: Borrow<Q>,
+        Q: TreeKey + AsRef<[u8]>,
+        V: PartialEq,
+    {
+        let remove_key = match self.root {
+            Some(ref mut root) => match iterative_mut_find(root, KeyProbe::new(&key)) {
+                Some(&mut ValueList::One(ref only)) if only == value => true,
+                Some(&mut ValueList::Many(ref mut values)) => match values.iter().position(|other| other == value) {
+                    Some(idx) if values.len() > 1 => {
+                        values.remove(idx);
+                        self.size -= 1;
+                        return true;
+                    }
+                    Some(_) => true,
+                    None => return false,
+                },
+                _ => return false,
+            },
+            None => return false,
+        };
+
+        if remove_key {
+            self.remove_all(key);
+        }
+
+        remove_key
+    }
+
+    // Removes the key, returning all of its values in insertion order
+    pub fn remove_all<Q>(&mut self, key: &Q) -> Vec<V>
+    where
+        K: Borrow<Q>,
+        Q: TreeKey,
+    {
+        match iterative_remove(&mut self.root, KeyProbe::new(key)) {
+            Some(values) => {
+                let values = values.into_vec();
+                self.size -= values.len();
+                self.key_count -= 1;
+
+                values
+            }
+            None => Vec::new(),
+        }
+    }
+
+    pub fn iter<'a>(&'a self) -> MultiIter<'a, K, V> {
+        MultiIter {
+            walker: LeafWalker::new(self.root.as_ref(), false),
+            upper: Bound::Unbounded,
+        }
+    }
+
+    pub fn prefix_iter<'a>(&'a self, prefix: &[u8]) -> MultiIter<'a, K, V> {
+        MultiIter {
+            walker: LeafWalker::seek(self.root.as_ref(), Bound::Included(prefix), false),
+            upper: prefix_upper(prefix),
+        }
+    }
+}
+
+impl<K: TreeKey, V> Drop for RadixMultiMap<K, V> {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+impl<K: TreeKey, V> Default for RadixMultiMap<K, V> {
+    fn default() -> Self {
+        RadixMultiMap::new()
+    }
+}
+
+impl<K: TreeKey + fmt::Debug, V: fmt::Debug> fmt::Debug for RadixMultiMap<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+// Iterates over the keys of a multimap in key order, each with all its values
+pub struct MultiIter<'a, K: 'a + TreeKey, V: 'a> {
+    walker: LeafWalker<'a, MultiEntry<K, V>>,
+    upper: Bound<Vec<u8>>,
+}
+
+impl<'a, K: 'a + TreeKey, V: 'a> iter::Iterator for MultiIter<'a, K, V> {
+    type Item = (&'a K, &'a [V]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.walker.next_leaf() {
+            Some(leaf) if within_upper(leaf.entry().key().as_bytes(), &self.upper) => {
+                let entry = leaf.entry();
+
+                Some((entry.key(), entry.value().as_slice()))
+            }
+            Some(_) => {
+                self.walker.finish();
+                None
+            }
+            None => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod radix_multimap_tests {
+    use super::*;
+
+    #[test]
+    fn insert_appends_values() {
+        let mut map = RadixMultiMap::new();
+
+        map.insert("rom", 1);
+        map.insert("romane", 2);
+        map.insert("rom", 3);
+        map.insert("rom", 1);
+
+        assert_eq!(map.len(), 4);
+        assert_eq!(map.key_count(), 2);
+        assert_eq!(map.get_all(&"rom"), &[1, 3, 1]);
+        assert_eq!(map.get_all(&"romane"), &[2]);
+        assert_eq!(map.get_all(&"roma"), &[] as &[i32]);
+        assert!(!map.contains_key(&"roma"));
+    }
+
+    #[test]
+    fn remove_one_and_all() {
+        let mut map = RadixMultiMap::new();
+
+        for &(key, value) in [("a", 1), ("ab", 2), ("ab", 3), ("ab", 2), ("b", 4)].iter() {
+            map.insert(key, value);
+        }
+
+        assert!(map.remove_one(&"ab", &2));
+        assert_eq!(map.get_all(&"ab"), &[3, 2]);
+        assert!(!map.remove_one(&"ab", &7));
+        assert!(!map.remove_one(&"c", &1));
+
+        assert!(map.remove_one(&"a", &1));
+        assert!(!map.contains_key(&"a"));
+        assert_eq!(map.key_count(), 2);
+
+        assert!(map.remove_one(&"ab", &3));
+        assert!(map.remove_one(&"ab", &2));
+        assert!(!map.contains_key(&"ab"));
+
+        assert_eq!(map.remove_all(&"b"), vec![4]);
+        assert_eq!(map.remove_all(&"b"), Vec::<i32>::new());
+        assert!(map.is_empty());
+        assert_eq!(map.key_count(), 0);
+    }
+
+    #[test]
+    fn iterate_grouped_by_key() {
+        let mut map = RadixMultiMap::new();
+
+        for &(key, value) in [("romulus", 1), ("rom", 2), ("romane", 3), ("rom", 4), ("rubens", 5)].iter() {
+            map.insert(key, value);
+        }
+
+        assert_eq!(
+            map.iter().collect::<Vec<_>>(),
+            vec![(&"rom", &[2, 4][..]), (&"romane", &[3][..]), (&"romulus", &[1][..]), (&"rubens", &[5][..])]
+        );
+        assert_eq!(map.prefix_iter(b"roma").collect::<Vec<_>>(), vec![(&"romane", &[3][..])]);
+    }
+}
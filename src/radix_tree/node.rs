@@ -1,674 +1,818 @@
-use std::mem;
-use std::fmt;
-use std::slice;
-use std::iter;
+use alloc::vec::Vec;
+use core::mem;
+use core::fmt;
+use core::cmp;
+use core::ops::Bound;
 
-use super::key::{KeyMatchResult, KeyPrefix, KeyProbe, TreeKey};
-use super::entry::KeyValue;
+use allocator_api2::alloc::{Allocator, Global};
+use allocator_api2::boxed::Box;
+use allocator_api2::vec::Vec as AllocVec;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct NodeChildren<K: TreeKey, V> {
-    children: Vec<(u8, Box<RadixNode<K, V>>)>,
-    empty_child: Option<Box<RadixNode<K, V>>>,
+use super::shared::SharedBytes;
+use super::key::{KeyMatchResult, KeyPrefix, KeyProbe, TreeKey};
+use super::entry::LeafEntry;
+use super::children::NodeChildren;
+
+// Nodes, their children and their prefixes are all allocated from `A`. The
+// comparison and formatting impls are written out below, derived ones would
+// require the same of the allocator.
+pub struct RadixInteriorNode<E, A: Allocator + Clone = Global> {
+    prefix: KeyPrefix<A>,
+    children: NodeChildren<Box<RadixNode<E, A>, A>, A>,
 }
 
-impl<K: TreeKey, V> NodeChildren<K, V> {
-    pub fn new() -> Self {
-        NodeChildren {
-            children: Vec::new(),
-            empty_child: None,
-        }
-    }
-
-    pub fn contains_child(&self, prefix: u8) -> bool {
-        let search_result = self.children
-            .binary_search_by(|&(ref value, _)| value.cmp(&prefix));
-
-        match search_result {
-            Ok(_) => true,
-            Err(_) => false,
-        }
-    }
-
-    pub fn contains_empty(&self) -> bool {
-        self.empty_child.is_some()
+impl<E, A: Allocator + Clone> RadixInteriorNode<E, A> {
+    pub fn prefix(&self) -> &KeyPrefix<A> {
+        &self.prefix
     }
 
-    pub fn get_child(&self, possible_prefix: Option<u8>) -> Option<&Box<RadixNode<K, V>>> {
-        if let Some(prefix) = possible_prefix {
-            let search_result = self.children
-                .binary_search_by(|&(ref value, _)| value.cmp(&prefix));
-
-            match search_result {
-                Ok(found_index) => {
-                    let (_, ref child) = self.children[found_index];
-                    Some(child)
-                }
-                Err(_) => None,
-            }
-        } else {
-            if let Some(ref child) = self.empty_child {
-                Some(child)
-            } else {
-                None
-            }
-        }
+    pub fn children(&self) -> &NodeChildren<Box<RadixNode<E, A>, A>, A> {
+        &self.children
     }
+}
 
-    pub fn get_child_mut(
-        &mut self,
-        possible_prefix: Option<u8>,
-    ) -> Option<&mut Box<RadixNode<K, V>>> {
-        if let Some(prefix) = possible_prefix {
-            let search_result = self.children
-                .binary_search_by(|&(ref value, _)| value.cmp(&prefix));
+pub struct RadixLeafNode<E, A: Allocator = Global> {
+    entry: E,
+    remaining_key: KeyPrefix<A>,
+}
 
-            match search_result {
-                Ok(found_index) => {
-                    let (_, ref mut child) = self.children[found_index];
-                    Some(child)
-                }
-                Err(_) => None,
-            }
-        } else {
-            if let Some(ref mut child) = self.empty_child {
-                Some(child)
-            } else {
-                None
-            }
-        }
+impl<E, A: Allocator> RadixLeafNode<E, A> {
+    pub fn entry(&self) -> &E {
+        &self.entry
     }
 
-    pub fn insert_child(
-        &mut self,
-        possible_prefix: Option<u8>,
-        new_child: Box<RadixNode<K, V>>,
-    ) -> Option<Box<RadixNode<K, V>>> {
-        if let Some(prefix) = possible_prefix {
-            let search_result = self.children
-                .binary_search_by(|&(ref value, _)| value.cmp(&prefix));
-
-            match search_result {
-                Ok(found_index) => {
-                    let (_, ref mut old_child) = self.children[found_index];
-                    let old_child = mem::replace(old_child, new_child);
-                    Some(old_child)
-                }
-                Err(insert_index) => {
-                    self.children.insert(insert_index, (prefix, new_child));
-                    None
-                }
-            }
-        } else {
-            if self.empty_child.is_some() {
-                let old_child = mem::replace(
-                    self.empty_child
-                        .as_mut()
-                        .expect(&format!("{}: {}", file!(), line!())),
-                    new_child,
-                );
-                Some(old_child)
-            } else {
-                self.empty_child = Some(new_child);
-                None
-            }
-        }
-    }
-
-    pub fn remove_child(&mut self, possible_prefix: Option<u8>) -> Option<Box<RadixNode<K, V>>> {
-        if let Some(prefix) = possible_prefix {
-            let search_result = self.children
-                .binary_search_by(|&(ref value, _)| value.cmp(&prefix));
-
-            match search_result {
-                Ok(found_index) => {
-                    let (_, child) = self.children.remove(found_index);
-                    Some(child)
-                }
-                Err(_) => None,
-            }
-        } else {
-            if self.empty_child.is_some() {
-                let old_child = mem::replace(&mut self.empty_child, None).expect(&format!(
-                    "{}: {}",
-                    file!(),
-                    line!()
-                ));
-
-                Some(old_child)
-            } else {
-                None
-            }
-        }
+    pub fn remaining_key(&self) -> &KeyPrefix<A> {
+        &self.remaining_key
     }
+}
 
-    pub fn iter<'a>(&'a self) -> ChildrenIter<'a, K, V>
-    where
-        K: 'a + TreeKey,
-        V: 'a,
-    {
-        ChildrenIter {
-            iter: self.children.iter(),
-        }
+impl<E: fmt::Debug, A: Allocator> fmt::Debug for RadixLeafNode<E, A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<{:?}> -> {:?}", self.remaining_key, self.entry)
     }
 }
 
-pub struct ChildrenIter<'a, K: 'a, V: 'a>
-where
-    K: TreeKey,
-{
-    iter: slice::Iter<'a, (u8, Box<RadixNode<K, V>>)>,
+impl<E: PartialEq, A: Allocator> PartialEq for RadixLeafNode<E, A> {
+    fn eq(&self, other: &Self) -> bool {
+        self.remaining_key == other.remaining_key && self.entry == other.entry
+    }
 }
 
-impl<'a, K: 'a, V: 'a> iter::Iterator for ChildrenIter<'a, K, V>
-where
-    K: TreeKey,
-{
-    type Item = &'a (u8, Box<RadixNode<K, V>>);
+impl<E: Eq, A: Allocator> Eq for RadixLeafNode<E, A> {}
 
-    fn next(&mut self) -> Option<Self::Item> {
-        self.iter.next()
-    }
+pub enum RadixNode<E, A: Allocator + Clone = Global> {
+    // Leaf nodes will have no children and the data value will be set.
+    // Leaf nodes can be direct descendents of only branch nodes in the
+    // scenario that there is no more key bytes after the branch byte
+    Leaf(RadixLeafNode<E, A>),
+    // Branch nodes occur in the interior of the tree.
+    // They will contain > 1 children, and the key ptr will point to
+    // an array of byte values that will prefix each child as part of the key
+    Interior(RadixInteriorNode<E, A>),
+}
 
-    fn size_hint(&self) -> (usize, Option<usize>) {
-        self.iter.size_hint()
+impl<E: fmt::Debug, A: Allocator + Clone> fmt::Debug for RadixInteriorNode<E, A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RadixInteriorNode")
+            .field("prefix", &self.prefix)
+            .field("children", &self.children)
+            .finish()
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct RadixInteriorNode<K: TreeKey, V> {
-    prefix: KeyPrefix,
-    children: NodeChildren<K, V>,
+impl<E: PartialEq, A: Allocator + Clone> PartialEq for RadixInteriorNode<E, A> {
+    fn eq(&self, other: &Self) -> bool {
+        self.prefix == other.prefix && self.children == other.children
+    }
 }
 
-#[derive(Clone, PartialEq, Eq)]
-pub struct RadixLeafNode<K: TreeKey, V> {
-    entry: Box<KeyValue<K, V>>,
-    remaining_key: KeyPrefix,
-}
+impl<E: Eq, A: Allocator + Clone> Eq for RadixInteriorNode<E, A> {}
 
-impl<K: TreeKey + fmt::Debug, V: fmt::Debug> fmt::Debug for RadixLeafNode<K, V> {
+impl<E: fmt::Debug, A: Allocator + Clone> fmt::Debug for RadixNode<E, A> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "<{:?}> -> {:?}", self.remaining_key, self.entry)
+        match *self {
+            RadixNode::Leaf(ref node) => f.debug_tuple("Leaf").field(node).finish(),
+            RadixNode::Interior(ref node) => f.debug_tuple("Interior").field(node).finish(),
+        }
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub enum RadixNode<K: TreeKey, V> {
-    // Leaf nodes will have no children and the data value will be set.
-    // Leaf nodes can be direct descendents of only branch nodes in the
-    // scenario that there is no more key bytes after the branch byte
-    Leaf(RadixLeafNode<K, V>),
-    // Branch nodes occur in the interior of the tree.
-    // They will contain > 1 children, and the key ptr will point to
-    // an array of byte values that will prefix each child as part of the key
-    Interior(RadixInteriorNode<K, V>),
+impl<E: PartialEq, A: Allocator + Clone> PartialEq for RadixNode<E, A> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (&RadixNode::Leaf(ref left), &RadixNode::Leaf(ref right)) => left == right,
+            (&RadixNode::Interior(ref left), &RadixNode::Interior(ref right)) => left == right,
+            _ => false,
+        }
+    }
 }
 
-impl<K: TreeKey, V> RadixNode<K, V> {
-    pub fn new_leaf(key: K, value: V) -> Self {
-        let key_bytes = key.as_bytes();
+impl<E: Eq, A: Allocator + Clone> Eq for RadixNode<E, A> {}
+
+impl<E: LeafEntry, A: Allocator + Clone> RadixNode<E, A> {
+    pub fn new_leaf(remaining_key: KeyPrefix<A>, entry: E) -> Self {
         RadixNode::Leaf(RadixLeafNode {
-            remaining_key: KeyPrefix::new(key_bytes),
-            entry: box KeyValue::new(key.clone(), value),
+            remaining_key,
+            entry,
         })
     }
 
+    pub fn new_interior(prefix: KeyPrefix<A>, children: NodeChildren<Box<RadixNode<E, A>, A>, A>) -> Self {
+        RadixNode::Interior(RadixInteriorNode { prefix, children })
+    }
+
     pub fn is_leaf(&self) -> bool {
-        match *self {
-            RadixNode::Leaf(_) => true,
-            _ => false,
-        }
+        matches!(*self, RadixNode::Leaf(_))
     }
 
-    pub fn get_leaf(&self) -> &RadixLeafNode<K, V> {
+    pub fn get_leaf(&self) -> &RadixLeafNode<E, A> {
         match *self {
             RadixNode::Leaf(ref node) => node,
             _ => panic!("called `RadixNode::get_leaf()` on a `Interior` node"),
         }
     }
 
-    pub fn get_leaf_mut(&mut self) -> &mut RadixLeafNode<K, V> {
+    pub fn get_leaf_mut(&mut self) -> &mut RadixLeafNode<E, A> {
         match *self {
             RadixNode::Leaf(ref mut node) => node,
             _ => panic!("called `RadixNode::get_leaf()` on a `Interior` node"),
         }
     }
 
-    pub fn unwrap_leaf(self) -> RadixLeafNode<K, V> {
+    pub fn unwrap_leaf(self) -> RadixLeafNode<E, A> {
         match self {
             RadixNode::Leaf(node) => node,
             _ => panic!("called `RadixNode::unwrap_leaf()` on a `Interior` node"),
         }
     }
 
-    pub fn is_interior(&self) -> bool {
-        match *self {
-            RadixNode::Interior(_) => true,
-            _ => false,
-        }
-    }
-
-    pub fn get_interior(&self) -> &RadixInteriorNode<K, V> {
+    pub fn get_interior(&self) -> &RadixInteriorNode<E, A> {
         match *self {
             RadixNode::Interior(ref node) => node,
             _ => panic!("called `RadixNode::get_interior()` on a `Leaf` node"),
         }
     }
 
-    pub fn get_interior_mut(&mut self) -> &mut RadixInteriorNode<K, V> {
+    pub fn get_interior_mut(&mut self) -> &mut RadixInteriorNode<E, A> {
         match *self {
             RadixNode::Interior(ref mut node) => node,
             _ => panic!("called `RadixNode::get_interior()` on a `Leaf` node"),
         }
     }
 
-    pub fn unwrap_interior(self) -> RadixInteriorNode<K, V> {
-        match self {
-            RadixNode::Interior(node) => node,
-            _ => panic!("called `RadixNode::unwrap_interior()` on a `Leaf` node"),
+    fn prefix_mut(&mut self) -> &mut KeyPrefix<A> {
+        match *self {
+            RadixNode::Leaf(ref mut node) => &mut node.remaining_key,
+            RadixNode::Interior(ref mut node) => &mut node.prefix,
         }
     }
 }
 
-pub fn recursive_insert<'a, K: TreeKey, V>(
-    current: Box<RadixNode<K, V>>,
-    probe: KeyProbe<'a>,
-    new_entry: KeyValue<K, V>,
-) -> (Box<RadixNode<K, V>>, Option<V>) {
-    match *current {
-        RadixNode::Leaf(mut node) => match node.remaining_key.match_with(probe) {
-            KeyMatchResult::Complete => {
-                let old_value = node.entry.swap_value(new_entry.take_value());
+// The tree operations below walk down from the root in a loop rather than
+// recursing once per level, so that keys which branch at every byte cannot
+// exhaust the stack.
 
-                (box RadixNode::Leaf(node), Some(old_value))
-            }
-            KeyMatchResult::Partial(mut remaining_probe) => {
-                let mut new_interior = RadixInteriorNode {
-                    children: NodeChildren::new(),
-                    prefix: node.remaining_key,
-                };
+pub fn iterative_insert<'a, E: LeafEntry, A: Allocator + Clone>(
+    root: &mut Box<RadixNode<E, A>, A>,
+    mut probe: KeyProbe<'a, A>,
+    new_entry: E,
+) -> Option<E::Value> {
+    let mut current = root;
 
-                node.remaining_key = KeyPrefix::empty();
-                new_interior
-                    .children
-                    .insert_child(None, box RadixNode::Leaf(node));
+    loop {
+        let match_result = match **current {
+            RadixNode::Leaf(ref node) => node.remaining_key.match_with(probe),
+            RadixNode::Interior(ref node) => node.prefix.match_with(probe),
+        };
 
-                let next_char_new =
-                    remaining_probe
-                        .pop()
-                        .expect(&format!("{}: {}", file!(), line!()));
-                let new_leaf: RadixNode<K, V> = RadixNode::Leaf(RadixLeafNode {
-                    remaining_key: From::from(remaining_probe),
-                    entry: box new_entry,
-                });
+        let (split_index, remaining_probe) = match match_result {
+            KeyMatchResult::Complete => {
+                if current.is_leaf() {
+                    let node = current.get_leaf_mut();
 
-                debug_assert!(!new_interior.children.contains_child(next_char_new));
-                new_interior
-                    .children
-                    .insert_child(Some(next_char_new), box new_leaf);
+                    return Some(node.entry.swap_value(new_entry.take_value()));
+                }
 
-                (box RadixNode::Interior(new_interior), None)
-            }
-            KeyMatchResult::LongerPrefix(split_index) => {
-                let (common, mut difference) = node.remaining_key.split_at(split_index);
+                let children = &mut current.get_interior_mut().children;
+                if let Some(child) = children.get_child_mut(None) {
+                    debug_assert!(child.is_leaf());
+                    debug_assert!(child.get_leaf().remaining_key.is_empty());
 
-                let mut new_interior = RadixInteriorNode {
-                    prefix: common,
-                    children: NodeChildren::new(),
-                };
+                    return Some(child.get_leaf_mut().entry.swap_value(new_entry.take_value()));
+                }
 
                 let new_leaf = RadixNode::Leaf(RadixLeafNode {
                     remaining_key: KeyPrefix::empty(),
-                    entry: box new_entry,
+                    entry: new_entry,
                 });
+                let alloc = children.allocator().clone();
+                children.insert_child(None, Box::new_in(new_leaf, alloc));
 
-                new_interior.children.insert_child(None, box new_leaf);
-
-                let next_char = difference
+                return None;
+            }
+            // The leaf key is a strict prefix of the new key, so the leaf
+            // ends up as the empty child of a new interior node
+            KeyMatchResult::Partial(remaining_probe) if current.is_leaf() => {
+                (current.get_leaf().remaining_key.len(), remaining_probe)
+            }
+            KeyMatchResult::Partial(mut remaining_probe) => {
+                let next_char = remaining_probe
                     .pop()
-                    .expect(&format!("{}: {}", file!(), line!()));
-                node.remaining_key = difference;
-                new_interior
+                    .expect(concat!(file!(), ": ", line!()));
+
+                if !current.get_interior().children.contains_child(next_char) {
+                    let alloc = Box::allocator(current).clone();
+                    let new_leaf = RadixNode::Leaf(RadixLeafNode {
+                        remaining_key: KeyPrefix::from_probe_in(remaining_probe, alloc.clone()),
+                        entry: new_entry,
+                    });
+                    current
+                        .get_interior_mut()
+                        .children
+                        .insert_child(Some(next_char), Box::new_in(new_leaf, alloc));
+
+                    return None;
+                }
+
+                current = current
+                    .get_interior_mut()
                     .children
-                    .insert_child(Some(next_char), box RadixNode::Leaf(node));
+                    .get_child_mut(Some(next_char))
+                    .expect(concat!(file!(), ": ", line!()));
+                probe = remaining_probe;
 
-                (box RadixNode::Interior(new_interior), None)
+                continue;
             }
-            KeyMatchResult::Incomplete(split_index, mut remaining_probe) => {
-                let (common, mut difference) = node.remaining_key.split_at(split_index);
+            KeyMatchResult::LongerPrefix(split_index) => (split_index, KeyProbe::empty()),
+            KeyMatchResult::Incomplete(split_index, remaining_probe) => (split_index, remaining_probe),
+        };
 
-                let mut new_interior = RadixInteriorNode {
-                    prefix: common,
-                    children: NodeChildren::new(),
-                };
+        split_node(current, split_index, remaining_probe, new_entry);
 
-                let next_char_old = difference
-                    .pop()
-                    .expect(&format!("{}: {}", file!(), line!()));
-                let next_char_new =
-                    remaining_probe
-                        .pop()
-                        .expect(&format!("{}: {}", file!(), line!()));
+        return None;
+    }
+}
 
-                node.remaining_key = difference;
-                let new_leaf = RadixLeafNode {
-                    remaining_key: From::from(remaining_probe),
-                    entry: box new_entry,
-                };
+// Replaces `current` with an interior node holding the first `split_index`
+// bytes of its prefix, with the old node and a leaf for the new entry as the
+// two children
+fn split_node<'a, E: LeafEntry, A: Allocator + Clone>(
+    current: &mut Box<RadixNode<E, A>, A>,
+    split_index: usize,
+    mut remaining_probe: KeyProbe<'a, A>,
+    new_entry: E,
+) {
+    let alloc = Box::allocator(current).clone();
+    let old_prefix = mem::replace(current.prefix_mut(), KeyPrefix::empty());
+    let (common, mut difference) = old_prefix.split_at(split_index);
+
+    let next_char_old = difference.pop();
+    debug_assert!(next_char_old.is_some() || current.is_leaf());
+    *current.prefix_mut() = difference;
+
+    let mut old_node = RadixNode::Interior(RadixInteriorNode {
+        prefix: common,
+        children: NodeChildren::new_in(alloc.clone()),
+    });
+    mem::swap(&mut **current, &mut old_node);
+
+    let next_char_new = remaining_probe.pop();
+    let new_leaf = RadixNode::Leaf(RadixLeafNode {
+        remaining_key: KeyPrefix::from_probe_in(remaining_probe, alloc.clone()),
+        entry: new_entry,
+    });
+
+    let children = &mut current.get_interior_mut().children;
+    children.insert_child(next_char_old, Box::new_in(old_node, alloc.clone()));
+    children.insert_child(next_char_new, Box::new_in(new_leaf, alloc));
+}
 
-                new_interior
-                    .children
-                    .insert_child(Some(next_char_old), box RadixNode::Leaf(node));
-                new_interior
-                    .children
-                    .insert_child(Some(next_char_new), box RadixNode::Leaf(new_leaf));
+pub fn iterative_find<'p, 'v, E: LeafEntry, A: Allocator + Clone>(
+    root: &'v Box<RadixNode<E, A>, A>,
+    mut probe: KeyProbe<'p, A>,
+) -> Option<&'v E::Value> {
+    let mut current = root;
+
+    loop {
+        match **current {
+            RadixNode::Interior(ref node) => match node.prefix.match_with(probe) {
+                KeyMatchResult::Complete => {
+                    return node.children.get_child(None).map(|child| {
+                        debug_assert!(child.is_leaf());
+                        debug_assert!(child.get_leaf().remaining_key.is_empty());
 
-                (box RadixNode::Interior(new_interior), None)
+                        child.get_leaf().entry.value()
+                    });
+                }
+                KeyMatchResult::Partial(mut remaining_probe) => {
+                    let next_char = remaining_probe
+                        .pop()
+                        .expect(concat!(file!(), ": ", line!()));
+                    match node.children.get_child(Some(next_char)) {
+                        Some(child) => {
+                            current = child;
+                            probe = remaining_probe;
+                        }
+                        None => return None,
+                    }
+                }
+                _ => return None,
+            },
+            RadixNode::Leaf(ref node) => {
+                return match node.remaining_key.match_with(probe) {
+                    KeyMatchResult::Complete => Some(node.entry.value()),
+                    _ => None,
+                };
             }
-        },
-        RadixNode::Interior(mut node) => match node.prefix.match_with(probe) {
-            KeyMatchResult::Complete => {
-                if node.children.contains_empty() {
-                    let old_node = node.children.remove_child(None).expect(&format!(
-                        "{}: {}",
-                        file!(),
-                        line!()
-                    ));
+        }
+    }
+}
 
-                    let (updated, replaced_value) =
-                        recursive_insert(old_node, KeyProbe::empty(), new_entry);
+pub fn iterative_mut_find<'p, 'v, E: LeafEntry, A: Allocator + Clone>(
+    root: &'v mut Box<RadixNode<E, A>, A>,
+    mut probe: KeyProbe<'p, A>,
+) -> Option<&'v mut E::Value> {
+    let mut current = root;
 
-                    node.children.insert_child(None, updated);
+    loop {
+        match **current {
+            RadixNode::Interior(ref mut node) => match node.prefix.match_with(probe) {
+                KeyMatchResult::Complete => {
+                    return node.children.get_child_mut(None).map(|child| {
+                        debug_assert!(child.is_leaf());
+                        debug_assert!(child.get_leaf().remaining_key.is_empty());
 
-                    (box RadixNode::Interior(node), replaced_value)
-                } else {
-                    let new_leaf = RadixNode::Leaf(RadixLeafNode {
-                        remaining_key: KeyPrefix::empty(),
-                        entry: box new_entry,
+                        child.get_leaf_mut().entry.value_mut()
                     });
-
-                    node.children.insert_child(None, box new_leaf);
-
-                    (box RadixNode::Interior(node), None)
                 }
+                KeyMatchResult::Partial(mut remaining_probe) => {
+                    let next_char = remaining_probe
+                        .pop()
+                        .expect(concat!(file!(), ": ", line!()));
+                    match node.children.get_child_mut(Some(next_char)) {
+                        Some(child) => {
+                            current = child;
+                            probe = remaining_probe;
+                        }
+                        None => return None,
+                    }
+                }
+                _ => return None,
+            },
+            RadixNode::Leaf(ref mut node) => {
+                return match node.remaining_key.match_with(probe) {
+                    KeyMatchResult::Complete => Some(node.entry.value_mut()),
+                    _ => None,
+                };
             }
+        }
+    }
+}
+
+// Leaves are removed from their parent, so the walk looks one level ahead
+// and stops at the interior node holding the matching leaf
+pub fn iterative_remove<'p, E: LeafEntry, A: Allocator + Clone>(
+    root: &mut Option<Box<RadixNode<E, A>, A>>,
+    mut probe: KeyProbe<'p, A>,
+) -> Option<E::Value> {
+    let root_is_leaf = match *root {
+        Some(ref node) => node.is_leaf(),
+        None => return None,
+    };
+
+    if root_is_leaf {
+        let is_match = matches!(
+            root.as_ref().map(|node| node.get_leaf().remaining_key.match_with(probe)),
+            Some(KeyMatchResult::Complete)
+        );
+
+        return if is_match {
+            root.take().map(|node| Box::into_inner(node).unwrap_leaf().entry.take_value())
+        } else {
+            None
+        };
+    }
+
+    let mut current = root.as_mut().expect(concat!(file!(), ": ", line!()));
+
+    loop {
+        let (branch, remaining_probe) = match current.get_interior().prefix.match_with(probe) {
+            KeyMatchResult::Complete => (None, KeyProbe::empty()),
             KeyMatchResult::Partial(mut remaining_probe) => {
                 let next_char = remaining_probe
                     .pop()
-                    .expect(&format!("{}: {}", file!(), line!()));
+                    .expect(concat!(file!(), ": ", line!()));
 
-                if node.children.contains_child(next_char) {
-                    let old_node = node.children.remove_child(Some(next_char)).expect(&format!(
-                        "{}: {}",
-                        file!(),
-                        line!()
-                    ));
+                (Some(next_char), remaining_probe)
+            }
+            _ => return None,
+        };
+
+        let is_match = match current.get_interior().children.get_child(branch) {
+            Some(child) if child.is_leaf() => {
+                match child.get_leaf().remaining_key.match_with(remaining_probe.clone()) {
+                    KeyMatchResult::Complete => true,
+                    _ => return None,
+                }
+            }
+            Some(_) => false,
+            None => return None,
+        };
+
+        if is_match {
+            let old_value = current
+                .get_interior_mut()
+                .children
+                .remove_child(branch)
+                .map(|child| Box::into_inner(child).unwrap_leaf().entry.take_value());
+            compress(current);
+
+            return old_value;
+        }
 
-                    let (updated, replaced_value) =
-                        recursive_insert(old_node, remaining_probe, new_entry);
+        current = current
+            .get_interior_mut()
+            .children
+            .get_child_mut(branch)
+            .expect(concat!(file!(), ": ", line!()));
+        probe = remaining_probe;
+    }
+}
 
-                    node.children.insert_child(Some(next_char), updated);
+// An interior node left with a single entry after a removal is replaced by
+// that entry, which takes over the prefix and the branching byte
+fn compress<E: LeafEntry, A: Allocator + Clone>(node: &mut Box<RadixNode<E, A>, A>) {
+    let only_branch = {
+        let children = &node.get_interior().children;
 
-                    (box RadixNode::Interior(node), replaced_value)
-                } else {
-                    let new_leaf = RadixNode::Leaf(RadixLeafNode {
-                        remaining_key: From::from(remaining_probe),
-                        entry: box new_entry,
-                    });
+        match (children.len(), children.contains_empty()) {
+            (0, true) => None,
+            (1, false) => children.iter().next().map(|(branch, _)| branch),
+            _ => return,
+        }
+    };
 
-                    node.children.insert_child(Some(next_char), box new_leaf);
+    let mut only_child = node.get_interior_mut()
+        .children
+        .remove_child(only_branch)
+        .expect(concat!(file!(), ": ", line!()));
+    let alloc = Box::allocator(node).clone();
+    let joined = node.get_interior().prefix.join_in(only_branch, only_child.prefix_mut(), alloc);
+    *only_child.prefix_mut() = joined;
 
-                    (box RadixNode::Interior(node), None)
-                }
-            }
-            KeyMatchResult::LongerPrefix(split_index) => {
-                let (common, mut difference) = node.prefix.split_at(split_index);
+    *node = only_child;
+}
 
-                let mut new_interior = RadixInteriorNode {
-                    prefix: common,
-                    children: NodeChildren::new(),
-                };
+// Frees a subtree without recursing, dropping a deep tree through the
+// derived drop glue would recurse once per level
+pub fn iterative_drop<E, A: Allocator + Clone>(root: Box<RadixNode<E, A>, A>) {
+    let mut pending = AllocVec::new_in(Box::allocator(&root).clone());
+    pending.push(root);
 
-                let new_leaf = RadixNode::Leaf(RadixLeafNode {
-                    entry: box new_entry,
-                    remaining_key: KeyPrefix::empty(),
-                });
+    while let Some(node) = pending.pop() {
+        if let RadixNode::Interior(node) = Box::into_inner(node) {
+            pending.extend(node.children.into_children());
+        }
+    }
+}
 
-                new_interior.children.insert_child(None, box new_leaf);
+// Puts entries in the order `build_sorted` needs, strictly ascending by key
+// bytes. Input that is already in order, like the entries of another tree,
+// is only checked. Otherwise the entries are sorted and, as with inserting
+// them one by one, the last entry for a repeated key wins.
+pub fn sort_for_build<K: TreeKey, T>(entries: Vec<(K, T)>) -> Vec<(SharedBytes, K, T)> {
+    let mut entries = entries
+        .into_iter()
+        .map(|(key, item)| (SharedBytes::copy_from_slice(key.as_bytes()), key, item))
+        .collect::<Vec<_>>();
 
-                let next_char = difference
-                    .pop()
-                    .expect(&format!("{}: {}", file!(), line!()));
-                node.prefix = difference;
+    if entries.windows(2).any(|pair| pair[0].0 >= pair[1].0) {
+        entries.sort_by(|left, right| left.0.cmp(&right.0));
 
-                new_interior
-                    .children
-                    .insert_child(Some(next_char), box RadixNode::Interior(node));
+        // The sort is stable, so the last of a run of equal keys is the one
+        // that arrived last
+        entries.reverse();
+        entries.dedup_by(|later, earlier| later.0 == earlier.0);
+        entries.reverse();
+    }
 
-                (box RadixNode::Interior(new_interior), None)
-            }
-            KeyMatchResult::Incomplete(split_index, mut remaining_probe) => {
-                let (common, mut difference) = node.prefix.split_at(split_index);
+    entries
+}
 
-                let next_char_old = difference
-                    .pop()
-                    .expect(&format!("{}: {}", file!(), line!()));
-                node.prefix = difference;
-                let next_char_new =
-                    remaining_probe
-                        .pop()
-                        .expect(&format!("{}: {}", file!(), line!()));
+// Builds a tree from entries with strictly ascending keys. Every node is
+// created once with all of its children, where inserting the entries one by
+// one splits nodes as longer keys arrive. Runs from an explicit stack of
+// tasks so deep trees cannot exhaust the stack.
+pub fn build_sorted<E: LeafEntry, A: Allocator + Clone>(
+    entries: Vec<(SharedBytes<A>, E)>,
+    alloc: A,
+) -> Option<Box<RadixNode<E, A>, A>> {
+    enum Task<A: Allocator> {
+        // Build the subtree for the entries `lo..hi`, which all share their
+        // first `depth` bytes
+        Build { lo: usize, hi: usize, depth: usize },
+        // Gather the last subtrees built as the children of an interior node
+        Assemble { prefix: KeyPrefix<A>, branches: Vec<Option<u8>> },
+    }
+
+    debug_assert!(entries.windows(2).all(|pair| pair[0].0 < pair[1].0));
+
+    let (keys, mut slots): (Vec<SharedBytes<A>>, Vec<Option<E>>) =
+        entries.into_iter().map(|(key, entry)| (key, Some(entry))).unzip();
+    let mut tasks = vec![
+        Task::Build {
+            lo: 0,
+            hi: keys.len(),
+            depth: 0,
+        },
+    ];
+    let mut built: Vec<Box<RadixNode<E, A>, A>> = Vec::new();
 
-                let mut new_interior = RadixInteriorNode {
-                    prefix: common,
-                    children: NodeChildren::new(),
-                };
+    while let Some(task) = tasks.pop() {
+        match task {
+            Task::Build { hi, lo, .. } if hi == lo => {}
+            Task::Build { lo, hi, depth } if hi - lo == 1 => {
+                let entry = slots[lo].take().expect(concat!(file!(), ": ", line!()));
+                let remaining_key = KeyPrefix::shared(&keys[lo], depth, keys[lo].len());
 
-                let new_leaf = RadixLeafNode {
-                    remaining_key: From::from(remaining_probe),
-                    entry: box new_entry,
-                };
+                built.push(Box::new_in(RadixNode::new_leaf(remaining_key, entry), alloc.clone()));
+            }
+            Task::Build { mut lo, hi, depth } => {
+                // The keys are sorted, so the bytes shared by the first and
+                // last key are shared by all of them
+                let common = keys[lo][depth..]
+                    .iter()
+                    .zip(keys[hi - 1][depth..].iter())
+                    .take_while(|&(first, last)| first == last)
+                    .count();
+                let split = depth + common;
+                let mut groups = Vec::new();
+
+                if keys[lo].len() == split {
+                    groups.push((None, lo, lo + 1));
+                    lo += 1;
+                }
 
-                new_interior
-                    .children
-                    .insert_child(Some(next_char_old), box RadixNode::Interior(node));
-                new_interior
-                    .children
-                    .insert_child(Some(next_char_new), box RadixNode::Leaf(new_leaf));
+                while lo < hi {
+                    let branch = keys[lo][split];
+                    let end = lo + keys[lo..hi].iter().take_while(|key| key[split] == branch).count();
 
-                (box RadixNode::Interior(new_interior), None)
-            }
-        },
-    }
-}
+                    groups.push((Some(branch), lo, end));
+                    lo = end;
+                }
 
-pub fn recursive_find<'p, 'v, K: TreeKey, V>(
-    current: &'v Box<RadixNode<K, V>>,
-    probe: KeyProbe<'p>,
-) -> Option<&'v V> {
-    match **current {
-        RadixNode::Interior(ref node) => match node.prefix.match_with(probe) {
-            KeyMatchResult::Complete => {
-                if node.children.contains_empty() {
-                    let child =
-                        node.children
-                            .get_child(None)
-                            .expect(&format!("{}: {}", file!(), line!()));
-                    debug_assert!(child.is_leaf());
-                    debug_assert!(child.get_leaf().remaining_key.is_empty());
+                tasks.push(Task::Assemble {
+                    prefix: KeyPrefix::shared(&keys[hi - 1], depth, split),
+                    branches: groups.iter().map(|&(branch, _, _)| branch).collect(),
+                });
+                for &(branch, lo, hi) in groups.iter().rev() {
+                    let depth = if branch.is_some() { split + 1 } else { split };
 
-                    Some(child.get_leaf().entry.value())
-                } else {
-                    None
+                    tasks.push(Task::Build { lo, hi, depth });
                 }
             }
-            KeyMatchResult::Partial(mut remaining_probe) => {
-                let next_char = remaining_probe
-                    .pop()
-                    .expect(&format!("{}: {}", file!(), line!()));
-                if node.children.contains_child(next_char) {
-                    return recursive_find(
-                        node.children.get_child(Some(next_char)).expect(&format!(
-                            "{}: {}",
-                            file!(),
-                            line!()
-                        )),
-                        remaining_probe,
-                    );
-                } else {
-                    None
+            Task::Assemble { prefix, branches } => {
+                let first_child = built.len() - branches.len();
+                let mut children = NodeChildren::new_in(alloc.clone());
+
+                for (branch, child) in branches.into_iter().zip(built.drain(first_child..)) {
+                    children.insert_child(branch, child);
                 }
+
+                built.push(Box::new_in(RadixNode::new_interior(prefix, children), alloc.clone()));
             }
-            _ => None,
-        },
-        RadixNode::Leaf(ref node) => match node.remaining_key.match_with(probe) {
-            KeyMatchResult::Complete => Some(node.entry.value()),
-            _ => None,
-        },
+        }
     }
+
+    built.pop()
 }
 
-pub fn recursive_mut_find<'p, 'v, K: TreeKey, V>(
-    current: &'v mut Box<RadixNode<K, V>>,
-    probe: KeyProbe<'p>,
-) -> Option<&'v mut V> {
-    match **current {
-        RadixNode::Interior(ref mut node) => match node.prefix.match_with(probe) {
-            KeyMatchResult::Complete => {
-                if node.children.contains_empty() {
-                    let child = node.children.get_child_mut(None).expect(&format!(
-                        "{}: {}",
-                        file!(),
-                        line!()
-                    ));
-                    debug_assert!(child.is_leaf());
-                    debug_assert!(child.get_leaf().remaining_key.is_empty());
+// Visits the leaves of a tree in the order of their keys, or in reverse. A
+// key that is a prefix of another sorts first, so the empty child of an
+// interior node comes before the children reached through a branching byte.
+pub struct LeafWalker<'a, E: 'a, A: 'a + Allocator + Clone = Global> {
+    pending: Vec<(&'a RadixNode<E, A>, usize, Option<u8>)>,
+    path: Vec<u8>,
+    track_path: bool,
+    reverse: bool,
+}
+
+impl<'a, E: 'a, A: 'a + Allocator + Clone> LeafWalker<'a, E, A> {
+    pub fn new(root: Option<&'a Box<RadixNode<E, A>, A>>, track_path: bool) -> Self {
+        LeafWalker {
+            pending: root.into_iter().map(|root| (&**root, 0, None)).collect(),
+            path: Vec::new(),
+            track_path,
+            reverse: false,
+        }
+    }
+
+    pub fn new_reversed(root: Option<&'a Box<RadixNode<E, A>, A>>, track_path: bool) -> Self {
+        LeafWalker {
+            reverse: true,
+            ..LeafWalker::new(root, track_path)
+        }
+    }
 
-                    Some(child.get_leaf_mut().entry.value_mut())
-                } else {
-                    None
+    // Starts the walk at the first leaf whose key is not below `lower`,
+    // skipping the subtrees that hold only smaller keys
+    pub fn seek(root: Option<&'a Box<RadixNode<E, A>, A>>, lower: Bound<&[u8]>, track_path: bool) -> Self {
+        let (mut remaining, inclusive) = match lower {
+            Bound::Included(lower) => (lower, true),
+            Bound::Excluded(lower) => (lower, false),
+            Bound::Unbounded => return LeafWalker::new(root, track_path),
+        };
+        let mut walker = LeafWalker::new(None, track_path);
+        let mut current = match root {
+            Some(root) => &**root,
+            None => return walker,
+        };
+
+        loop {
+            let path_len = walker.path.len();
+
+            match *current {
+                RadixNode::Leaf(ref leaf) => {
+                    let key = leaf.remaining_key.bytes();
+
+                    if key > remaining || (inclusive && key == remaining) {
+                        walker.pending.push((current, path_len, None));
+                    }
+
+                    break;
                 }
-            }
-            KeyMatchResult::Partial(mut remaining_probe) => {
-                let next_char = remaining_probe
-                    .pop()
-                    .expect(&format!("{}: {}", file!(), line!()));
-                if node.children.contains_child(next_char) {
-                    return recursive_mut_find(
-                        node.children
-                            .get_child_mut(Some(next_char))
-                            .expect(&format!("{}: {}", file!(), line!())),
-                        remaining_probe,
-                    );
-                } else {
-                    None
+                RadixNode::Interior(ref interior) => {
+                    let prefix = interior.prefix.bytes();
+                    let common = cmp::min(prefix.len(), remaining.len());
+
+                    match prefix[..common].cmp(&remaining[..common]) {
+                        cmp::Ordering::Less => break,
+                        cmp::Ordering::Greater => {
+                            walker.pending.push((current, path_len, None));
+                            break;
+                        }
+                        // Every key below extends the bound, so is above it
+                        cmp::Ordering::Equal if prefix.len() > remaining.len() => {
+                            walker.pending.push((current, path_len, None));
+                            break;
+                        }
+                        cmp::Ordering::Equal => {}
+                    }
+
+                    walker.path.extend_from_slice(prefix);
+                    remaining = &remaining[prefix.len()..];
+                    let path_len = walker.path.len();
+
+                    match remaining.split_first() {
+                        Some((&next_char, rest)) => {
+                            walker.push_children(interior, path_len, Some(next_char));
+
+                            match interior.children.get_child(Some(next_char)) {
+                                Some(child) => {
+                                    walker.path.push(next_char);
+                                    current = child;
+                                    remaining = rest;
+                                }
+                                None => break,
+                            }
+                        }
+                        // The empty child holds exactly the bound
+                        None => {
+                            walker.push_children(interior, path_len, None);
+
+                            if inclusive {
+                                if let Some(empty_child) = interior.children.get_child(None) {
+                                    walker.pending.push((&**empty_child, path_len, None));
+                                }
+                            }
+
+                            break;
+                        }
+                    }
                 }
             }
-            _ => None,
-        },
-        RadixNode::Leaf(ref mut node) => match node.remaining_key.match_with(probe) {
-            KeyMatchResult::Complete => Some(node.entry.value_mut()),
-            _ => None,
-        },
+        }
+
+        if !track_path {
+            walker.path.clear();
+        }
+
+        walker
     }
-}
 
-pub fn recursive_remove<'p, 'v, K: TreeKey, V>(
-    current: Box<RadixNode<K, V>>,
-    probe: KeyProbe<'p>,
-) -> (Option<Box<RadixNode<K, V>>>, Option<V>) {
-    match *current {
-        RadixNode::Leaf(node) => match node.remaining_key.match_with(probe) {
-            KeyMatchResult::Complete => (None, Some(node.entry.take_value())),
-            _ => (Some(box RadixNode::Leaf(node)), None),
-        },
-        RadixNode::Interior(mut node) => match node.prefix.match_with(probe) {
-            KeyMatchResult::Complete => {
-                let removed_value = if node.children.contains_empty() {
-                    let empty_child = node.children.remove_child(None).unwrap();
+    // The key bytes leading to the last leaf returned, not counting the
+    // remaining key held by the leaf. Only kept when tracking the path.
+    pub fn path(&self) -> &[u8] {
+        &self.path
+    }
 
-                    let (updated_empty, removed_value) =
-                        recursive_remove(empty_child, KeyProbe::empty());
+    pub fn next_leaf(&mut self) -> Option<&'a RadixLeafNode<E, A>> {
+        while let Some((node, path_len, branch)) = self.pending.pop() {
+            if self.track_path {
+                self.path.truncate(path_len);
+                self.path.extend(branch);
+            }
 
-                    if let Some(updated_empty) = updated_empty {
-                        node.children.insert_child(None, updated_empty);
+            match *node {
+                RadixNode::Leaf(ref leaf) => return Some(leaf),
+                RadixNode::Interior(ref interior) => {
+                    if self.track_path {
+                        self.path.extend_from_slice(interior.prefix.bytes());
                     }
 
-                    removed_value
-                } else {
-                    None
-                };
-
-                (Some(box RadixNode::Interior(node)), removed_value)
-            },
-            KeyMatchResult::Partial(mut remaining_probe) => {
-                let next_char = remaining_probe.pop().unwrap();
+                    let path_len = self.path.len();
+                    let empty_child = interior
+                        .children
+                        .get_child(None)
+                        .map(|empty_child| (&**empty_child, path_len, None));
+
+                    if self.reverse {
+                        self.pending.extend(empty_child);
+                        self.push_children(interior, path_len, None);
+                    } else {
+                        self.push_children(interior, path_len, None);
+                        self.pending.extend(empty_child);
+                    }
+                }
+            }
+        }
 
-                let removed_value = if node.children.contains_child(next_char) {
-                    let child = node.children.remove_child(Some(next_char)).unwrap();
+        None
+    }
 
-                    let (updated_child, removed_value) = recursive_remove(child, remaining_probe);
+    // Drops the rest of the walk
+    pub fn finish(&mut self) {
+        self.pending.clear();
+    }
 
-                    if let Some(updated_child) = updated_child {
-                        node.children.insert_child(Some(next_char), updated_child);
-                    }
+    // Queues the children reached through a byte greater than `after`, so
+    // that they are popped in the order of the walk
+    fn push_children(&mut self, interior: &'a RadixInteriorNode<E, A>, path_len: usize, after: Option<u8>) {
+        let first_child = self.pending.len();
 
-                    removed_value
-                } else {
-                    None
-                };
+        self.pending.extend(
+            interior
+                .children
+                .iter()
+                .filter(|&(branch, _)| after.is_none_or(|after| branch > after))
+                .map(|(branch, child)| (&**child, path_len, Some(branch))),
+        );
 
-                (Some(box RadixNode::Interior(node)), removed_value)
-            }
-            _ => (Some(box RadixNode::Interior(node)), None),
-        },
+        if !self.reverse {
+            self.pending[first_child..].reverse();
+        }
     }
 }
 
 #[cfg(test)]
 mod radix_node_tests {
     use super::*;
+    use super::super::entry::KeyValue;
 
     #[test]
     fn new_leaf() {
-        let node = RadixNode::new_leaf("hello", 10);
+        let node: RadixNode<_> = RadixNode::new_leaf(KeyPrefix::new(b"hello"), Box::new(KeyValue::new("hello", 10)));
 
         assert_eq!(
             node,
             RadixNode::Leaf(RadixLeafNode {
-                entry: box KeyValue::new("hello", 10),
+                entry: Box::new(KeyValue::new("hello", 10)),
                 remaining_key: KeyPrefix::new(b"hello"),
             })
         );
+        assert_eq!(node.get_leaf().entry.value(), &10);
     }
 }
 
-#[cfg(any(debug_assertions, test))]
+#[cfg(all(feature = "std", any(debug_assertions, test)))]
 pub mod debug {
-    use std::fmt;
-    use std::cell::Cell;
-    use std::str;
-    use std::iter;
+    use core::fmt;
+    use core::cell::Cell;
+    use core::str;
+
+    use allocator_api2::alloc::{Allocator, Global};
+    use allocator_api2::boxed::Box;
 
     use super::RadixNode;
-    use super::super::key::TreeKey;
+    use super::super::entry::LeafEntry;
 
-    pub struct TreeView<'a, K, V>
+    pub struct TreeView<'a, E, A = Global>
     where
-        K: 'a + TreeKey,
-        V: 'a + fmt::Debug,
+        E: 'a + LeafEntry,
+        E::Value: fmt::Debug,
+        A: 'a + Allocator + Clone,
     {
-        root: &'a Box<RadixNode<K, V>>,
+        root: &'a Box<RadixNode<E, A>, A>,
         context: TreeViewContext,
     }
 
-    impl<'a, K, V> TreeView<'a, K, V>
+    impl<'a, E, A> TreeView<'a, E, A>
     where
-        K: 'a + TreeKey,
-        V: 'a + fmt::Debug,
+        E: 'a + LeafEntry,
+        E::Value: fmt::Debug,
+        A: 'a + Allocator + Clone,
     {
-        pub fn new(root: &'a Box<RadixNode<K, V>>, indent_size: usize) -> Self {
+        pub fn new(root: &'a Box<RadixNode<E, A>, A>, indent_size: usize) -> Self {
             TreeView {
                 root,
                 context: TreeViewContext::new(indent_size),
@@ -676,10 +820,11 @@ pub mod debug {
         }
     }
 
-    impl<'a, K, V> fmt::Debug for TreeView<'a, K, V>
+    impl<'a, E, A> fmt::Debug for TreeView<'a, E, A>
     where
-        K: 'a + TreeKey + fmt::Debug,
-        V: 'a + fmt::Debug,
+        E: 'a + LeafEntry,
+        E::Value: fmt::Debug,
+        A: 'a + Allocator + Clone,
     {
         fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
             recursive_tree_format(self.root, f, &self.context)
@@ -709,29 +854,22 @@ pub mod debug {
         }
     }
 
-    fn recursive_tree_format<'p, 'v, K: TreeKey, V>(
-        current: &'v Box<RadixNode<K, V>>,
+    fn recursive_tree_format<'v, E: LeafEntry, A: Allocator + Clone>(
+        current: &'v Box<RadixNode<E, A>, A>,
         f: &mut fmt::Formatter,
         context: &TreeViewContext,
     ) -> fmt::Result
     where
-        K: fmt::Debug,
-        V: fmt::Debug,
+        E::Value: fmt::Debug,
     {
-        let indent: String = iter::repeat(" ")
-            .take(context.indent.get() * context.indent_size)
-            .collect();
+        let indent = " ".repeat(context.indent.get() * context.indent_size);
 
         match **current {
             RadixNode::Interior(ref node) => {
-                write!(
+                writeln!(
                     f,
-                    "[{}]\n",
-                    str::from_utf8(node.prefix.bytes()).expect(&format!(
-                        "{}: {}",
-                        file!(),
-                        line!()
-                    ))
+                    "[{}]",
+                    str::from_utf8(node.prefix.bytes()).expect(concat!(file!(), ": ", line!()))
                 )?;
 
                 context.indent.set(context.indent.get() + 1);
@@ -741,27 +879,23 @@ pub mod debug {
                     let empty_child =
                         node.children
                             .get_child(None)
-                            .expect(&format!("{}: {}", file!(), line!()));
-                    recursive_tree_format(empty_child, f, &context)?;
+                            .expect(concat!(file!(), ": ", line!()));
+                    recursive_tree_format(empty_child, f, context)?;
                 }
 
-                for &(ref branch_char, ref child) in node.children.iter() {
-                    write!(f, "{}({}) -> ", indent, *branch_char as char)?;
-                    recursive_tree_format(child, f, &context)?;
+                for (branch_char, child) in node.children.iter() {
+                    write!(f, "{}({}) -> ", indent, branch_char as char)?;
+                    recursive_tree_format(child, f, context)?;
                 }
 
                 context.indent.set(context.indent.get() - 1);
 
                 Ok(())
             }
-            RadixNode::Leaf(ref node) => write!(
+            RadixNode::Leaf(ref node) => writeln!(
                 f,
-                "{}: {:?}\n",
-                str::from_utf8(node.remaining_key.bytes()).expect(&format!(
-                    "{}: {}",
-                    file!(),
-                    line!()
-                )),
+                "{}: {:?}",
+                str::from_utf8(node.remaining_key.bytes()).expect(concat!(file!(), ": ", line!())),
                 node.entry.value()
             ),
         }
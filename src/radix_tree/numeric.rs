@@ -0,0 +1,287 @@
+use core::fmt;
+use core::mem;
+#[cfg(feature = "std")]
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::key::RebuildableKey;
+
+// Keys for numbers, encoded so that comparing the bytes of two keys gives the
+// same order as comparing the numbers. Unsigned integers are stored big
+// endian, signed integers have their sign bit flipped so that negative
+// numbers sort first, and floats are ordered by the IEEE 754 total order.
+
+macro_rules! integer_key {
+    ($name:ident, $int:ty, $uint:ty, $flip:expr) => {
+        #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub struct $name {
+            bytes: [u8; mem::size_of::<$int>()],
+        }
+
+        impl $name {
+            pub fn new(value: $int) -> Self {
+                $name {
+                    bytes: ((value as $uint) ^ $flip).to_be_bytes(),
+                }
+            }
+
+            pub fn value(&self) -> $int {
+                (<$uint>::from_be_bytes(self.bytes) ^ $flip) as $int
+            }
+        }
+
+        impl From<$int> for $name {
+            fn from(value: $int) -> Self {
+                $name::new(value)
+            }
+        }
+
+        impl AsRef<[u8]> for $name {
+            fn as_ref(&self) -> &[u8] {
+                &self.bytes
+            }
+        }
+
+        impl RebuildableKey for $name {
+            fn from_bytes(key_bytes: &[u8]) -> Self {
+                let mut bytes = [0; mem::size_of::<$int>()];
+                bytes.copy_from_slice(key_bytes);
+
+                $name { bytes }
+            }
+        }
+
+        impl fmt::Debug for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "{}({})", stringify!($name), self.value())
+            }
+        }
+    };
+}
+
+integer_key!(U32Key, u32, u32, 0);
+integer_key!(U64Key, u64, u64, 0);
+integer_key!(I32Key, i32, u32, 1 << 31);
+integer_key!(I64Key, i64, u64, 1 << 63);
+
+// Positive floats only need the sign bit set to sort above the negative
+// ones, negative floats have every bit flipped so that larger magnitudes
+// sort first. NaNs sort beyond the infinities and -0.0 sorts before 0.0, so
+// equality of keys is equality of the bit patterns rather than `==`.
+macro_rules! float_key {
+    ($name:ident, $float:ty, $bits:ty) => {
+        #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub struct $name {
+            bytes: [u8; mem::size_of::<$float>()],
+        }
+
+        impl $name {
+            pub fn new(value: $float) -> Self {
+                const SIGN_BIT: $bits = 1 << (mem::size_of::<$bits>() * 8 - 1);
+
+                let bits = value.to_bits();
+                let ordered = if bits & SIGN_BIT == 0 { bits ^ SIGN_BIT } else { !bits };
+
+                $name {
+                    bytes: ordered.to_be_bytes(),
+                }
+            }
+
+            pub fn value(&self) -> $float {
+                const SIGN_BIT: $bits = 1 << (mem::size_of::<$bits>() * 8 - 1);
+
+                let ordered = <$bits>::from_be_bytes(self.bytes);
+                let bits = if ordered & SIGN_BIT == 0 { !ordered } else { ordered ^ SIGN_BIT };
+
+                <$float>::from_bits(bits)
+            }
+        }
+
+        impl From<$float> for $name {
+            fn from(value: $float) -> Self {
+                $name::new(value)
+            }
+        }
+
+        impl AsRef<[u8]> for $name {
+            fn as_ref(&self) -> &[u8] {
+                &self.bytes
+            }
+        }
+
+        impl RebuildableKey for $name {
+            fn from_bytes(key_bytes: &[u8]) -> Self {
+                let mut bytes = [0; mem::size_of::<$float>()];
+                bytes.copy_from_slice(key_bytes);
+
+                $name { bytes }
+            }
+        }
+
+        impl fmt::Debug for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "{}({})", stringify!($name), self.value())
+            }
+        }
+    };
+}
+
+float_key!(F32Key, f32, u32);
+float_key!(F64Key, f64, u64);
+
+// A point in time, stored as the signed seconds from the unix epoch followed
+// by the nanoseconds within that second, so times before the epoch are
+// ordered correctly too. Needs `std` for `SystemTime`.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TimestampKey {
+    bytes: [u8; 12],
+}
+
+#[cfg(feature = "std")]
+impl TimestampKey {
+    pub fn new(time: SystemTime) -> Self {
+        let (seconds, nanos) = match time.duration_since(UNIX_EPOCH) {
+            Ok(after) => (after.as_secs() as i64, after.subsec_nanos()),
+            Err(error) => {
+                let before = error.duration();
+
+                if before.subsec_nanos() == 0 {
+                    (-(before.as_secs() as i64), 0)
+                } else {
+                    (-(before.as_secs() as i64) - 1, 1_000_000_000 - before.subsec_nanos())
+                }
+            }
+        };
+
+        let mut bytes = [0; 12];
+        bytes[..8].copy_from_slice(I64Key::new(seconds).as_ref());
+        bytes[8..].copy_from_slice(&nanos.to_be_bytes());
+
+        TimestampKey { bytes }
+    }
+
+    pub fn value(&self) -> SystemTime {
+        let seconds = I64Key::from_bytes(&self.bytes[..8]).value();
+        let nanos = U32Key::from_bytes(&self.bytes[8..]).value();
+
+        if seconds >= 0 {
+            UNIX_EPOCH + Duration::new(seconds as u64, nanos)
+        } else {
+            UNIX_EPOCH - Duration::new(seconds.wrapping_neg() as u64, 0) + Duration::new(0, nanos)
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<SystemTime> for TimestampKey {
+    fn from(time: SystemTime) -> Self {
+        TimestampKey::new(time)
+    }
+}
+
+#[cfg(feature = "std")]
+impl AsRef<[u8]> for TimestampKey {
+    fn as_ref(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+#[cfg(feature = "std")]
+impl RebuildableKey for TimestampKey {
+    fn from_bytes(key_bytes: &[u8]) -> Self {
+        let mut bytes = [0; 12];
+        bytes.copy_from_slice(key_bytes);
+
+        TimestampKey { bytes }
+    }
+}
+
+#[cfg(feature = "std")]
+impl fmt::Debug for TimestampKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "TimestampKey({:?})", self.value())
+    }
+}
+
+#[cfg(test)]
+mod numeric_key_tests {
+    use super::*;
+
+    fn assert_byte_order<T: AsRef<[u8]> + fmt::Debug>(ordered: &[T]) {
+        for pair in ordered.windows(2) {
+            assert!(pair[0].as_ref() < pair[1].as_ref(), "{:?} !< {:?}", pair[0], pair[1]);
+        }
+    }
+
+    #[test]
+    fn unsigned_order() {
+        let values = [0, 1, 255, 256, 65_535, 1 << 40, u64::MAX];
+        let keys = values.iter().map(|&value| U64Key::new(value)).collect::<Vec<_>>();
+
+        assert_byte_order(&keys);
+        for (key, &value) in keys.iter().zip(values.iter()) {
+            assert_eq!(key.value(), value);
+        }
+    }
+
+    #[test]
+    fn signed_order() {
+        let values = [i64::MIN, -(1 << 40), -256, -1, 0, 1, 256, i64::MAX];
+        let keys = values.iter().map(|&value| I64Key::new(value)).collect::<Vec<_>>();
+
+        assert_byte_order(&keys);
+        for (key, &value) in keys.iter().zip(values.iter()) {
+            assert_eq!(key.value(), value);
+        }
+
+        let small = [i32::MIN, -7, 0, 7, i32::MAX];
+        let small_keys = small.iter().map(|&value| I32Key::new(value)).collect::<Vec<_>>();
+        assert_byte_order(&small_keys);
+        assert_eq!(small_keys[1].value(), -7);
+    }
+
+    #[test]
+    fn float_order() {
+        let values = [
+            f64::NEG_INFINITY,
+            f64::MIN,
+            -1.5,
+            -f64::MIN_POSITIVE,
+            -0.0,
+            0.0,
+            f64::MIN_POSITIVE,
+            1.5,
+            f64::MAX,
+            f64::INFINITY,
+            f64::NAN,
+        ];
+        let keys = values.iter().map(|&value| F64Key::new(value)).collect::<Vec<_>>();
+
+        assert_byte_order(&keys);
+        for (key, &value) in keys.iter().zip(values.iter()) {
+            assert_eq!(key.value().to_bits(), value.to_bits());
+        }
+
+        assert_byte_order(&[F32Key::new(-2.0), F32Key::new(-0.5), F32Key::new(0.25), F32Key::new(3.0)]);
+        assert_eq!(F32Key::new(-0.5).value(), -0.5);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn timestamp_order() {
+        let times = [
+            UNIX_EPOCH - Duration::new(86_400, 500),
+            UNIX_EPOCH - Duration::new(1, 0),
+            UNIX_EPOCH - Duration::new(0, 1),
+            UNIX_EPOCH,
+            UNIX_EPOCH + Duration::new(0, 1),
+            UNIX_EPOCH + Duration::new(1_500_000_000, 999_999_999),
+        ];
+        let keys = times.iter().map(|&time| TimestampKey::new(time)).collect::<Vec<_>>();
+
+        assert_byte_order(&keys);
+        for (key, &time) in keys.iter().zip(times.iter()) {
+            assert_eq!(key.value(), time);
+        }
+    }
+}
@@ -0,0 +1,1087 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::iter;
+use std::marker::PhantomData;
+use std::path::Path;
+use std::rc::Rc;
+
+use error::{Error, Result};
+use super::frozen::{FrozenValue, crc32, read_fixed, read_varint, write_fixed, write_length_prefixed, write_varint};
+
+// A radix tree kept in a file of fixed size pages, one node to a page, with
+// only recently used nodes held in memory.
+//
+// Pages are never written in place. Changing a node writes a copy to a free
+// page, and so does every node up to the root, so the tree of the last commit
+// is left untouched until a commit replaces it (shadow paging). Pages 0 and 1
+// hold the two newest commits, alternating, each naming its root page. A
+// commit syncs the new nodes and then writes the older of the two, so a crash
+// at any point leaves at least one complete commit to open.
+//
+// Pages left behind by a commit are only reused once the next commit is
+// written. Opening a file walks the tree of the newest commit to find every
+// page it does not use, which also reclaims pages written after the commit.
+//
+// Every page starts with a CRC-32 and the length of its content, the CRC
+// covering the length and the content. Nodes use the same encoding as frozen
+// trees with page numbers in place of offsets.
+
+pub const PAGE_SIZE: usize = 4096;
+const PAGE_HEADER_LEN: usize = 6;
+// Bounds a node to a page. The largest interior node has a prefix as long as
+// a key and 256 children.
+pub const MAX_KEY_LEN: usize = 1024;
+pub const MAX_VALUE_LEN: usize = 2048;
+
+const MAGIC: &[u8; 4] = b"RDXP";
+const VERSION: u16 = 1;
+const META_PAGES: u64 = 2;
+
+const LEAF: u8 = 0;
+const INTERIOR: u8 = 1;
+const HAS_EMPTY: u8 = 2;
+
+#[derive(Debug, Clone, PartialEq)]
+enum PagedNode {
+    Leaf {
+        remaining_key: Vec<u8>,
+        // The value as encoded by `FrozenValue`
+        value: Vec<u8>,
+    },
+    Interior {
+        prefix: Vec<u8>,
+        empty_child: Option<u64>,
+        // Ascending by branching byte
+        children: Vec<(u8, u64)>,
+    },
+}
+
+impl PagedNode {
+    // The key bytes the node covers after the byte branching to it
+    fn own_bytes(&self) -> &[u8] {
+        match *self {
+            PagedNode::Leaf {
+                ref remaining_key, ..
+            } => remaining_key,
+            PagedNode::Interior { ref prefix, .. } => prefix,
+        }
+    }
+
+    fn with_own_bytes(self, bytes: Vec<u8>) -> Self {
+        match self {
+            PagedNode::Leaf { value, .. } => PagedNode::Leaf {
+                remaining_key: bytes,
+                value,
+            },
+            PagedNode::Interior {
+                empty_child,
+                children,
+                ..
+            } => PagedNode::Interior {
+                prefix: bytes,
+                empty_child,
+                children,
+            },
+        }
+    }
+
+    fn child(&self, branch: Option<u8>) -> Option<u64> {
+        match (self, branch) {
+            (&PagedNode::Interior { empty_child, .. }, None) => empty_child,
+            (&PagedNode::Interior { ref children, .. }, Some(branch)) => children
+                .binary_search_by_key(&branch, |&(child_branch, _)| child_branch)
+                .ok()
+                .map(|idx| children[idx].1),
+            (&PagedNode::Leaf { .. }, _) => None,
+        }
+    }
+
+    fn set_child(&mut self, branch: Option<u8>, page: u64) {
+        match (self, branch) {
+            (&mut PagedNode::Interior {
+                ref mut empty_child, ..
+            }, None) => *empty_child = Some(page),
+            (&mut PagedNode::Interior {
+                ref mut children, ..
+            }, Some(branch)) => match children.binary_search_by_key(&branch, |&(child_branch, _)| child_branch) {
+                Ok(idx) => children[idx].1 = page,
+                Err(idx) => children.insert(idx, (branch, page)),
+            },
+            (&mut PagedNode::Leaf { .. }, _) => unreachable!("{}: {}", file!(), line!()),
+        }
+    }
+
+    fn remove_child(&mut self, branch: Option<u8>) {
+        match (self, branch) {
+            (&mut PagedNode::Interior {
+                ref mut empty_child, ..
+            }, None) => *empty_child = None,
+            (&mut PagedNode::Interior {
+                ref mut children, ..
+            }, Some(branch)) => children.retain(|&(child_branch, _)| child_branch != branch),
+            (&mut PagedNode::Leaf { .. }, _) => unreachable!("{}: {}", file!(), line!()),
+        }
+    }
+
+    // Every child page, the empty child first
+    fn entries(&self) -> Vec<(Option<u8>, u64)> {
+        match *self {
+            PagedNode::Leaf { .. } => Vec::new(),
+            PagedNode::Interior {
+                empty_child,
+                ref children,
+                ..
+            } => empty_child
+                .map(|page| (None, page))
+                .into_iter()
+                .chain(children.iter().map(|&(branch, page)| (Some(branch), page)))
+                .collect(),
+        }
+    }
+
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        match *self {
+            PagedNode::Leaf {
+                ref remaining_key,
+                ref value,
+            } => {
+                buffer.push(LEAF);
+                write_length_prefixed(buffer, remaining_key);
+                write_length_prefixed(buffer, value);
+            }
+            PagedNode::Interior {
+                ref prefix,
+                empty_child,
+                ref children,
+            } => {
+                buffer.push(if empty_child.is_some() { INTERIOR | HAS_EMPTY } else { INTERIOR });
+                write_length_prefixed(buffer, prefix);
+                write_varint(buffer, children.len() as u64);
+                if let Some(empty_child) = empty_child {
+                    write_fixed(buffer, empty_child, 8);
+                }
+                buffer.extend(children.iter().map(|&(branch, _)| branch));
+                for &(_, page) in children {
+                    write_fixed(buffer, page, 8);
+                }
+            }
+        }
+    }
+
+    fn decode(mut input: &[u8]) -> Option<Self> {
+        fn take<'a>(input: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+            if input.len() < len {
+                return None;
+            }
+
+            let (taken, rest) = input.split_at(len);
+            *input = rest;
+            Some(taken)
+        }
+
+        let tag = take(&mut input, 1)?[0];
+        let first = read_varint(&mut input)? as usize;
+        let first = take(&mut input, first)?.to_vec();
+
+        match tag {
+            LEAF => {
+                let value = read_varint(&mut input)? as usize;
+
+                Some(PagedNode::Leaf {
+                    remaining_key: first,
+                    value: take(&mut input, value)?.to_vec(),
+                })
+            }
+            _ if tag & !HAS_EMPTY == INTERIOR => {
+                let child_count = read_varint(&mut input)? as usize;
+                let empty_child = if tag & HAS_EMPTY != 0 {
+                    Some(read_fixed(take(&mut input, 8)?))
+                } else {
+                    None
+                };
+                let branches = take(&mut input, child_count)?;
+                let pages = take(&mut input, child_count * 8)?;
+
+                Some(PagedNode::Interior {
+                    prefix: first,
+                    empty_child,
+                    children: branches
+                        .iter()
+                        .zip(pages.chunks(8))
+                        .map(|(&branch, page)| (branch, read_fixed(page)))
+                        .collect(),
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+// The state a commit names, kept in one of the two meta pages
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Meta {
+    seq: u64,
+    root: Option<u64>,
+    len: u64,
+    page_count: u64,
+}
+
+impl Meta {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(MAGIC);
+        write_fixed(buffer, VERSION as u64, 2);
+        write_fixed(buffer, self.seq, 8);
+        // Page 0 is a meta page, so it marks an empty tree
+        write_fixed(buffer, self.root.unwrap_or(0), 8);
+        write_fixed(buffer, self.len, 8);
+        write_fixed(buffer, self.page_count, 8);
+    }
+
+    fn decode(input: &[u8]) -> Option<Self> {
+        if input.len() < 38 || input[..4] != MAGIC[..] || read_fixed(&input[4..6]) != VERSION as u64 {
+            return None;
+        }
+
+        let root = read_fixed(&input[14..22]);
+        Some(Meta {
+            seq: read_fixed(&input[6..14]),
+            root: if root == 0 { None } else { Some(root) },
+            len: read_fixed(&input[22..30]),
+            page_count: read_fixed(&input[30..38]),
+        })
+    }
+}
+
+// The content of a page, or None when its checksum does not match
+fn checked_page(page: &[u8]) -> Option<&[u8]> {
+    let len = read_fixed(&page[4..6]) as usize;
+    if PAGE_HEADER_LEN + len > PAGE_SIZE || crc32(&page[4..PAGE_HEADER_LEN + len]) != read_fixed(&page[..4]) as u32 {
+        return None;
+    }
+
+    Some(&page[PAGE_HEADER_LEN..PAGE_HEADER_LEN + len])
+}
+
+// Least recently used nodes are dropped first, every use moves a node to the
+// back of `by_use`
+struct PageCache {
+    capacity: usize,
+    tick: u64,
+    pages: HashMap<u64, (Rc<PagedNode>, u64)>,
+    by_use: BTreeMap<u64, u64>,
+}
+
+impl PageCache {
+    fn new(capacity: usize) -> Self {
+        PageCache {
+            capacity,
+            tick: 0,
+            pages: HashMap::new(),
+            by_use: BTreeMap::new(),
+        }
+    }
+
+    fn get(&mut self, page: u64) -> Option<Rc<PagedNode>> {
+        let (ref node, ref mut last_use) = *self.pages.get_mut(&page)?;
+
+        self.by_use.remove(last_use);
+        self.tick += 1;
+        *last_use = self.tick;
+        self.by_use.insert(self.tick, page);
+
+        Some(node.clone())
+    }
+
+    fn insert(&mut self, page: u64, node: Rc<PagedNode>) {
+        self.remove(page);
+        if self.capacity == 0 {
+            return;
+        }
+
+        if self.pages.len() >= self.capacity {
+            let (&oldest, &evicted) = self.by_use.iter().next().expect(concat!(file!(), ": ", line!()));
+            self.by_use.remove(&oldest);
+            self.pages.remove(&evicted);
+        }
+
+        self.tick += 1;
+        self.pages.insert(page, (node, self.tick));
+        self.by_use.insert(self.tick, page);
+    }
+
+    fn remove(&mut self, page: u64) {
+        if let Some((_, last_use)) = self.pages.remove(&page) {
+            self.by_use.remove(&last_use);
+        }
+    }
+}
+
+fn common_prefix_len(left: &[u8], right: &[u8]) -> usize {
+    left.iter().zip(right).take_while(|&(left, right)| left == right).count()
+}
+
+// Changes are written to the file as they are made but only survive a crash
+// or a reopen once `commit` returns. Reads take `&mut self` since they go
+// through the page cache.
+pub struct PagedRadixTree<V> {
+    file: File,
+    cache: PageCache,
+    meta: Meta,
+    free: Vec<u64>,
+    // Pages the last commit still uses, free once the next commit is written
+    pending_free: Vec<u64>,
+    // Pages written since the last commit, which no commit uses
+    uncommitted: HashSet<u64>,
+    value_type: PhantomData<fn() -> V>,
+}
+
+impl<V: FrozenValue> PagedRadixTree<V> {
+    // Opens the tree in the file at `path`, creating an empty tree when the
+    // file is empty or missing. At most `cache_pages` nodes are kept in
+    // memory.
+    pub fn open<P: AsRef<Path>>(path: P, cache_pages: usize) -> Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .map_err(|err| Error::io("could not open paged tree file", err))?;
+        let file_len = file.metadata().map_err(|err| Error::io("could not read paged tree file", err))?.len();
+
+        let mut tree = PagedRadixTree {
+            file,
+            cache: PageCache::new(cache_pages),
+            meta: Meta {
+                seq: 0,
+                root: None,
+                len: 0,
+                page_count: META_PAGES,
+            },
+            free: Vec::new(),
+            pending_free: Vec::new(),
+            uncommitted: HashSet::new(),
+            value_type: PhantomData,
+        };
+
+        if file_len == 0 {
+            tree.write_meta()?;
+            tree.file.sync_all().map_err(|err| Error::io("could not sync paged tree file", err))?;
+            return Ok(tree);
+        }
+
+        let mut newest: Option<Meta> = None;
+        for page in (0..META_PAGES).filter(|&page| (page + 1) * PAGE_SIZE as u64 <= file_len) {
+            let content = tree.read_page(page)?;
+
+            if let Some(meta) = checked_page(&content).and_then(Meta::decode) {
+                if newest.is_none_or(|newest| meta.seq > newest.seq) {
+                    newest = Some(meta);
+                }
+            }
+        }
+        tree.meta = match newest {
+            Some(meta) => meta,
+            None => corrupt!("paged tree file has no valid meta page"),
+        };
+
+        // Drops whatever was written after the newest commit
+        tree.file
+            .set_len(tree.meta.page_count * PAGE_SIZE as u64)
+            .map_err(|err| Error::io("could not truncate paged tree file", err))?;
+        tree.reclaim_free_pages()?;
+
+        Ok(tree)
+    }
+
+    pub fn len(&self) -> usize {
+        self.meta.len as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.meta.len == 0
+    }
+
+    // Makes every change so far durable
+    pub fn commit(&mut self) -> Result<()> {
+        if self.uncommitted.is_empty() && self.pending_free.is_empty() {
+            return Ok(());
+        }
+
+        self.file.sync_data().map_err(|err| Error::io("could not sync paged tree nodes", err))?;
+        self.meta.seq += 1;
+        self.write_meta()?;
+        self.file.sync_data().map_err(|err| Error::io("could not sync paged tree commit", err))?;
+
+        self.free.append(&mut self.pending_free);
+        self.uncommitted.clear();
+        Ok(())
+    }
+
+    pub fn get(&mut self, key: &[u8]) -> Result<Option<V>> {
+        let mut current = self.meta.root;
+        let mut remaining = key;
+
+        while let Some(page) = current {
+            let node = self.read_node(page)?;
+
+            match *node {
+                PagedNode::Leaf {
+                    ref remaining_key,
+                    ref value,
+                } => {
+                    if &remaining_key[..] != remaining {
+                        return Ok(None);
+                    }
+
+                    return V::decode_value(value).map(Some);
+                }
+                PagedNode::Interior { ref prefix, .. } => {
+                    if !remaining.starts_with(prefix) {
+                        return Ok(None);
+                    }
+
+                    remaining = &remaining[prefix.len()..];
+                    match remaining.split_first() {
+                        Some((&next_char, rest)) => {
+                            current = node.child(Some(next_char));
+                            remaining = rest;
+                        }
+                        None => current = node.child(None),
+                    }
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    pub fn contains_key(&mut self, key: &[u8]) -> Result<bool> {
+        self.get(key).map(|value| value.is_some())
+    }
+
+    pub fn insert(&mut self, key: &[u8], value: V) -> Result<Option<V>> {
+        let mut encoded = Vec::new();
+        value.encode_value(&mut encoded);
+        if key.len() > MAX_KEY_LEN {
+            return Err(Error::CapacityExceeded {
+                what: "paged tree key bytes",
+                limit: MAX_KEY_LEN,
+                needed: key.len(),
+            });
+        }
+        if encoded.len() > MAX_VALUE_LEN {
+            return Err(Error::CapacityExceeded {
+                what: "paged tree value bytes",
+                limit: MAX_VALUE_LEN,
+                needed: encoded.len(),
+            });
+        }
+
+        let new_leaf = |remaining_key: &[u8], value: Vec<u8>| PagedNode::Leaf {
+            remaining_key: remaining_key.to_vec(),
+            value,
+        };
+
+        // The nodes above the one being replaced, with the branch taken
+        let mut path = Vec::new();
+        let mut current = self.meta.root;
+        let mut remaining = key;
+
+        let (replacement, old_value) = loop {
+            let page = match current {
+                Some(page) => page,
+                None => break (new_leaf(remaining, encoded), None),
+            };
+            let node = self.read_node(page)?;
+
+            match *node {
+                PagedNode::Leaf {
+                    ref remaining_key,
+                    ref value,
+                } => {
+                    if &remaining_key[..] == remaining {
+                        break (new_leaf(remaining, encoded), Some(value.clone()));
+                    }
+
+                    let common = common_prefix_len(remaining_key, remaining);
+                    let old_leaf = new_leaf(remaining_key, value.clone());
+
+                    break (
+                        self.split_node(old_leaf, remaining, common, encoded)?,
+                        None,
+                    );
+                }
+                PagedNode::Interior { ref prefix, .. } => {
+                    if !remaining.starts_with(prefix) {
+                        let common = common_prefix_len(prefix, remaining);
+
+                        break (
+                            self.split_node((*node).clone(), remaining, common, encoded)?,
+                            None,
+                        );
+                    }
+
+                    let rest = &remaining[prefix.len()..];
+                    let branch = rest.first().cloned();
+
+                    match (node.child(branch), branch) {
+                        // The empty child is a leaf, replaced from here
+                        (Some(empty_child), None) => {
+                            let old_value = match *self.read_node(empty_child)? {
+                                PagedNode::Leaf { ref value, .. } => value.clone(),
+                                PagedNode::Interior { .. } => corrupt!("paged tree empty child is an interior node"),
+                            };
+                            let empty_child = self.rewrite_node(Some(empty_child), new_leaf(&[], encoded))?;
+                            let mut replacement = (*node).clone();
+                            replacement.set_child(None, empty_child);
+
+                            break (replacement, Some(old_value));
+                        }
+                        (Some(child), Some(_)) => {
+                            path.push((page, node.clone(), branch));
+                            current = Some(child);
+                            remaining = &rest[1..];
+                        }
+                        (None, _) => {
+                            let leaf = self.write_node(new_leaf(rest.get(1..).unwrap_or(&[]), encoded))?;
+                            let mut replacement = (*node).clone();
+                            replacement.set_child(branch, leaf);
+
+                            break (replacement, None);
+                        }
+                    }
+                }
+            }
+        };
+
+        self.replace_path(path, current, Some(replacement))?;
+
+        if old_value.is_none() {
+            self.meta.len += 1;
+        }
+
+        match old_value {
+            Some(old_value) => V::decode_value(&old_value).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    // Splits `node` where its own bytes stop matching the `remaining` key
+    // being inserted, `common` bytes in, giving an interior node over the
+    // rest of `node` and a new leaf
+    fn split_node(&mut self, node: PagedNode, remaining: &[u8], common: usize, value: Vec<u8>) -> Result<PagedNode> {
+        let own_bytes = node.own_bytes().to_vec();
+        let mut interior = PagedNode::Interior {
+            prefix: remaining[..common].to_vec(),
+            empty_child: None,
+            children: Vec::new(),
+        };
+
+        // The node keeps its bytes after the branching byte, and is always
+        // rewritten as they got shorter. A leaf whose key ends where the new
+        // key goes on becomes the empty child.
+        let old_child = self.write_node(node.with_own_bytes(own_bytes.get(common + 1..).unwrap_or(&[]).to_vec()))?;
+        interior.set_child(own_bytes.get(common).cloned(), old_child);
+
+        let new_leaf = self.write_node(PagedNode::Leaf {
+            remaining_key: remaining.get(common + 1..).unwrap_or(&[]).to_vec(),
+            value,
+        })?;
+        interior.set_child(remaining.get(common).cloned(), new_leaf);
+
+        Ok(interior)
+    }
+
+    pub fn remove(&mut self, key: &[u8]) -> Result<Option<V>> {
+        let mut path = Vec::new();
+        let mut current = self.meta.root;
+        let mut remaining = key;
+
+        let value = loop {
+            let page = match current {
+                Some(page) => page,
+                None => return Ok(None),
+            };
+            let node = self.read_node(page)?;
+
+            match *node {
+                PagedNode::Leaf {
+                    ref remaining_key,
+                    ref value,
+                } => {
+                    if &remaining_key[..] != remaining {
+                        return Ok(None);
+                    }
+
+                    self.free_page(page);
+                    break V::decode_value(value)?;
+                }
+                PagedNode::Interior { ref prefix, .. } => {
+                    if !remaining.starts_with(prefix) {
+                        return Ok(None);
+                    }
+
+                    remaining = &remaining[prefix.len()..];
+                    let branch = remaining.first().cloned();
+                    remaining = remaining.get(1..).unwrap_or(&[]);
+
+                    path.push((page, node.clone(), branch));
+                    current = node.child(branch);
+                }
+            }
+        };
+
+        // The parent loses the leaf, and is merged with its last entry when
+        // that is all it has left
+        let (replaced, replacement) = match path.pop() {
+            Some((page, parent, branch)) => {
+                let mut parent = (*parent).clone();
+                parent.remove_child(branch);
+
+                let entries = parent.entries();
+                if entries.len() == 1 {
+                    let (only_branch, only_page) = entries[0];
+                    let only_child = self.read_node(only_page)?;
+                    self.free_page(only_page);
+
+                    let mut joined = parent.own_bytes().to_vec();
+                    joined.extend(only_branch);
+                    joined.extend_from_slice(only_child.own_bytes());
+
+                    (Some(page), Some((*only_child).clone().with_own_bytes(joined)))
+                } else {
+                    (Some(page), Some(parent))
+                }
+            }
+            None => (None, None),
+        };
+
+        self.replace_path(path, replaced, replacement)?;
+        self.meta.len -= 1;
+
+        Ok(Some(value))
+    }
+
+    // Writes `replacement` over the node at `replaced` and copies of every
+    // node in `path` above it, pointing at the new pages, ending with the new
+    // root. Nodes rewritten in place keep their page, so the copying stops
+    // at the first of them.
+    fn replace_path(
+        &mut self,
+        mut path: Vec<(u64, Rc<PagedNode>, Option<u8>)>,
+        mut replaced: Option<u64>,
+        replacement: Option<PagedNode>,
+    ) -> Result<()> {
+        let mut child = match replacement {
+            Some(replacement) => Some(self.rewrite_node(replaced, replacement)?),
+            None => {
+                if let Some(page) = replaced {
+                    self.free_page(page);
+                }
+
+                None
+            }
+        };
+
+        while let Some((page, node, branch)) = path.pop() {
+            if child == replaced {
+                return Ok(());
+            }
+
+            let mut node = (*node).clone();
+            match child {
+                Some(child) => node.set_child(branch, child),
+                None => node.remove_child(branch),
+            }
+
+            replaced = Some(page);
+            child = Some(self.rewrite_node(replaced, node)?);
+        }
+
+        self.meta.root = child;
+        Ok(())
+    }
+
+    pub fn iter<'a>(&'a mut self) -> PagedIter<'a, V> {
+        self.prefix_iter(&[])
+    }
+
+    // Every key starting with `prefix` and its value, in key order. Errors
+    // reading pages are yielded and end the iteration.
+    pub fn prefix_iter<'a>(&'a mut self, prefix: &[u8]) -> PagedIter<'a, V> {
+        PagedIter {
+            tree: self,
+            prefix: Some(prefix.to_vec()),
+            pending: Vec::new(),
+            path: Vec::new(),
+        }
+    }
+
+    fn read_page(&mut self, page: u64) -> Result<Vec<u8>> {
+        let mut content = vec![0; PAGE_SIZE];
+
+        self.file
+            .seek(SeekFrom::Start(page * PAGE_SIZE as u64))
+            .and_then(|_| self.file.read_exact(&mut content))
+            .map_err(|err| Error::io(format!("could not read paged tree page {}", page), err))?;
+
+        Ok(content)
+    }
+
+    fn write_page(&mut self, page: u64, content: &[u8]) -> Result<()> {
+        let mut buffer = Vec::with_capacity(PAGE_SIZE);
+        write_fixed(&mut buffer, 0, 4);
+        write_fixed(&mut buffer, content.len() as u64, 2);
+        buffer.extend_from_slice(content);
+
+        let checksum = crc32(&buffer[4..]);
+        buffer[..4].copy_from_slice(&[checksum as u8, (checksum >> 8) as u8, (checksum >> 16) as u8, (checksum >> 24) as u8]);
+        buffer.resize(PAGE_SIZE, 0);
+
+        self.file
+            .seek(SeekFrom::Start(page * PAGE_SIZE as u64))
+            .and_then(|_| self.file.write_all(&buffer))
+            .map_err(|err| Error::io(format!("could not write paged tree page {}", page), err))
+    }
+
+    fn write_meta(&mut self) -> Result<()> {
+        let mut content = Vec::new();
+        self.meta.encode(&mut content);
+
+        let page = self.meta.seq % META_PAGES;
+        self.write_page(page, &content)
+    }
+
+    fn read_node(&mut self, page: u64) -> Result<Rc<PagedNode>> {
+        if let Some(node) = self.cache.get(page) {
+            return Ok(node);
+        }
+
+        if page < META_PAGES || page >= self.meta.page_count {
+            corrupt!("paged tree refers to page {} outside of its {} pages", page, self.meta.page_count);
+        }
+
+        let content = self.read_page(page)?;
+        let node = match checked_page(&content).and_then(PagedNode::decode) {
+            Some(node) => Rc::new(node),
+            None => corrupt!("paged tree page {} is corrupt", page),
+        };
+
+        self.cache.insert(page, node.clone());
+        Ok(node)
+    }
+
+    fn write_node(&mut self, node: PagedNode) -> Result<u64> {
+        let page = match self.free.pop() {
+            Some(page) => page,
+            None => {
+                self.meta.page_count += 1;
+                self.meta.page_count - 1
+            }
+        };
+
+        self.write_node_at(page, node)?;
+        self.uncommitted.insert(page);
+        Ok(page)
+    }
+
+    // Writes `node` in place of the node at `replaced`. Only pages no commit
+    // uses can be overwritten, any other node moves to a new page.
+    fn rewrite_node(&mut self, replaced: Option<u64>, node: PagedNode) -> Result<u64> {
+        match replaced {
+            Some(page) if self.uncommitted.contains(&page) => {
+                self.write_node_at(page, node)?;
+                Ok(page)
+            }
+            _ => {
+                if let Some(page) = replaced {
+                    self.free_page(page);
+                }
+
+                self.write_node(node)
+            }
+        }
+    }
+
+    fn write_node_at(&mut self, page: u64, node: PagedNode) -> Result<()> {
+        let mut content = Vec::with_capacity(PAGE_SIZE);
+        node.encode(&mut content);
+        self.write_page(page, &content)?;
+
+        self.cache.insert(page, Rc::new(node));
+        Ok(())
+    }
+
+    fn free_page(&mut self, page: u64) {
+        self.cache.remove(page);
+
+        if self.uncommitted.remove(&page) {
+            self.free.push(page);
+        } else {
+            self.pending_free.push(page);
+        }
+    }
+
+    // Every page the newest commit does not reach is free
+    fn reclaim_free_pages(&mut self) -> Result<()> {
+        let mut used = vec![false; self.meta.page_count as usize];
+        let mut pending = self.meta.root.into_iter().collect::<Vec<_>>();
+
+        while let Some(page) = pending.pop() {
+            if used[page as usize] {
+                corrupt!("paged tree page {} is used twice", page);
+            }
+            used[page as usize] = true;
+
+            let node = self.read_node(page)?;
+            pending.extend(node.entries().into_iter().map(|(_, child)| child));
+        }
+
+        self.free = (META_PAGES..self.meta.page_count)
+            .rev()
+            .filter(|&page| !used[page as usize])
+            .collect();
+        Ok(())
+    }
+}
+
+pub struct PagedIter<'a, V: 'a> {
+    tree: &'a mut PagedRadixTree<V>,
+    // The prefix to find before the first item
+    prefix: Option<Vec<u8>>,
+    pending: Vec<(u64, usize, Option<u8>)>,
+    path: Vec<u8>,
+}
+
+impl<'a, V: FrozenValue> PagedIter<'a, V> {
+    // Walks down to the node holding every key that starts with `prefix`,
+    // leaving it on the stack
+    fn find_prefix(&mut self, prefix: &[u8]) -> Result<()> {
+        let mut current = self.tree.meta.root;
+        let mut remaining = prefix;
+
+        while let Some(page) = current {
+            let node = self.tree.read_node(page)?;
+            let own_bytes = node.own_bytes();
+
+            if remaining.len() <= own_bytes.len() {
+                if own_bytes.starts_with(remaining) {
+                    self.pending.push((page, self.path.len(), None));
+                }
+
+                break;
+            }
+
+            if !remaining.starts_with(own_bytes) {
+                break;
+            }
+
+            let next_char = remaining[own_bytes.len()];
+            current = node.child(Some(next_char));
+            self.path.extend_from_slice(own_bytes);
+            self.path.push(next_char);
+            remaining = &remaining[own_bytes.len() + 1..];
+        }
+
+        Ok(())
+    }
+
+    fn next_entry(&mut self) -> Result<Option<(Vec<u8>, V)>> {
+        if let Some(prefix) = self.prefix.take() {
+            self.find_prefix(&prefix)?;
+        }
+
+        while let Some((page, path_len, branch)) = self.pending.pop() {
+            self.path.truncate(path_len);
+            self.path.extend(branch);
+
+            let node = self.tree.read_node(page)?;
+            self.path.extend_from_slice(node.own_bytes());
+
+            match *node {
+                PagedNode::Leaf { ref value, .. } => {
+                    return Ok(Some((self.path.clone(), V::decode_value(value)?)));
+                }
+                PagedNode::Interior {
+                    empty_child,
+                    ref children,
+                    ..
+                } => {
+                    let path_len = self.path.len();
+
+                    self.pending
+                        .extend(children.iter().rev().map(|&(branch, child)| (child, path_len, Some(branch))));
+                    self.pending.extend(empty_child.map(|child| (child, path_len, None)));
+                }
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+impl<'a, V: FrozenValue> iter::Iterator for PagedIter<'a, V> {
+    type Item = Result<(Vec<u8>, V)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_entry() {
+            Ok(entry) => entry.map(Ok),
+            Err(err) => {
+                self.pending.clear();
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod paged_tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::process;
+
+    // A file in the temporary directory, removed when dropped
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str) -> Self {
+            let path = env::temp_dir().join(format!("paged_radix_tree_{}_{}.bin", name, process::id()));
+            let _ = fs::remove_file(&path);
+
+            TempFile(path)
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn entries(tree: &mut PagedRadixTree<u64>) -> Vec<(Vec<u8>, u64)> {
+        tree.iter().collect::<Result<Vec<_>>>().unwrap()
+    }
+
+    #[test]
+    fn matches_btree_map_across_reopens() {
+        let file = TempFile::new("random");
+        let mut expected = BTreeMap::new();
+        let mut tree = PagedRadixTree::open(&file.0, 8).unwrap();
+
+        // A xorshift sequence over a small alphabet, so keys share prefixes
+        // and are often prefixes of each other
+        let mut state = 0x9e37_79b9_7f4a_7c15u64;
+        for step in 0..3000 {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+
+            let key = (0..state % 6).map(|idx| b"abc"[(state >> (8 + 2 * idx)) as usize % 3]).collect::<Vec<_>>();
+            if state.is_multiple_of(3) {
+                assert_eq!(tree.remove(&key).unwrap(), expected.remove(&key));
+            } else {
+                assert_eq!(tree.insert(&key, step).unwrap(), expected.insert(key, step));
+            }
+
+            assert!(tree.cache.pages.len() <= 8);
+            if step % 500 == 499 {
+                tree.commit().unwrap();
+                tree = PagedRadixTree::open(&file.0, 8).unwrap();
+
+                assert_eq!(tree.len(), expected.len());
+                assert_eq!(entries(&mut tree), expected.clone().into_iter().collect::<Vec<_>>());
+            }
+        }
+
+        for (key, value) in expected.iter() {
+            assert_eq!(tree.get(key).unwrap(), Some(*value));
+        }
+        assert_eq!(
+            tree.prefix_iter(b"ab").collect::<Result<Vec<_>>>().unwrap(),
+            expected
+                .iter()
+                .filter(|&(key, _)| key.starts_with(b"ab"))
+                .map(|(key, value)| (key.clone(), *value))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn uncommitted_changes_are_lost() {
+        let file = TempFile::new("uncommitted");
+        {
+            let mut tree = PagedRadixTree::<u64>::open(&file.0, 4).unwrap();
+            tree.insert(b"romane", 1).unwrap();
+            tree.insert(b"romanus", 2).unwrap();
+            tree.commit().unwrap();
+
+            tree.insert(b"romulus", 3).unwrap();
+            tree.remove(b"romane").unwrap();
+        }
+
+        let mut tree = PagedRadixTree::<u64>::open(&file.0, 4).unwrap();
+        assert_eq!(entries(&mut tree), vec![(b"romane".to_vec(), 1), (b"romanus".to_vec(), 2)]);
+    }
+
+    #[test]
+    fn torn_commit_falls_back() {
+        let file = TempFile::new("torn");
+        {
+            let mut tree = PagedRadixTree::<u64>::open(&file.0, 4).unwrap();
+            tree.insert(b"rubens", 1).unwrap();
+            tree.commit().unwrap();
+            tree.insert(b"ruber", 2).unwrap();
+            tree.remove(b"rubens").unwrap();
+            tree.commit().unwrap();
+        }
+
+        // The second commit went to meta page 0, tear it
+        let mut bytes = fs::read(&file.0).unwrap();
+        bytes[PAGE_HEADER_LEN + 10] ^= 0xff;
+        fs::write(&file.0, &bytes).unwrap();
+
+        let mut tree = PagedRadixTree::<u64>::open(&file.0, 4).unwrap();
+        assert_eq!(entries(&mut tree), vec![(b"rubens".to_vec(), 1)]);
+
+        bytes[PAGE_SIZE + PAGE_HEADER_LEN + 10] ^= 0xff;
+        fs::write(&file.0, &bytes).unwrap();
+        assert!(PagedRadixTree::<u64>::open(&file.0, 4).is_err());
+    }
+
+    #[test]
+    fn pages_are_reused() {
+        let file = TempFile::new("reuse");
+        let mut tree = PagedRadixTree::open(&file.0, 16).unwrap();
+
+        for round in 0..20 {
+            for idx in 0..50u64 {
+                tree.insert(format!("key{}", idx).as_bytes(), idx + round).unwrap();
+            }
+            tree.commit().unwrap();
+        }
+
+        // Each round copies at most a handful of pages per key
+        assert!(tree.meta.page_count < 500, "{} pages", tree.meta.page_count);
+
+        let page_count = tree.meta.page_count;
+        let mut tree = PagedRadixTree::<u64>::open(&file.0, 16).unwrap();
+        assert_eq!(tree.meta.page_count, page_count);
+        assert!(!tree.free.is_empty());
+        assert_eq!(tree.get(b"key7").unwrap(), Some(26));
+    }
+
+    #[test]
+    fn oversized_entries_are_rejected() {
+        let file = TempFile::new("oversized");
+        let mut tree = PagedRadixTree::<Vec<u8>>::open(&file.0, 4).unwrap();
+
+        assert!(tree.insert(&[b'a'; MAX_KEY_LEN + 1], Vec::new()).is_err());
+        assert!(tree.insert(b"a", vec![0; MAX_VALUE_LEN + 1]).is_err());
+        assert_eq!(tree.insert(&[b'a'; MAX_KEY_LEN], vec![0; MAX_VALUE_LEN]).unwrap(), None);
+    }
+}
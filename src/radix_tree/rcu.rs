@@ -0,0 +1,749 @@
+use std::borrow::Borrow;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+
+use crossbeam_epoch::{self as epoch, Atomic, Guard, Owned, Shared};
+
+use super::shared::SharedBytes;
+use super::key::{KeyMatchResult, KeyPrefix, KeyProbe, TreeKey};
+use super::entry::KeyValue;
+
+// Nodes are never modified once they have been published. The writer copies
+// every node on the path to a change and links the copies to the untouched
+// subtrees, so an old and a new version of the tree share most of their nodes.
+struct LeafNode<K: TreeKey, V> {
+    entry: KeyValue<K, V>,
+    remaining_key: KeyPrefix,
+}
+
+struct InteriorNode<K: TreeKey, V> {
+    prefix: KeyPrefix,
+    children: Vec<(u8, Atomic<Node<K, V>>)>,
+    empty_child: Atomic<Node<K, V>>,
+}
+
+enum Node<K: TreeKey, V> {
+    Leaf(LeafNode<K, V>),
+    Interior(InteriorNode<K, V>),
+}
+
+// A child and the byte branching to it, `None` for the empty child
+type Branch<'g, K, V> = (Option<u8>, Shared<'g, Node<K, V>>);
+
+// A published version of the tree
+struct Root<K: TreeKey, V> {
+    size: usize,
+    node: Atomic<Node<K, V>>,
+}
+
+impl<K: TreeKey, V> InteriorNode<K, V> {
+    fn new(prefix: KeyPrefix) -> Self {
+        InteriorNode {
+            prefix,
+            children: Vec::new(),
+            empty_child: Atomic::null(),
+        }
+    }
+
+    fn get_child<'g>(&self, possible_prefix: Option<u8>, guard: &'g Guard) -> Shared<'g, Node<K, V>> {
+        if let Some(prefix) = possible_prefix {
+            let search_result = self.children
+                .binary_search_by(|&(ref value, _)| value.cmp(&prefix));
+
+            match search_result {
+                Ok(found_index) => self.children[found_index].1.load(Ordering::Acquire, guard),
+                Err(_) => Shared::null(),
+            }
+        } else {
+            self.empty_child.load(Ordering::Acquire, guard)
+        }
+    }
+
+    // Only used while building a node that has not been published yet. A
+    // null child removes the entry.
+    fn set_child<'g>(&mut self, possible_prefix: Option<u8>, child: Shared<'g, Node<K, V>>) {
+        if let Some(prefix) = possible_prefix {
+            let search_result = self.children
+                .binary_search_by(|&(ref value, _)| value.cmp(&prefix));
+
+            match (search_result, child.is_null()) {
+                (Ok(found_index), false) => self.children[found_index].1 = Atomic::from(child),
+                (Ok(found_index), true) => {
+                    self.children.remove(found_index);
+                }
+                (Err(insert_index), false) => self.children
+                    .insert(insert_index, (prefix, Atomic::from(child))),
+                (Err(_), true) => {}
+            }
+        } else {
+            self.empty_child = Atomic::from(child);
+        }
+    }
+
+    fn copy_with_prefix<'g>(&self, prefix: KeyPrefix, guard: &'g Guard) -> Self {
+        InteriorNode {
+            prefix,
+            children: self.children
+                .iter()
+                .map(|&(branch, ref child)| (branch, Atomic::from(child.load(Ordering::Acquire, guard))))
+                .collect(),
+            empty_child: Atomic::from(self.empty_child.load(Ordering::Acquire, guard)),
+        }
+    }
+
+    // Returns the branch and node of the only remaining entry, if there is
+    // exactly one left.
+    fn single_child<'g>(&self, guard: &'g Guard) -> Option<Branch<'g, K, V>> {
+        let empty_child = self.empty_child.load(Ordering::Acquire, guard);
+
+        match (self.children.len(), empty_child.is_null()) {
+            (0, false) => Some((None, empty_child)),
+            (1, true) => {
+                let (branch, ref child) = self.children[0];
+                Some((Some(branch), child.load(Ordering::Acquire, guard)))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl<K: TreeKey, V: Clone> Node<K, V> {
+    fn new_leaf(remaining_key: KeyPrefix, entry: KeyValue<K, V>) -> Self {
+        Node::Leaf(LeafNode {
+            entry,
+            remaining_key,
+        })
+    }
+
+    fn copy_with_prefix<'g>(&self, prefix: KeyPrefix, guard: &'g Guard) -> Self {
+        match *self {
+            Node::Leaf(ref leaf) => Node::new_leaf(prefix, leaf.entry.clone()),
+            Node::Interior(ref interior) => Node::Interior(interior.copy_with_prefix(prefix, guard)),
+        }
+    }
+
+    fn prefix(&self) -> &KeyPrefix {
+        match *self {
+            Node::Leaf(ref leaf) => &leaf.remaining_key,
+            Node::Interior(ref interior) => &interior.prefix,
+        }
+    }
+}
+
+fn publish<'g, K: TreeKey, V>(node: Node<K, V>, guard: &'g Guard) -> Shared<'g, Node<K, V>> {
+    Owned::new(node).into_shared(guard)
+}
+
+// An interior node on the path to a change and the branch taken from it
+type PathStep<'g, K, V> = (Shared<'g, Node<K, V>>, &'g InteriorNode<K, V>, Option<u8>);
+
+// Builds the replacement for `root` with the entry inserted. Every node that
+// is replaced by a copy is pushed onto `retired`. The walk down keeps the
+// path on a stack and the copies are made on the way back up, so a tree as
+// deep as its longest key does not recurse per level.
+fn persistent_insert<'g, K: TreeKey, V: Clone>(
+    root: Shared<'g, Node<K, V>>,
+    mut probe: KeyProbe,
+    new_entry: KeyValue<K, V>,
+    retired: &mut Vec<Shared<'g, Node<K, V>>>,
+    guard: &'g Guard,
+) -> (Shared<'g, Node<K, V>>, Option<V>) {
+    let mut path: Vec<PathStep<'g, K, V>> = Vec::new();
+    let mut current = root;
+
+    let (mut updated, old_value) = loop {
+        let node = match unsafe { current.as_ref() } {
+            Some(node) => node,
+            None => break (publish(Node::new_leaf(From::from(probe), new_entry), guard), None),
+        };
+
+        let (replacement, old_value) = match node.prefix().match_with(probe) {
+            KeyMatchResult::Complete => match *node {
+                Node::Leaf(ref leaf) => {
+                    let entry = KeyValue::new(leaf.entry.key().clone(), new_entry.take_value());
+
+                    (
+                        Node::new_leaf(leaf.remaining_key.clone(), entry),
+                        Some(leaf.entry.value().clone()),
+                    )
+                }
+                Node::Interior(ref interior) => {
+                    path.push((current, interior, None));
+                    current = interior.get_child(None, guard);
+                    probe = KeyProbe::empty();
+                    continue;
+                }
+            },
+            KeyMatchResult::Partial(mut remaining_probe) => {
+                let next_char = remaining_probe
+                    .pop()
+                    .expect(concat!(file!(), ": ", line!()));
+
+                match *node {
+                    Node::Leaf(ref leaf) => {
+                        let mut new_interior = InteriorNode::new(leaf.remaining_key.clone());
+                        let old_leaf = node.copy_with_prefix(KeyPrefix::empty(), guard);
+                        let new_leaf = Node::new_leaf(From::from(remaining_probe), new_entry);
+
+                        new_interior.set_child(None, publish(old_leaf, guard));
+                        new_interior.set_child(Some(next_char), publish(new_leaf, guard));
+
+                        (Node::Interior(new_interior), None)
+                    }
+                    Node::Interior(ref interior) => {
+                        path.push((current, interior, Some(next_char)));
+                        current = interior.get_child(Some(next_char), guard);
+                        probe = remaining_probe;
+                        continue;
+                    }
+                }
+            }
+            KeyMatchResult::LongerPrefix(split_index) => {
+                let (common, mut difference) = node.prefix().clone().split_at(split_index);
+                let next_char_old = difference
+                    .pop()
+                    .expect(concat!(file!(), ": ", line!()));
+
+                let mut new_interior = InteriorNode::new(common);
+                let old_node = node.copy_with_prefix(difference, guard);
+                let new_leaf = Node::new_leaf(KeyPrefix::empty(), new_entry);
+
+                new_interior.set_child(None, publish(new_leaf, guard));
+                new_interior.set_child(Some(next_char_old), publish(old_node, guard));
+
+                (Node::Interior(new_interior), None)
+            }
+            KeyMatchResult::Incomplete(split_index, mut remaining_probe) => {
+                let (common, mut difference) = node.prefix().clone().split_at(split_index);
+                let next_char_old = difference
+                    .pop()
+                    .expect(concat!(file!(), ": ", line!()));
+                let next_char_new = remaining_probe
+                    .pop()
+                    .expect(concat!(file!(), ": ", line!()));
+
+                let mut new_interior = InteriorNode::new(common);
+                let old_node = node.copy_with_prefix(difference, guard);
+                let new_leaf = Node::new_leaf(From::from(remaining_probe), new_entry);
+
+                new_interior.set_child(Some(next_char_old), publish(old_node, guard));
+                new_interior.set_child(Some(next_char_new), publish(new_leaf, guard));
+
+                (Node::Interior(new_interior), None)
+            }
+        };
+
+        retired.push(current);
+        break (publish(replacement, guard), old_value);
+    };
+
+    while let Some((current, interior, branch)) = path.pop() {
+        let mut copy = interior.copy_with_prefix(interior.prefix.clone(), guard);
+        copy.set_child(branch, updated);
+        retired.push(current);
+
+        updated = publish(Node::Interior(copy), guard);
+    }
+
+    (updated, old_value)
+}
+
+// Builds the replacement for `root` with the entry removed, or returns `root`
+// itself when the key is not present. Interior nodes that are left with a
+// single entry are merged with it. Walks down and back up like
+// `persistent_insert`.
+fn persistent_remove<'g, K: TreeKey, V: Clone>(
+    root: Shared<'g, Node<K, V>>,
+    mut probe: KeyProbe,
+    retired: &mut Vec<Shared<'g, Node<K, V>>>,
+    guard: &'g Guard,
+) -> (Shared<'g, Node<K, V>>, Option<V>) {
+    let mut path: Vec<PathStep<'g, K, V>> = Vec::new();
+    let mut current = root;
+
+    let (removed, old_value) = loop {
+        let interior = match unsafe { current.as_ref() } {
+            None => return (root, None),
+            Some(&Node::Leaf(ref leaf)) => match leaf.remaining_key.match_with(probe) {
+                KeyMatchResult::Complete => break (current, leaf.entry.value().clone()),
+                _ => return (root, None),
+            },
+            Some(&Node::Interior(ref interior)) => interior,
+        };
+
+        let branch = match interior.prefix.match_with(probe) {
+            KeyMatchResult::Complete => {
+                probe = KeyProbe::empty();
+                None
+            }
+            KeyMatchResult::Partial(mut remaining_probe) => {
+                let next_char = remaining_probe
+                    .pop()
+                    .expect(concat!(file!(), ": ", line!()));
+                probe = remaining_probe;
+                Some(next_char)
+            }
+            _ => return (root, None),
+        };
+
+        path.push((current, interior, branch));
+        current = interior.get_child(branch, guard);
+    };
+
+    retired.push(removed);
+    let mut updated = Shared::null();
+
+    while let Some((current, interior, branch)) = path.pop() {
+        let mut copy = interior.copy_with_prefix(interior.prefix.clone(), guard);
+        copy.set_child(branch, updated);
+        retired.push(current);
+
+        let replacement = match copy.single_child(guard) {
+            Some((remaining_branch, remaining)) => {
+                let remaining_node = unsafe { remaining.deref() };
+                let merged_prefix = copy.prefix.join(remaining_branch, remaining_node.prefix());
+                retired.push(remaining);
+
+                remaining_node.copy_with_prefix(merged_prefix, guard)
+            }
+            None => Node::Interior(copy),
+        };
+
+        updated = publish(replacement, guard);
+    }
+
+    (updated, Some(old_value))
+}
+
+struct RcuShared<K: TreeKey, V> {
+    root: Atomic<Root<K, V>>,
+}
+
+impl<K: TreeKey, V> Drop for RcuShared<K, V> {
+    fn drop(&mut self) {
+        unsafe {
+            let guard = epoch::unprotected();
+            let root = self.root.load(Ordering::Relaxed, guard);
+
+            destroy_subtree(root.deref().node.load(Ordering::Relaxed, guard), guard);
+            drop(root.into_owned());
+        }
+    }
+}
+
+// Only safe to call once no other thread can access the tree. Walks with an
+// explicit stack, so a tree as deep as its longest key is freed without
+// recursing per level.
+unsafe fn destroy_subtree<'g, K: TreeKey, V>(root: Shared<'g, Node<K, V>>, guard: &'g Guard) {
+    let mut pending = vec![root];
+
+    while let Some(current) = pending.pop() {
+        if current.is_null() {
+            continue;
+        }
+
+        if let Node::Interior(ref interior) = *current.deref() {
+            pending.push(interior.empty_child.load(Ordering::Relaxed, guard));
+            pending.extend(interior.children.iter().map(|&(_, ref child)| child.load(Ordering::Relaxed, guard)));
+        }
+
+        drop(current.into_owned());
+    }
+}
+
+impl<K, V> RcuShared<K, V>
+where
+    K: TreeKey + Send + Sync + 'static,
+    V: Send + Sync + 'static,
+{
+    fn read(&self) -> RcuSnapshot<K, V> {
+        let guard = epoch::pin();
+        let root = unsafe { self.root.load(Ordering::Acquire, &guard).deref() };
+
+        RcuSnapshot {
+            size: root.size,
+            root: root.node.load(Ordering::Acquire, &guard).as_raw(),
+            guard,
+        }
+    }
+}
+
+// A radix tree with a single writer and any number of readers. The writer
+// changes copies of the nodes on the path to a key and then publishes a new
+// root in one atomic store. Readers never block or lock, and keep seeing the
+// tree as it was published when they took their snapshot. Replaced nodes are
+// reclaimed once every snapshot that could reach them has been dropped.
+pub struct RcuRadixTree<K: TreeKey, V> {
+    shared: Arc<RcuShared<K, V>>,
+}
+
+// A handle for reading an `RcuRadixTree` from other threads, cloned for each
+pub struct RcuReader<K: TreeKey, V> {
+    shared: Arc<RcuShared<K, V>>,
+}
+
+// The tree as it was when the snapshot was taken. Holding one keeps the
+// nodes the writer has since replaced from being reclaimed, so snapshots
+// should not be kept longer than needed.
+pub struct RcuSnapshot<K: TreeKey, V> {
+    size: usize,
+    root: *const Node<K, V>,
+    guard: Guard,
+}
+
+impl<K, V> RcuRadixTree<K, V>
+where
+    K: TreeKey + Send + Sync + 'static,
+    V: Clone + Send + Sync + 'static,
+{
+    pub fn new() -> Self {
+        let root = Root {
+            size: 0,
+            node: Atomic::null(),
+        };
+
+        RcuRadixTree {
+            shared: Arc::new(RcuShared {
+                root: Atomic::new(root),
+            }),
+        }
+    }
+
+    pub fn reader(&self) -> RcuReader<K, V> {
+        RcuReader {
+            shared: self.shared.clone(),
+        }
+    }
+
+    pub fn read(&self) -> RcuSnapshot<K, V> {
+        self.shared.read()
+    }
+
+    pub fn len(&self) -> usize {
+        self.read().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let guard = &epoch::pin();
+        let root = unsafe { self.shared.root.load(Ordering::Acquire, guard).deref() };
+        let mut retired = Vec::new();
+
+        let (updated, old_value) = {
+            let shared_key = SharedBytes::copy_from_slice(key.as_bytes());
+            let probe = KeyProbe::shared(&shared_key);
+            let new_entry = KeyValue::new(key.clone(), value);
+
+            persistent_insert(
+                root.node.load(Ordering::Acquire, guard),
+                probe,
+                new_entry,
+                &mut retired,
+                guard,
+            )
+        };
+
+        let size = if old_value.is_none() {
+            root.size + 1
+        } else {
+            root.size
+        };
+        self.publish(size, updated, retired, guard);
+
+        old_value
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: TreeKey,
+    {
+        let guard = &epoch::pin();
+        let root = unsafe { self.shared.root.load(Ordering::Acquire, guard).deref() };
+        let mut retired = Vec::new();
+
+        let (updated, old_value) = persistent_remove(
+            root.node.load(Ordering::Acquire, guard),
+            KeyProbe::new(key),
+            &mut retired,
+            guard,
+        );
+
+        if old_value.is_some() {
+            self.publish(root.size - 1, updated, retired, guard);
+        }
+
+        old_value
+    }
+
+    fn publish<'g>(
+        &mut self,
+        size: usize,
+        node: Shared<'g, Node<K, V>>,
+        retired: Vec<Shared<'g, Node<K, V>>>,
+        guard: &'g Guard,
+    ) {
+        let new_root = Owned::new(Root {
+            size,
+            node: Atomic::from(node),
+        });
+        let old_root = self.shared.root.swap(new_root, Ordering::AcqRel, guard);
+
+        unsafe {
+            guard.defer_destroy(old_root);
+
+            for node in retired {
+                guard.defer_destroy(node);
+            }
+        }
+    }
+}
+
+impl<K, V> RcuReader<K, V>
+where
+    K: TreeKey + Send + Sync + 'static,
+    V: Send + Sync + 'static,
+{
+    pub fn read(&self) -> RcuSnapshot<K, V> {
+        self.shared.read()
+    }
+}
+
+impl<K: TreeKey, V> Clone for RcuReader<K, V> {
+    fn clone(&self) -> Self {
+        RcuReader {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<K: TreeKey, V> RcuSnapshot<K, V> {
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: TreeKey + AsRef<[u8]>,
+    {
+        self.get(key).is_some()
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: TreeKey + AsRef<[u8]>,
+    {
+        let mut probe = KeyProbe::new(&key);
+        let mut current = self.root_node();
+
+        loop {
+            let interior = match current {
+                None => return None,
+                Some(&Node::Leaf(ref leaf)) => {
+                    return match leaf.remaining_key.match_with(probe) {
+                        KeyMatchResult::Complete => Some(leaf.entry.value()),
+                        _ => None,
+                    }
+                }
+                Some(&Node::Interior(ref interior)) => interior,
+            };
+
+            let branch = match interior.prefix.match_with(probe) {
+                KeyMatchResult::Complete => {
+                    probe = KeyProbe::empty();
+                    None
+                }
+                KeyMatchResult::Partial(mut remaining_probe) => {
+                    let next_char = remaining_probe
+                        .pop()
+                        .expect(concat!(file!(), ": ", line!()));
+                    probe = remaining_probe;
+                    Some(next_char)
+                }
+                _ => return None,
+            };
+
+            current = unsafe { interior.get_child(branch, &self.guard).as_ref() };
+        }
+    }
+
+    pub fn iter<'s>(&'s self) -> SnapshotIter<'s, K, V> {
+        SnapshotIter::new(self.root_node())
+    }
+
+    // Yields every entry whose key starts with `prefix`, in key order
+    pub fn prefix_iter<'s, Q>(&'s self, prefix: &Q) -> SnapshotIter<'s, K, V>
+    where
+        Q: TreeKey,
+    {
+        let mut probe = KeyProbe::new(prefix);
+        let mut current = self.root_node();
+
+        loop {
+            let interior = match current {
+                None => return SnapshotIter::new(None),
+                Some(&Node::Leaf(ref leaf)) => {
+                    return match leaf.remaining_key.match_with(probe) {
+                        KeyMatchResult::Complete | KeyMatchResult::LongerPrefix(_) => {
+                            SnapshotIter::new(current)
+                        }
+                        _ => SnapshotIter::new(None),
+                    }
+                }
+                Some(&Node::Interior(ref interior)) => interior,
+            };
+
+            match interior.prefix.match_with(probe) {
+                KeyMatchResult::Complete | KeyMatchResult::LongerPrefix(_) => {
+                    return SnapshotIter::new(current)
+                }
+                KeyMatchResult::Partial(mut remaining_probe) => {
+                    let next_char = remaining_probe
+                        .pop()
+                        .expect(concat!(file!(), ": ", line!()));
+                    probe = remaining_probe;
+                    current = unsafe { interior.get_child(Some(next_char), &self.guard).as_ref() };
+                }
+                KeyMatchResult::Incomplete(..) => return SnapshotIter::new(None),
+            }
+        }
+    }
+
+    // The guard held by the snapshot keeps every node reachable from the
+    // root alive for as long as the snapshot exists
+    fn root_node(&self) -> Option<&Node<K, V>> {
+        unsafe { self.root.as_ref() }
+    }
+}
+
+pub struct SnapshotIter<'s, K: 's + TreeKey, V: 's> {
+    stack: Vec<&'s Node<K, V>>,
+    _snapshot: PhantomData<&'s RcuSnapshot<K, V>>,
+}
+
+impl<'s, K: 's + TreeKey, V: 's> SnapshotIter<'s, K, V> {
+    fn new(start: Option<&'s Node<K, V>>) -> Self {
+        SnapshotIter {
+            stack: start.into_iter().collect(),
+            _snapshot: PhantomData,
+        }
+    }
+}
+
+impl<'s, K: 's + TreeKey, V: 's> Iterator for SnapshotIter<'s, K, V> {
+    type Item = (&'s K, &'s V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(node) = self.stack.pop() {
+            match *node {
+                Node::Leaf(ref leaf) => return Some((leaf.entry.key(), leaf.entry.value())),
+                Node::Interior(ref interior) => {
+                    // The nodes stay alive for 's through the snapshot's guard
+                    let guard = unsafe { epoch::unprotected() };
+
+                    for &(_, ref child) in interior.children.iter().rev() {
+                        if let Some(child) = unsafe { child.load(Ordering::Acquire, guard).as_ref() } {
+                            self.stack.push(child);
+                        }
+                    }
+
+                    if let Some(child) = unsafe { interior.empty_child.load(Ordering::Acquire, guard).as_ref() } {
+                        self.stack.push(child);
+                    }
+                }
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod rcu_tree_tests {
+    use super::*;
+
+    #[test]
+    fn create_tree() {
+        let rax = RcuRadixTree::<String, ()>::new();
+
+        assert!(rax.is_empty());
+        assert_eq!(rax.read().iter().count(), 0);
+    }
+
+    #[test]
+    fn insert_retrieve_remove() {
+        let mut rax = RcuRadixTree::<&str, usize>::new();
+
+        assert_eq!(rax.insert("hello", 1), None);
+        assert_eq!(rax.insert("he", 2), None);
+        assert_eq!(rax.insert("help", 3), None);
+        assert_eq!(rax.insert("hello", 4), Some(1));
+        assert_eq!(rax.len(), 3);
+
+        {
+            let snapshot = rax.read();
+            assert_eq!(snapshot.get(&"hello"), Some(&4));
+            assert_eq!(snapshot.get(&"he"), Some(&2));
+            assert_eq!(snapshot.get(&"help"), Some(&3));
+            assert_eq!(snapshot.get(&"hel"), None);
+        }
+
+        assert_eq!(rax.remove(&"he"), Some(2));
+        assert_eq!(rax.remove(&"he"), None);
+        assert_eq!(rax.remove(&"help"), Some(3));
+        assert_eq!(rax.len(), 1);
+        assert_eq!(rax.read().get(&"hello"), Some(&4));
+    }
+
+    #[test]
+    fn snapshot_is_isolated_from_writes() {
+        let mut rax = RcuRadixTree::<&str, usize>::new();
+        let reader = rax.reader();
+
+        rax.insert("hello", 1);
+        let before = reader.read();
+
+        rax.insert("help", 2);
+        rax.remove(&"hello");
+        let after = reader.read();
+
+        assert_eq!(before.len(), 1);
+        assert_eq!(before.get(&"hello"), Some(&1));
+        assert_eq!(before.get(&"help"), None);
+
+        assert_eq!(after.len(), 1);
+        assert_eq!(after.get(&"hello"), None);
+        assert_eq!(after.get(&"help"), Some(&2));
+    }
+
+    #[test]
+    fn ordered_prefix_iteration() {
+        let mut rax = RcuRadixTree::<&str, usize>::new();
+
+        for (idx, word) in ["hello", "he", "help", "hallo", "world", "hel"].iter().enumerate() {
+            rax.insert(word, idx);
+        }
+
+        let snapshot = rax.read();
+        let all = snapshot.iter().map(|(key, _)| *key).collect::<Vec<_>>();
+        assert_eq!(all, vec!["hallo", "he", "hel", "hello", "help", "world"]);
+
+        let prefixed = snapshot.prefix_iter(&"hel").map(|(key, _)| *key).collect::<Vec<_>>();
+        assert_eq!(prefixed, vec!["hel", "hello", "help"]);
+
+        assert_eq!(snapshot.prefix_iter(&"h").count(), 5);
+        assert_eq!(snapshot.prefix_iter(&"wo").count(), 1);
+        assert_eq!(snapshot.prefix_iter(&"hex").count(), 0);
+        assert_eq!(snapshot.prefix_iter(&"").count(), 6);
+    }
+}
@@ -0,0 +1,522 @@
+use core::fmt;
+use core::iter;
+use core::mem;
+use core::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use core::ops::Bound;
+
+use allocator_api2::boxed::Box;
+
+use super::alphabet::{BitAlphabet, KeyAlphabet};
+use super::shared::SharedBytes;
+use super::key::{KeyPrefix, KeyProbe};
+use super::node::{RadixNode, LeafWalker, iterative_insert, iterative_find, iterative_remove, iterative_drop};
+use super::entry::LeafEntry;
+
+macro_rules! ip_net {
+    ($name:ident, $variant:ident, $addr:ty, $bits:ty) => {
+        // A network, the first `prefix_len` bits of an address. The host
+        // bits are cleared on creation, so equal networks compare equal.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub struct $name {
+            addr: $addr,
+            prefix_len: u8,
+        }
+
+        impl $name {
+            const MAX_PREFIX_LEN: u8 = (mem::size_of::<$bits>() * 8) as u8;
+
+            // Fails when `prefix_len` is over the bits of the address
+            pub fn new(addr: $addr, prefix_len: u8) -> ::error::Result<Self> {
+                if prefix_len > Self::MAX_PREFIX_LEN {
+                    return Err(::error::Error::InvalidPrefixLength {
+                        prefix_len,
+                        max: Self::MAX_PREFIX_LEN,
+                    });
+                }
+
+                let mask = <$bits>::MAX
+                    .checked_shl(u32::from(Self::MAX_PREFIX_LEN - prefix_len))
+                    .unwrap_or(0);
+
+                Ok($name {
+                    addr: <$addr>::from(<$bits>::from(addr) & mask),
+                    prefix_len,
+                })
+            }
+
+            // The network holding just `addr`
+            pub fn host(addr: $addr) -> Self {
+                $name {
+                    addr,
+                    prefix_len: Self::MAX_PREFIX_LEN,
+                }
+            }
+
+            pub fn addr(&self) -> $addr {
+                self.addr
+            }
+
+            pub fn prefix_len(&self) -> u8 {
+                self.prefix_len
+            }
+
+            // Whether every address of `other` is in this network, which
+            // includes the network itself
+            pub fn contains(&self, other: &$name) -> bool {
+                other.prefix_len >= self.prefix_len
+                    && $name::new(other.addr, self.prefix_len).expect(concat!(file!(), ": ", line!())) == *self
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "{}/{}", self.addr, self.prefix_len)
+            }
+        }
+
+        impl From<$name> for IpNet {
+            fn from(net: $name) -> Self {
+                IpNet::$variant(net)
+            }
+        }
+    };
+}
+
+ip_net!(Ipv4Net, V4, Ipv4Addr, u32);
+ip_net!(Ipv6Net, V6, Ipv6Addr, u128);
+
+// A network of either family. Every IPv4 network sorts before every IPv6
+// network.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum IpNet {
+    V4(Ipv4Net),
+    V6(Ipv6Net),
+}
+
+impl IpNet {
+    pub fn addr(&self) -> IpAddr {
+        match *self {
+            IpNet::V4(ref net) => IpAddr::V4(net.addr()),
+            IpNet::V6(ref net) => IpAddr::V6(net.addr()),
+        }
+    }
+
+    pub fn prefix_len(&self) -> u8 {
+        match *self {
+            IpNet::V4(ref net) => net.prefix_len(),
+            IpNet::V6(ref net) => net.prefix_len(),
+        }
+    }
+
+    // Networks of different families never contain each other
+    pub fn contains(&self, other: &IpNet) -> bool {
+        match (*self, *other) {
+            (IpNet::V4(ref net), IpNet::V4(ref other)) => net.contains(other),
+            (IpNet::V6(ref net), IpNet::V6(ref other)) => net.contains(other),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for IpNet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            IpNet::V4(ref net) => net.fmt(f),
+            IpNet::V6(ref net) => net.fmt(f),
+        }
+    }
+}
+
+// The network holding just `addr`
+impl From<IpAddr> for IpNet {
+    fn from(addr: IpAddr) -> Self {
+        match addr {
+            IpAddr::V4(addr) => IpNet::V4(Ipv4Net::host(addr)),
+            IpAddr::V6(addr) => IpNet::V6(Ipv6Net::host(addr)),
+        }
+    }
+}
+
+// The longest key, the family symbol and the bits of an IPv6 address
+const MAX_KEY_LEN: usize = 1 + 128;
+
+// The key of a network in the tree, a symbol for the family followed by one
+// symbol for each bit of the prefix. Routes are keyed by exactly their
+// prefix bits, so they split and compress at any bit rather than at byte
+// boundaries.
+struct RouteKey {
+    symbols: [u8; MAX_KEY_LEN],
+    len: usize,
+}
+
+impl RouteKey {
+    fn new(net: &IpNet) -> Self {
+        let mut symbols = [0; MAX_KEY_LEN];
+
+        match *net {
+            IpNet::V4(ref net) => BitAlphabet::expand_into(&net.addr().octets(), &mut symbols[1..33]),
+            IpNet::V6(ref net) => {
+                symbols[0] = 1;
+                BitAlphabet::expand_into(&net.addr().octets(), &mut symbols[1..])
+            }
+        }
+
+        RouteKey {
+            symbols,
+            len: 1 + net.prefix_len() as usize,
+        }
+    }
+
+    fn symbols(&self) -> &[u8] {
+        &self.symbols[..self.len]
+    }
+}
+
+// The entry of a route, the network is kept beside the value so lookups can
+// return it
+struct Route<V> {
+    net: IpNet,
+    value: V,
+}
+
+impl<V> Route<V> {
+    fn pair(&self) -> (IpNet, &V) {
+        (self.net, &self.value)
+    }
+}
+
+impl<V> LeafEntry for Route<V> {
+    type Value = V;
+
+    fn value(&self) -> &V {
+        &self.value
+    }
+
+    fn value_mut(&mut self) -> &mut V {
+        &mut self.value
+    }
+
+    fn take_value(self) -> V {
+        self.value
+    }
+
+    fn swap_value(&mut self, new_value: V) -> V {
+        mem::replace(&mut self.value, new_value)
+    }
+}
+
+type RouteNode<V> = RadixNode<Box<Route<V>>>;
+
+// A routing table mapping IPv4 and IPv6 networks to values, looked up by
+// longest prefix match. Both families share one tree, split by the first
+// symbol of every key.
+pub struct IpRoutingTable<V> {
+    size: usize,
+    root: Option<Box<RouteNode<V>>>,
+}
+
+impl<V> IpRoutingTable<V> {
+    pub fn new() -> Self {
+        IpRoutingTable { size: 0, root: None }
+    }
+
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    pub fn clear(&mut self) {
+        if let Some(root) = self.root.take() {
+            iterative_drop(root);
+        }
+
+        self.size = 0;
+    }
+
+    pub fn insert<N: Into<IpNet>>(&mut self, net: N, value: V) -> Option<V> {
+        let net = net.into();
+        let shared_key = SharedBytes::copy_from_slice(RouteKey::new(&net).symbols());
+        let new_entry = Box::new(Route { net, value });
+
+        let old_entry = if let Some(ref mut root) = self.root {
+            iterative_insert(root, KeyProbe::shared(&shared_key), new_entry)
+        } else {
+            let new_leaf = RadixNode::new_leaf(KeyPrefix::from(KeyProbe::shared(&shared_key)), new_entry);
+            self.root = Some(Box::new(new_leaf));
+            None
+        };
+
+        if old_entry.is_none() {
+            self.size += 1;
+        }
+
+        old_entry
+    }
+
+    // The value of exactly this network, without looking at shorter
+    // prefixes
+    pub fn get<N: Into<IpNet>>(&self, net: N) -> Option<&V> {
+        let key = RouteKey::new(&net.into());
+
+        self.root.as_ref().and_then(|root| iterative_find(root, KeyProbe::from_bytes(key.symbols())))
+    }
+
+    pub fn remove<N: Into<IpNet>>(&mut self, net: N) -> Option<V> {
+        let key = RouteKey::new(&net.into());
+        let old_entry = iterative_remove(&mut self.root, KeyProbe::from_bytes(key.symbols()));
+
+        if old_entry.is_some() {
+            self.size -= 1;
+        }
+
+        old_entry
+    }
+
+    // The most specific route containing `addr`
+    pub fn longest_match<T: Into<IpAddr>>(&self, addr: T) -> Option<(IpNet, &V)> {
+        self.covering(IpNet::from(addr.into())).last()
+    }
+
+    // The routes containing `net`, including `net` itself, from the shortest
+    // prefix to the longest
+    pub fn covering<'a, N: Into<IpNet>>(&'a self, net: N) -> Covering<'a, V> {
+        Covering {
+            next: self.root.as_deref(),
+            key: RouteKey::new(&net.into()),
+            matched: 0,
+        }
+    }
+
+    // The routes inside `net`, including `net` itself, in address order
+    pub fn covered_by<'a, N: Into<IpNet>>(&'a self, net: N) -> RouteIter<'a, V> {
+        let net = net.into();
+        let key = RouteKey::new(&net);
+
+        RouteIter {
+            walker: LeafWalker::seek(self.root.as_ref(), Bound::Included(key.symbols()), false),
+            within: Some(net),
+        }
+    }
+
+    // Every route in address order, IPv4 first
+    pub fn iter<'a>(&'a self) -> RouteIter<'a, V> {
+        RouteIter {
+            walker: LeafWalker::new(self.root.as_ref(), false),
+            within: None,
+        }
+    }
+}
+
+impl<V> Drop for IpRoutingTable<V> {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+impl<V> Default for IpRoutingTable<V> {
+    fn default() -> Self {
+        IpRoutingTable::new()
+    }
+}
+
+impl<V: fmt::Debug> fmt::Debug for IpRoutingTable<V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+// Walks down the path to a network, returning each route met on the way.
+// Those are the empty children of interior nodes the path passes through,
+// and the leaf it ends at when the leaf key starts the rest of the path.
+pub struct Covering<'a, V: 'a> {
+    next: Option<&'a RouteNode<V>>,
+    key: RouteKey,
+    matched: usize,
+}
+
+impl<'a, V: 'a> iter::Iterator for Covering<'a, V> {
+    type Item = (IpNet, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(node) = self.next.take() {
+            let remaining = &self.key.symbols()[self.matched..];
+
+            match *node {
+                RadixNode::Leaf(ref leaf) => {
+                    if remaining.starts_with(leaf.remaining_key().bytes()) {
+                        return Some(leaf.entry().pair());
+                    }
+                }
+                RadixNode::Interior(ref interior) => {
+                    let prefix = interior.prefix().bytes();
+                    if !remaining.starts_with(prefix) {
+                        return None;
+                    }
+
+                    self.matched += prefix.len();
+                    let children = interior.children();
+
+                    if let Some(&symbol) = self.key.symbols().get(self.matched) {
+                        self.next = children.get_child(Some(symbol)).map(|child| &**child);
+                        self.matched += 1;
+                    }
+
+                    if let Some(empty_child) = children.get_child(None) {
+                        return Some(empty_child.get_leaf().entry().pair());
+                    }
+                }
+            }
+        }
+
+        None
+    }
+}
+
+// Iterates over routes in address order, a network before the networks
+// inside it
+pub struct RouteIter<'a, V: 'a> {
+    walker: LeafWalker<'a, Box<Route<V>>>,
+    // Routes inside a network are next to each other in the walk, so the
+    // first route outside it ends the iteration
+    within: Option<IpNet>,
+}
+
+impl<'a, V: 'a> iter::Iterator for RouteIter<'a, V> {
+    type Item = (IpNet, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.walker.next_leaf() {
+            Some(leaf) if self.within.is_none_or(|net| net.contains(&leaf.entry().net)) => {
+                Some(leaf.entry().pair())
+            }
+            Some(_) => {
+                self.walker.finish();
+                None
+            }
+            None => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod ip_routing_table_tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    fn v4(net: &str) -> IpNet {
+        let (addr, prefix_len) = net.split_at(net.find('/').unwrap());
+
+        Ipv4Net::new(addr.parse().unwrap(), prefix_len[1..].parse().unwrap()).unwrap().into()
+    }
+
+    fn v6(net: &str) -> IpNet {
+        let (addr, prefix_len) = net.split_at(net.find('/').unwrap());
+
+        Ipv6Net::new(addr.parse().unwrap(), prefix_len[1..].parse().unwrap()).unwrap().into()
+    }
+
+    fn nets<'a, I: Iterator<Item = (IpNet, &'a usize)>>(routes: I) -> Vec<String> {
+        routes.map(|(net, _)| net.to_string()).collect()
+    }
+
+    fn sample_table() -> IpRoutingTable<usize> {
+        let mut table = IpRoutingTable::new();
+
+        for (idx, net) in ["0.0.0.0/0", "10.0.0.0/8", "10.1.0.0/16", "10.1.2.0/24", "10.1.3.0/24", "192.168.0.0/20"]
+            .iter()
+            .enumerate()
+        {
+            assert_eq!(table.insert(v4(net), idx), None);
+        }
+        table.insert(v6("2001:db8::/32"), 6);
+        table.insert(v6("2001:db8:1::/48"), 7);
+
+        table
+    }
+
+    #[test]
+    fn networks_clear_host_bits() {
+        assert_eq!(v4("10.1.2.3/12").to_string(), "10.0.0.0/12");
+        assert_eq!(v4("10.1.2.3/32").to_string(), "10.1.2.3/32");
+        assert_eq!(v6("2001:db8:ffff::1/33").to_string(), "2001:db8:8000::/33");
+
+        match Ipv4Net::new(Ipv4Addr::new(10, 0, 0, 0), 33) {
+            Err(::error::Error::InvalidPrefixLength { prefix_len: 33, max: 32 }) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(Ipv6Net::new(Ipv6Addr::LOCALHOST, 129).is_err());
+
+        assert!(v4("10.0.0.0/8").contains(&v4("10.1.0.0/16")));
+        assert!(v4("10.0.0.0/8").contains(&v4("10.0.0.0/8")));
+        assert!(!v4("10.1.0.0/16").contains(&v4("10.0.0.0/8")));
+        assert!(!v4("0.0.0.0/0").contains(&v6("::/0")));
+    }
+
+    #[test]
+    fn longest_match_picks_most_specific() {
+        let table = sample_table();
+        let longest = |addr: &str| {
+            table
+                .longest_match(addr.parse::<IpAddr>().unwrap())
+                .map(|(net, &value)| (net.to_string(), value))
+        };
+
+        assert_eq!(longest("10.1.2.200"), Some(("10.1.2.0/24".to_string(), 3)));
+        assert_eq!(longest("10.1.4.1"), Some(("10.1.0.0/16".to_string(), 2)));
+        assert_eq!(longest("10.200.0.1"), Some(("10.0.0.0/8".to_string(), 1)));
+        assert_eq!(longest("192.168.15.255"), Some(("192.168.0.0/20".to_string(), 5)));
+        assert_eq!(longest("192.168.16.0"), Some(("0.0.0.0/0".to_string(), 0)));
+        assert_eq!(longest("2001:db8:1::5"), Some(("2001:db8:1::/48".to_string(), 7)));
+        assert_eq!(longest("2001:db8:2::5"), Some(("2001:db8::/32".to_string(), 6)));
+        assert_eq!(longest("2001:db9::1"), None);
+        assert_eq!(table.longest_match(Ipv4Addr::new(10, 1, 3, 9)).map(|(_, &value)| value), Some(4));
+    }
+
+    #[test]
+    fn covering_and_covered_by() {
+        let table = sample_table();
+
+        assert_eq!(
+            nets(table.covering(v4("10.1.2.0/25"))),
+            vec!["0.0.0.0/0", "10.0.0.0/8", "10.1.0.0/16", "10.1.2.0/24"]
+        );
+        assert_eq!(nets(table.covering(v4("10.1.0.0/16"))), vec!["0.0.0.0/0", "10.0.0.0/8", "10.1.0.0/16"]);
+        assert_eq!(nets(table.covering(v4("10.0.0.0/7"))), vec!["0.0.0.0/0"]);
+        assert_eq!(nets(table.covering(v6("2001:db8::/31"))), Vec::<String>::new());
+
+        assert_eq!(
+            nets(table.covered_by(v4("10.0.0.0/8"))),
+            vec!["10.0.0.0/8", "10.1.0.0/16", "10.1.2.0/24", "10.1.3.0/24"]
+        );
+        assert_eq!(nets(table.covered_by(v4("10.1.2.0/23"))), vec!["10.1.2.0/24", "10.1.3.0/24"]);
+        assert_eq!(nets(table.covered_by(v4("192.168.0.0/16"))), vec!["192.168.0.0/20"]);
+        assert_eq!(nets(table.covered_by(v4("172.16.0.0/12"))), Vec::<String>::new());
+        assert_eq!(nets(table.covered_by(v6("::/0"))), vec!["2001:db8::/32", "2001:db8:1::/48"]);
+        assert_eq!(table.covered_by(v4("0.0.0.0/0")).count(), 6);
+        assert_eq!(table.iter().count(), table.len());
+    }
+
+    #[test]
+    fn insert_get_remove() {
+        let mut table = sample_table();
+
+        assert_eq!(table.len(), 8);
+        assert_eq!(table.insert(v4("10.1.0.0/16"), 20), Some(2));
+        assert_eq!(table.get(v4("10.1.0.0/16")), Some(&20));
+        assert_eq!(table.get(v4("10.1.0.0/17")), None);
+
+        assert_eq!(table.remove(v4("10.1.0.0/16")), Some(20));
+        assert_eq!(table.remove(v4("10.1.0.0/16")), None);
+        assert_eq!(table.remove(v6("2001:db8::/32")), Some(6));
+        assert_eq!(table.len(), 6);
+        assert_eq!(table.longest_match("10.1.4.1".parse::<IpAddr>().unwrap()).map(|(_, &value)| value), Some(1));
+        assert_eq!(table.longest_match("2001:db8:2::5".parse::<IpAddr>().unwrap()), None);
+
+        table.clear();
+        assert!(table.is_empty());
+        assert_eq!(table.longest_match(Ipv4Addr::LOCALHOST), None);
+    }
+}
@@ -0,0 +1,176 @@
+use alloc::vec::Vec;
+use core::cmp;
+use core::fmt;
+use core::marker::PhantomData;
+#[cfg(feature = "std")]
+use std::time::SystemTime;
+
+use serde::de::{Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::ser::{Serialize, SerializeMap, SerializeSeq, Serializer};
+
+use super::tree::RadixTree;
+use super::set::RadixSet;
+use super::key::{RebuildableKey, TreeKey};
+use super::entry::{KeyStorage, RebuiltKeys};
+use super::numeric::{F32Key, F64Key, I32Key, I64Key, U32Key, U64Key};
+#[cfg(feature = "std")]
+use super::numeric::TimestampKey;
+use super::memcomparable::{KeyTuple, TupleKey};
+
+// Trees are written as a map and sets as a sequence, both in key order, so
+// reading them back goes through the sorted bulk build without sorting.
+
+// Stops a bogus length from a hostile input reserving a huge buffer up front
+fn cautious_capacity(size_hint: Option<usize>) -> usize {
+    cmp::min(size_hint.unwrap_or(0), 4096)
+}
+
+impl<K, V> Serialize for RadixTree<K, V>
+where
+    K: TreeKey + Serialize,
+    V: Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.len()))?;
+
+        for (key, value) in self.iter() {
+            map.serialize_entry(key, value)?;
+        }
+
+        map.end()
+    }
+}
+
+impl<K, V> Serialize for RadixTree<K, V, RebuiltKeys>
+where
+    K: RebuildableKey + Serialize,
+    V: Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.len()))?;
+
+        for (key, value) in self.iter() {
+            map.serialize_entry(&key, value)?;
+        }
+
+        map.end()
+    }
+}
+
+struct TreeVisitor<K, V, S> {
+    tree_type: PhantomData<(K, V, S)>,
+}
+
+impl<'de, K, V, S> Visitor<'de> for TreeVisitor<K, V, S>
+where
+    K: TreeKey + Deserialize<'de>,
+    V: Deserialize<'de>,
+    S: KeyStorage<K, V>,
+{
+    type Value = RadixTree<K, V, S>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a map")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<Self::Value, A::Error> {
+        let mut entries = Vec::with_capacity(cautious_capacity(access.size_hint()));
+
+        while let Some(entry) = access.next_entry()? {
+            entries.push(entry);
+        }
+
+        Ok(entries.into_iter().collect())
+    }
+}
+
+impl<'de, K, V, S> Deserialize<'de> for RadixTree<K, V, S>
+where
+    K: TreeKey + Deserialize<'de>,
+    V: Deserialize<'de>,
+    S: KeyStorage<K, V>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(TreeVisitor {
+            tree_type: PhantomData,
+        })
+    }
+}
+
+impl<K: TreeKey + Serialize> Serialize for RadixSet<K> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.len()))?;
+
+        for key in self.iter() {
+            seq.serialize_element(key)?;
+        }
+
+        seq.end()
+    }
+}
+
+struct SetVisitor<K> {
+    key_type: PhantomData<K>,
+}
+
+impl<'de, K: TreeKey + Deserialize<'de>> Visitor<'de> for SetVisitor<K> {
+    type Value = RadixSet<K>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a sequence")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut access: A) -> Result<Self::Value, A::Error> {
+        let mut keys = Vec::with_capacity(cautious_capacity(access.size_hint()));
+
+        while let Some(key) = access.next_element()? {
+            keys.push(key);
+        }
+
+        Ok(keys.into_iter().collect())
+    }
+}
+
+impl<'de, K: TreeKey + Deserialize<'de>> Deserialize<'de> for RadixSet<K> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_seq(SetVisitor { key_type: PhantomData })
+    }
+}
+
+// The key wrappers are written as the value they wrap, not as their encoding
+macro_rules! value_key_serde {
+    ($name:ident, $value:ty) => {
+        impl Serialize for $name {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                self.value().serialize(serializer)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                <$value>::deserialize(deserializer).map($name::new)
+            }
+        }
+    };
+}
+
+value_key_serde!(U32Key, u32);
+value_key_serde!(U64Key, u64);
+value_key_serde!(I32Key, i32);
+value_key_serde!(I64Key, i64);
+value_key_serde!(F32Key, f32);
+value_key_serde!(F64Key, f64);
+#[cfg(feature = "std")]
+value_key_serde!(TimestampKey, SystemTime);
+
+impl<T: KeyTuple + Serialize> Serialize for TupleKey<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.parts().serialize(serializer)
+    }
+}
+
+impl<'de, T: KeyTuple + Deserialize<'de>> Deserialize<'de> for TupleKey<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        T::deserialize(deserializer).map(TupleKey::new)
+    }
+}
@@ -0,0 +1,336 @@
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::cmp;
+use core::fmt;
+use core::iter::{self, FromIterator, Peekable};
+use core::ops::{Bound, RangeBounds};
+
+use allocator_api2::alloc::Global;
+use allocator_api2::boxed::Box;
+
+use super::shared::SharedBytes;
+use super::key::{KeyPrefix, KeyProbe, TreeKey, key_bound, owned_bound, prefix_upper, within_upper};
+use super::node::{RadixNode, LeafWalker, build_sorted, iterative_insert, iterative_find, iterative_remove, iterative_drop,
+                  sort_for_build};
+use super::entry::KeyOnly;
+
+// A set of keys, the leaves hold just the key without any value beside it
+pub struct RadixSet<K: TreeKey> {
+    size: usize,
+    root: Option<Box<RadixNode<KeyOnly<K>>>>,
+}
+
+impl<K: TreeKey> RadixSet<K> {
+    pub fn new() -> Self {
+        RadixSet { size: 0, root: None }
+    }
+
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    pub fn clear(&mut self) {
+        if let Some(root) = self.root.take() {
+            iterative_drop(root);
+        }
+
+        self.size = 0;
+    }
+
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: TreeKey + AsRef<[u8]>,
+    {
+        match self.root {
+            Some(ref root) => iterative_find(root, KeyProbe::new(&key)).is_some(),
+            None => false,
+        }
+    }
+
+    // Returns whether the key was not already in the set
+    pub fn insert(&mut self, key: K) -> bool {
+        let shared_key = SharedBytes::copy_from_slice(key.as_bytes());
+        let new_entry = KeyOnly::new(key);
+
+        let old_entry = if let Some(ref mut root) = self.root {
+            iterative_insert(root, KeyProbe::shared(&shared_key), new_entry)
+        } else {
+            let new_leaf = RadixNode::new_leaf(KeyPrefix::from(KeyProbe::shared(&shared_key)), new_entry);
+            self.root = Some(Box::new(new_leaf));
+            None
+        };
+
+        if old_entry.is_none() {
+            self.size += 1;
+        }
+
+        old_entry.is_none()
+    }
+
+    // Returns whether the key was in the set
+    pub fn remove<Q>(&mut self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: TreeKey,
+    {
+        let removed = iterative_remove(&mut self.root, KeyProbe::new(key)).is_some();
+
+        if removed {
+            self.size -= 1;
+        }
+
+        removed
+    }
+
+    pub fn iter<'a>(&'a self) -> SetIter<'a, K> {
+        SetIter {
+            walker: LeafWalker::new(self.root.as_ref(), false),
+            upper: Bound::Unbounded,
+        }
+    }
+
+    pub fn range<'a, R: RangeBounds<K>>(&'a self, range: R) -> SetIter<'a, K> {
+        SetIter {
+            walker: LeafWalker::seek(self.root.as_ref(), key_bound(range.start_bound()), false),
+            upper: owned_bound(key_bound(range.end_bound())),
+        }
+    }
+
+    pub fn prefix_iter<'a>(&'a self, prefix: &[u8]) -> SetIter<'a, K> {
+        SetIter {
+            walker: LeafWalker::seek(self.root.as_ref(), Bound::Included(prefix), false),
+            upper: prefix_upper(prefix),
+        }
+    }
+
+    pub fn first(&self) -> Option<&K> {
+        self.iter().next()
+    }
+
+    pub fn last(&self) -> Option<&K> {
+        LeafWalker::new_reversed(self.root.as_ref(), false)
+            .next_leaf()
+            .map(|leaf| leaf.entry().key())
+    }
+
+    // The set operations walk both sets side by side in key order, so each
+    // runs in time linear in the sizes of the sets
+    pub fn union<'a>(&'a self, other: &'a RadixSet<K>) -> SetOperation<'a, K> {
+        SetOperation::new(self, other, Operation::Union)
+    }
+
+    pub fn intersection<'a>(&'a self, other: &'a RadixSet<K>) -> SetOperation<'a, K> {
+        SetOperation::new(self, other, Operation::Intersection)
+    }
+
+    pub fn difference<'a>(&'a self, other: &'a RadixSet<K>) -> SetOperation<'a, K> {
+        SetOperation::new(self, other, Operation::Difference)
+    }
+
+    pub fn symmetric_difference<'a>(&'a self, other: &'a RadixSet<K>) -> SetOperation<'a, K> {
+        SetOperation::new(self, other, Operation::SymmetricDifference)
+    }
+
+    pub fn is_disjoint(&self, other: &RadixSet<K>) -> bool {
+        self.intersection(other).next().is_none()
+    }
+
+    pub fn is_subset(&self, other: &RadixSet<K>) -> bool {
+        self.len() <= other.len() && self.difference(other).next().is_none()
+    }
+
+    pub fn is_superset(&self, other: &RadixSet<K>) -> bool {
+        other.is_subset(self)
+    }
+}
+
+impl<K: TreeKey> Drop for RadixSet<K> {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+impl<K: TreeKey> Default for RadixSet<K> {
+    fn default() -> Self {
+        RadixSet::new()
+    }
+}
+
+impl<K: TreeKey> FromIterator<K> for RadixSet<K> {
+    fn from_iter<I: IntoIterator<Item = K>>(keys: I) -> Self {
+        let keys = sort_for_build(keys.into_iter().map(|key| (key, ())).collect());
+
+        RadixSet {
+            size: keys.len(),
+            root: build_sorted(
+                keys.into_iter()
+                    .map(|(key_bytes, key, _)| (key_bytes, KeyOnly::new(key)))
+                    .collect(),
+                Global,
+            ),
+        }
+    }
+}
+
+impl<K: TreeKey> Extend<K> for RadixSet<K> {
+    fn extend<I: IntoIterator<Item = K>>(&mut self, keys: I) {
+        for key in keys {
+            self.insert(key);
+        }
+    }
+}
+
+impl<K: TreeKey + fmt::Debug> fmt::Debug for RadixSet<K> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+// Iterates over the keys of a set in key order
+pub struct SetIter<'a, K: 'a + TreeKey> {
+    walker: LeafWalker<'a, KeyOnly<K>>,
+    upper: Bound<Vec<u8>>,
+}
+
+impl<'a, K: 'a + TreeKey> iter::Iterator for SetIter<'a, K> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.walker.next_leaf() {
+            Some(leaf) if within_upper(leaf.entry().key().as_bytes(), &self.upper) => Some(leaf.entry().key()),
+            Some(_) => {
+                self.walker.finish();
+                None
+            }
+            None => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operation {
+    Union,
+    Intersection,
+    Difference,
+    SymmetricDifference,
+}
+
+// Merges the keys of two sets in key order, keeping the ones the operation
+// selects
+pub struct SetOperation<'a, K: 'a + TreeKey> {
+    left: Peekable<SetIter<'a, K>>,
+    right: Peekable<SetIter<'a, K>>,
+    operation: Operation,
+}
+
+impl<'a, K: 'a + TreeKey> SetOperation<'a, K> {
+    fn new(left: &'a RadixSet<K>, right: &'a RadixSet<K>, operation: Operation) -> Self {
+        SetOperation {
+            left: left.iter().peekable(),
+            right: right.iter().peekable(),
+            operation,
+        }
+    }
+}
+
+impl<'a, K: 'a + TreeKey> iter::Iterator for SetOperation<'a, K> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let order = match (self.left.peek(), self.right.peek()) {
+                (Some(left), Some(right)) => left.as_bytes().cmp(right.as_bytes()),
+                (Some(_), None) => cmp::Ordering::Less,
+                (None, Some(_)) => cmp::Ordering::Greater,
+                (None, None) => return None,
+            };
+
+            match (order, self.operation) {
+                (cmp::Ordering::Less, Operation::Intersection) => {
+                    self.left.next();
+                }
+                (cmp::Ordering::Less, _) => return self.left.next(),
+                (cmp::Ordering::Greater, Operation::Union) |
+                (cmp::Ordering::Greater, Operation::SymmetricDifference) => return self.right.next(),
+                (cmp::Ordering::Greater, _) => {
+                    self.right.next();
+                }
+                (cmp::Ordering::Equal, Operation::Union) |
+                (cmp::Ordering::Equal, Operation::Intersection) => {
+                    self.right.next();
+                    return self.left.next();
+                }
+                (cmp::Ordering::Equal, _) => {
+                    self.left.next();
+                    self.right.next();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod radix_set_tests {
+    use super::*;
+
+    fn set_of(keys: &[&'static str]) -> RadixSet<&'static str> {
+        keys.iter().cloned().collect()
+    }
+
+    #[test]
+    fn insert_contains_remove() {
+        let mut set = RadixSet::new();
+
+        assert!(set.insert("romane"));
+        assert!(set.insert("rom"));
+        assert!(!set.insert("romane"));
+        assert_eq!(set.len(), 2);
+
+        assert!(set.contains(&"rom"));
+        assert!(!set.contains(&"roma"));
+
+        assert!(set.remove(&"rom"));
+        assert!(!set.remove(&"rom"));
+        assert_eq!(set.len(), 1);
+
+        set.clear();
+        assert!(set.is_empty());
+        assert_eq!(set.first(), None);
+    }
+
+    #[test]
+    fn ordered_and_prefix_iteration() {
+        let set = set_of(&["romulus", "rubens", "r", "romane", "ruber", "rom"]);
+
+        assert_eq!(set.iter().cloned().collect::<Vec<_>>(), vec!["r", "rom", "romane", "romulus", "rubens", "ruber"]);
+        assert_eq!(set.prefix_iter(b"rom").cloned().collect::<Vec<_>>(), vec!["rom", "romane", "romulus"]);
+        assert_eq!(set.range("roman".."rubf").cloned().collect::<Vec<_>>(), vec!["romane", "romulus", "rubens", "ruber"]);
+        assert_eq!(set.first(), Some(&"r"));
+        assert_eq!(set.last(), Some(&"ruber"));
+    }
+
+    #[test]
+    fn set_operations() {
+        let left = set_of(&["a", "ab", "abc", "b", "bc"]);
+        let right = set_of(&["ab", "b", "ba", "c"]);
+
+        let collect = |operation: SetOperation<&'static str>| operation.cloned().collect::<Vec<_>>();
+
+        assert_eq!(collect(left.union(&right)), vec!["a", "ab", "abc", "b", "ba", "bc", "c"]);
+        assert_eq!(collect(left.intersection(&right)), vec!["ab", "b"]);
+        assert_eq!(collect(left.difference(&right)), vec!["a", "abc", "bc"]);
+        assert_eq!(collect(right.difference(&left)), vec!["ba", "c"]);
+        assert_eq!(collect(left.symmetric_difference(&right)), vec!["a", "abc", "ba", "bc", "c"]);
+
+        assert!(!left.is_disjoint(&right));
+        assert!(left.is_disjoint(&set_of(&["aa", "d"])));
+        assert!(set_of(&["ab", "bc"]).is_subset(&left));
+        assert!(!right.is_subset(&left));
+        assert!(left.is_superset(&set_of(&[])));
+    }
+}
@@ -0,0 +1,278 @@
+use core::alloc::Layout;
+use core::cmp;
+use core::fmt;
+use core::ops::Deref;
+use core::ptr::{self, NonNull};
+use core::slice;
+use core::sync::atomic::{self, AtomicUsize, Ordering};
+
+use allocator_api2::alloc::{Allocator, Global};
+
+// The count of handles and the allocator that freed bytes go back to, kept
+// in front of the bytes in the same allocation
+struct Header<A> {
+    refs: AtomicUsize,
+    capacity: usize,
+    alloc: A,
+}
+
+// An immutable byte buffer shared by reference counting, where each handle
+// sees a window of the buffer. Plays the part `Bytes` used to, but takes its
+// memory from any allocator so that a tree built in an arena keeps its keys
+// there too. An empty buffer allocates nothing.
+pub struct SharedBytes<A: Allocator = Global> {
+    header: Option<NonNull<Header<A>>>,
+    start: NonNull<u8>,
+    len: usize,
+}
+
+// The count is atomic, so handles can move between threads whenever the
+// allocator can
+unsafe impl<A: Allocator + Send + Sync> Send for SharedBytes<A> {}
+unsafe impl<A: Allocator + Send + Sync> Sync for SharedBytes<A> {}
+
+impl SharedBytes<Global> {
+    pub fn copy_from_slice(bytes: &[u8]) -> Self {
+        SharedBytes::copy_from_slice_in(bytes, Global)
+    }
+}
+
+impl<A: Allocator> SharedBytes<A> {
+    pub fn new() -> Self {
+        SharedBytes {
+            header: None,
+            start: NonNull::dangling(),
+            len: 0,
+        }
+    }
+
+    pub fn copy_from_slice_in(bytes: &[u8], alloc: A) -> Self {
+        SharedBytes::concat_in(&[bytes], alloc)
+    }
+
+    // One buffer holding the parts one after the other
+    pub fn concat_in(parts: &[&[u8]], alloc: A) -> Self {
+        let len = parts.iter().map(|part| part.len()).sum();
+
+        SharedBytes::fill_in(len, alloc, |bytes| {
+            let mut written = 0;
+            for part in parts {
+                bytes[written..written + part.len()].copy_from_slice(part);
+                written += part.len();
+            }
+        })
+    }
+
+    // A buffer of `len` bytes written by `fill`, which starts from zeroes
+    pub fn fill_in<F: FnOnce(&mut [u8])>(len: usize, alloc: A, fill: F) -> Self {
+        if len == 0 {
+            return SharedBytes::new();
+        }
+
+        let (layout, offset) = Self::layout(len);
+        let memory = alloc
+            .allocate_zeroed(layout)
+            .unwrap_or_else(|_| ::alloc::alloc::handle_alloc_error(layout))
+            .cast::<u8>();
+
+        unsafe {
+            let header = memory.cast::<Header<A>>();
+            let start = NonNull::new_unchecked(memory.as_ptr().add(offset));
+
+            ptr::write(
+                header.as_ptr(),
+                Header {
+                    refs: AtomicUsize::new(1),
+                    capacity: len,
+                    alloc,
+                },
+            );
+            fill(slice::from_raw_parts_mut(start.as_ptr(), len));
+
+            SharedBytes {
+                header: Some(header),
+                start,
+                len,
+            }
+        }
+    }
+
+    // The header followed by `capacity` bytes, and where the bytes start
+    fn layout(capacity: usize) -> (Layout, usize) {
+        Layout::new::<Header<A>>()
+            .extend(Layout::array::<u8>(capacity).expect(concat!(file!(), ": ", line!())))
+            .expect(concat!(file!(), ": ", line!()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // The allocator holding the bytes, none for an empty buffer
+    #[cfg(test)]
+    pub fn allocator(&self) -> Option<&A> {
+        self.header.map(|header| unsafe { &(*header.as_ptr()).alloc })
+    }
+
+    // The bytes `start..end` of this window, sharing the buffer
+    pub fn slice(&self, start: usize, end: usize) -> Self {
+        assert!(start <= end && end <= self.len);
+
+        if start == end {
+            return SharedBytes::new();
+        }
+
+        let mut sliced = self.clone();
+        sliced.start = unsafe { NonNull::new_unchecked(self.start.as_ptr().add(start)) };
+        sliced.len = end - start;
+        sliced
+    }
+
+    // The window over `subset`, which must lie inside this window
+    pub fn slice_ref(&self, subset: &[u8]) -> Self {
+        if subset.is_empty() {
+            return SharedBytes::new();
+        }
+
+        let start = (subset.as_ptr() as usize)
+            .checked_sub(self.start.as_ptr() as usize)
+            .expect(concat!(file!(), ": ", line!()));
+
+        self.slice(start, start + subset.len())
+    }
+
+    // Keeps the bytes before `at` and returns the rest
+    pub fn split_off(&mut self, at: usize) -> Self {
+        let rest = self.slice(at, self.len);
+
+        self.len = at;
+        if at == 0 {
+            *self = SharedBytes::new();
+        }
+
+        rest
+    }
+
+    // Drops the first `count` bytes from the window
+    pub fn advance(&mut self, count: usize) {
+        *self = self.slice(count, self.len);
+    }
+}
+
+impl<A: Allocator> Clone for SharedBytes<A> {
+    fn clone(&self) -> Self {
+        if let Some(header) = self.header {
+            unsafe { (*header.as_ptr()).refs.fetch_add(1, Ordering::Relaxed) };
+        }
+
+        SharedBytes {
+            header: self.header,
+            start: self.start,
+            len: self.len,
+        }
+    }
+}
+
+impl<A: Allocator> Drop for SharedBytes<A> {
+    fn drop(&mut self) {
+        let header = match self.header {
+            Some(header) => header,
+            None => return,
+        };
+
+        unsafe {
+            if (*header.as_ptr()).refs.fetch_sub(1, Ordering::Release) != 1 {
+                return;
+            }
+            atomic::fence(Ordering::Acquire);
+
+            let Header { capacity, alloc, .. } = ptr::read(header.as_ptr());
+            alloc.deallocate(header.cast(), Self::layout(capacity).0);
+        }
+    }
+}
+
+impl<A: Allocator> Deref for SharedBytes<A> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.start.as_ptr(), self.len) }
+    }
+}
+
+impl<A: Allocator> PartialEq for SharedBytes<A> {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl<A: Allocator> Eq for SharedBytes<A> {}
+
+impl<A: Allocator> PartialOrd for SharedBytes<A> {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<A: Allocator> Ord for SharedBytes<A> {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        (**self).cmp(&**other)
+    }
+}
+
+impl<A: Allocator> fmt::Debug for SharedBytes<A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "b\"")?;
+        for &byte in self.iter() {
+            for escaped in core::ascii::escape_default(byte) {
+                write!(f, "{}", escaped as char)?;
+            }
+        }
+        write!(f, "\"")
+    }
+}
+
+#[cfg(test)]
+mod shared_bytes_tests {
+    use super::*;
+
+    #[test]
+    fn windows_share_the_buffer() {
+        let mut bytes = SharedBytes::copy_from_slice(b"hello world");
+        let world = bytes.split_off(6);
+
+        assert_eq!(&*bytes, b"hello ");
+        assert_eq!(&*world, b"world");
+        assert!(world.allocator().is_some());
+
+        let mut orld = world.clone();
+        orld.advance(1);
+        assert_eq!(&*orld, b"orld");
+        assert_eq!(&*bytes.slice_ref(&bytes[1..3]), b"el");
+        assert!(bytes.slice(2, 2).allocator().is_none());
+        assert_eq!(&*SharedBytes::concat_in(&[b"ab", b"", b"c"], Global), b"abc");
+
+        drop(bytes);
+        drop(world);
+        assert_eq!(&*orld, b"orld");
+    }
+
+    #[test]
+    fn empty_buffers_do_not_allocate() {
+        let empty = SharedBytes::copy_from_slice(b"");
+
+        assert!(empty.is_empty());
+        assert!(empty.allocator().is_none());
+        assert_eq!(empty, SharedBytes::new());
+        assert_eq!(format!("{:?}", SharedBytes::copy_from_slice(b"a\n")), "b\"a\\n\"");
+
+        let mut sorted = [SharedBytes::copy_from_slice(b"b"), SharedBytes::copy_from_slice(b"a")];
+        sorted.sort();
+        assert_eq!(&*sorted[0], b"a");
+        assert_eq!(&*sorted[1], b"b");
+    }
+}
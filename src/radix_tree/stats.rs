@@ -0,0 +1,104 @@
+use alloc::vec::Vec;
+use alloc::collections::BTreeMap;
+use core::fmt;
+use core::mem;
+
+use allocator_api2::alloc::Allocator;
+use allocator_api2::boxed::Box;
+
+use super::node::RadixNode;
+use super::entry::LeafEntry;
+
+// The shape of a tree and an estimate of the memory it takes. The heap
+// estimate counts the nodes, the child layouts, boxed entries and every
+// prefix at the length of its window. Prefixes share the expanded key
+// buffers they were cut from, so the estimate leaves out those buffers and
+// their reference counted headers, and counts shared bytes once per prefix.
+// Anything owned by the keys or values themselves is not counted either.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TreeStats {
+    pub interior_nodes: usize,
+    pub leaf_nodes: usize,
+    // Bytes of the interior prefixes and the remaining keys of the leaves
+    pub prefix_bytes: usize,
+    // Leaves held as the empty child of an interior node, for keys that are
+    // a prefix of other keys
+    pub empty_child_leaves: usize,
+    // Number of leaves at each depth, the root is at depth 0
+    pub depth_histogram: BTreeMap<usize, usize>,
+    // Number of interior nodes with each count of children, the empty child
+    // included
+    pub fan_out_histogram: BTreeMap<usize, usize>,
+    pub heap_bytes: usize,
+}
+
+impl TreeStats {
+    pub fn max_depth(&self) -> usize {
+        self.depth_histogram.keys().next_back().cloned().unwrap_or(0)
+    }
+
+    pub fn mean_depth(&self) -> f64 {
+        if self.leaf_nodes == 0 {
+            return 0.0;
+        }
+
+        let total_depth = self.depth_histogram
+            .iter()
+            .map(|(&depth, &count)| depth * count)
+            .sum::<usize>();
+
+        total_depth as f64 / self.leaf_nodes as f64
+    }
+}
+
+impl fmt::Display for TreeStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{} leaves ({} empty children), {} interior nodes, {} prefix bytes, ~{} heap bytes",
+            self.leaf_nodes, self.empty_child_leaves, self.interior_nodes, self.prefix_bytes, self.heap_bytes
+        )?;
+        writeln!(f, "depth: max {}, mean {:.2}, {:?}", self.max_depth(), self.mean_depth(), self.depth_histogram)?;
+        write!(f, "fan out: {:?}", self.fan_out_histogram)
+    }
+}
+
+// Walks every node with an explicit stack, so deep trees can be measured
+pub fn collect_stats<E: LeafEntry, A: Allocator + Clone>(root: Option<&Box<RadixNode<E, A>, A>>) -> TreeStats {
+    let mut stats = TreeStats::default();
+    let mut pending = root.into_iter().map(|root| (&**root, 0, false)).collect::<Vec<_>>();
+
+    while let Some((node, depth, is_empty_child)) = pending.pop() {
+        stats.heap_bytes += mem::size_of::<RadixNode<E, A>>();
+
+        match *node {
+            RadixNode::Leaf(ref leaf) => {
+                stats.leaf_nodes += 1;
+                stats.prefix_bytes += leaf.remaining_key().len();
+                stats.heap_bytes += leaf.remaining_key().heap_bytes() + leaf.entry().heap_bytes();
+                *stats.depth_histogram.entry(depth).or_insert(0) += 1;
+
+                if is_empty_child {
+                    stats.empty_child_leaves += 1;
+                }
+            }
+            RadixNode::Interior(ref interior) => {
+                let children = interior.children();
+                let empty_child = children.get_child(None);
+
+                stats.interior_nodes += 1;
+                stats.prefix_bytes += interior.prefix().len();
+                stats.heap_bytes += interior.prefix().heap_bytes() + children.heap_bytes();
+                *stats
+                    .fan_out_histogram
+                    .entry(children.len() + empty_child.iter().count())
+                    .or_insert(0) += 1;
+
+                pending.extend(empty_child.map(|child| (&**child, depth + 1, true)));
+                pending.extend(children.iter().map(|(_, child)| (&**child, depth + 1, false)));
+            }
+        }
+    }
+
+    stats
+}
@@ -1,22 +1,155 @@
-use std::mem;
-use std::borrow::Borrow;
-use std::fmt;
-
-use super::key::{KeyProbe, TreeKey};
-use super::node::{RadixNode, recursive_insert, recursive_find, recursive_mut_find, recursive_remove};
-use super::entry::KeyValue;
-
-#[derive(Debug)]
-pub struct RadixTree<K: TreeKey, V> {
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::fmt;
+use core::iter::{self, FromIterator};
+use core::marker::PhantomData;
+use core::ops::{Bound, RangeBounds};
+
+use allocator_api2::alloc::{Allocator, Global};
+use allocator_api2::boxed::Box;
+
+use super::alphabet::{ByteAlphabet, KeyAlphabet};
+use super::shared::SharedBytes;
+use super::key::{KeyPrefix, KeyProbe, RebuildableKey, TreeKey, key_bound, owned_bound, prefix_upper, within_upper};
+use super::node::{RadixNode, LeafWalker, build_sorted, iterative_insert, iterative_find, iterative_mut_find, iterative_remove,
+                  iterative_drop, sort_for_build};
+use super::dot::tree_to_dot;
+use super::frozen::{FrozenValue, write_frozen};
+use super::louds::{LoudsTrie, build_louds};
+use super::stats::{TreeStats, collect_stats};
+use super::validate::{InvariantViolation, validate_tree};
+use super::entry::{BareValue, KeyStorage, KeyValue, LeafEntry, RebuiltKeys, StoredKeys};
+
+// The storage parameter picks what the leaves hold. By default every leaf
+// keeps a clone of its key, a tree created with `with_rebuilt_keys` only
+// keeps the values and rebuilds keys from the path while iterating.
+//
+// Every node, prefix and stored entry is allocated from `A`, so a tree made
+// with `new_in` or `with_rebuilt_keys_in` can live in an arena. The keys and
+// values themselves are moved in as they are, and iterators and range
+// bounds still use the global allocator for their scratch space.
+//
+// The alphabet parameter picks the symbols nodes branch on. Keys are stored
+// and probed as their expanded symbols, so a tree over `NibbleAlphabet` or
+// `BitAlphabet` splits prefixes in the middle of a byte, while iteration
+// order and range bounds are still those of the key bytes. A probe expands
+// its key on the stack, or in `A` for long keys.
+pub struct RadixTree<
+    K: TreeKey,
+    V,
+    S: KeyStorage<K, V, A> = StoredKeys,
+    A: Allocator + Clone = Global,
+    L: KeyAlphabet = ByteAlphabet,
+> {
     size: usize,
-    root: Option<Box<RadixNode<K, V>>>,
+    root: Option<Box<RadixNode<S::Entry, A>, A>>,
+    alloc: A,
+    storage: PhantomData<(K, S, L)>,
 }
 
 impl<K: TreeKey, V> RadixTree<K, V> {
     pub fn new() -> Self {
+        RadixTree::new_in(Global)
+    }
+}
+
+impl<K: TreeKey, V, A: Allocator + Clone> RadixTree<K, V, StoredKeys, A> {
+    pub fn new_in(alloc: A) -> Self {
+        RadixTree::with_alphabet_in(alloc)
+    }
+}
+
+impl<K: TreeKey, V, A: Allocator + Clone, L: KeyAlphabet> RadixTree<K, V, StoredKeys, A, L> {
+    pub fn iter<'a>(&'a self) -> Iter<'a, K, V, A> {
+        Iter {
+            walker: LeafWalker::new(self.root.as_ref(), false),
+            upper: Bound::Unbounded,
+        }
+    }
+
+    // Iterates over the entries with keys inside `range`, compared by their
+    // bytes, in key order
+    pub fn range<'a, R: RangeBounds<K>>(&'a self, range: R) -> Iter<'a, K, V, A> {
+        Iter {
+            walker: seek_symbols::<_, _, L>(self.root.as_ref(), &self.alloc, key_bound(range.start_bound()), false),
+            upper: owned_bound(key_bound(range.end_bound())),
+        }
+    }
+
+    // Iterates over the entries whose keys start with the bytes of `prefix`,
+    // in key order
+    pub fn prefix_iter<'a>(&'a self, prefix: &[u8]) -> Iter<'a, K, V, A> {
+        Iter {
+            walker: seek_symbols::<_, _, L>(self.root.as_ref(), &self.alloc, Bound::Included(prefix), false),
+            upper: prefix_upper(prefix),
+        }
+    }
+
+    pub fn first<'a>(&'a self) -> Option<(&'a K, &'a V)> {
+        self.iter().next()
+    }
+
+    pub fn last<'a>(&'a self) -> Option<(&'a K, &'a V)> {
+        LeafWalker::new_reversed(self.root.as_ref(), false)
+            .next_leaf()
+            .map(|leaf| (leaf.entry().key(), leaf.entry().value()))
+    }
+}
+
+impl<K: RebuildableKey, V> RadixTree<K, V, RebuiltKeys> {
+    pub fn with_rebuilt_keys() -> Self {
+        RadixTree::with_rebuilt_keys_in(Global)
+    }
+}
+
+impl<K: RebuildableKey, V, A: Allocator + Clone> RadixTree<K, V, RebuiltKeys, A> {
+    pub fn with_rebuilt_keys_in(alloc: A) -> Self {
+        RadixTree::with_alphabet_in(alloc)
+    }
+}
+
+impl<K: RebuildableKey, V, A: Allocator + Clone, L: KeyAlphabet> RadixTree<K, V, RebuiltKeys, A, L> {
+    pub fn iter<'a>(&'a self) -> RebuiltIter<'a, K, V, A, L> {
+        RebuiltIter::new(LeafWalker::new(self.root.as_ref(), true), Bound::Unbounded)
+    }
+
+    // Iterates over the entries with keys inside `range`, compared by their
+    // bytes, in key order
+    pub fn range<'a, R: RangeBounds<K>>(&'a self, range: R) -> RebuiltIter<'a, K, V, A, L> {
+        RebuiltIter::new(
+            seek_symbols::<_, _, L>(self.root.as_ref(), &self.alloc, key_bound(range.start_bound()), true),
+            owned_bound(key_bound(range.end_bound())),
+        )
+    }
+
+    // Iterates over the entries whose keys start with the bytes of `prefix`,
+    // in key order
+    pub fn prefix_iter<'a>(&'a self, prefix: &[u8]) -> RebuiltIter<'a, K, V, A, L> {
+        RebuiltIter::new(
+            seek_symbols::<_, _, L>(self.root.as_ref(), &self.alloc, Bound::Included(prefix), true),
+            prefix_upper(prefix),
+        )
+    }
+
+    pub fn first<'a>(&'a self) -> Option<(K, &'a V)> {
+        self.iter().next()
+    }
+
+    pub fn last<'a>(&'a self) -> Option<(K, &'a V)> {
+        RebuiltIter::<K, V, A, L>::new(LeafWalker::new_reversed(self.root.as_ref(), true), Bound::Unbounded).next()
+    }
+}
+
+impl<K: TreeKey, V, S: KeyStorage<K, V, A>, A: Allocator + Clone, L: KeyAlphabet> RadixTree<K, V, S, A, L> {
+    // Creates a tree of any storage, allocator and alphabet, all picked by
+    // the type of the tree
+    pub fn with_alphabet_in(alloc: A) -> Self {
         RadixTree {
             size: 0,
             root: None,
+            alloc,
+            storage: PhantomData,
         }
     }
 
@@ -29,10 +162,14 @@ impl<K: TreeKey, V> RadixTree<K, V> {
     }
 
     pub fn clear(&mut self) {
-        self.root = None;
+        if let Some(root) = self.root.take() {
+            iterative_drop(root);
+        }
+
+        self.size = 0;
     }
 
-    pub fn contains_key<Q: ?Sized>(&self, key: &Q) -> bool
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
     where
         K: Borrow<Q>,
         Q: TreeKey + AsRef<[u8]>,
@@ -40,48 +177,56 @@ impl<K: TreeKey, V> RadixTree<K, V> {
         self.get(key).is_some()
     }
 
-    pub fn get<'k, 'v, Q: ?Sized>(&'v self, key: &'k Q) -> Option<&'v V>
+    // Walks the whole tree checking its structure, for tests and fuzzing
+    pub fn validate(&self) -> Result<(), InvariantViolation> {
+        validate_tree::<_, _, L>(self.root.as_ref(), self.size)
+    }
+
+    // Renders the tree as a Graphviz graph, leaves show their value through
+    // `format_value`
+    pub fn to_dot<F: Fn(&V) -> String>(&self, format_value: F) -> String {
+        tree_to_dot(self.root.as_ref(), format_value)
+    }
+
+    // Walks the whole tree to count its nodes and estimate its memory use
+    pub fn stats(&self) -> TreeStats {
+        collect_stats(self.root.as_ref())
+    }
+
+    pub fn get<'k, 'v, Q>(&'v self, key: &'k Q) -> Option<&'v V>
     where
         K: Borrow<Q>,
         Q: TreeKey + AsRef<[u8]>,
     {
-        if self.root.is_some() {
-            let probe = KeyProbe::new(&key);
+        let symbols = L::symbols_in(key.as_bytes(), &self.alloc);
 
-            recursive_find(self.root.as_ref().unwrap(), probe)
-        } else {
-            None
-        }
+        self.root.as_ref().and_then(|root| iterative_find(root, KeyProbe::from_bytes(&symbols)))
     }
 
-    pub fn get_mut<'k, 'v, Q: ?Sized>(&'v mut self, key: &'k Q) -> Option<&'v mut V>
+    pub fn get_mut<'k, 'v, Q>(&'v mut self, key: &'k Q) -> Option<&'v mut V>
     where
         K: Borrow<Q>,
         Q: TreeKey + AsRef<[u8]>,
     {
-        if self.root.is_some() {
-            let probe = KeyProbe::new(&key);
-            
-            recursive_mut_find(self.root.as_mut().unwrap(), probe)
-        } else {
-            None
-        }
+        let symbols = L::symbols_in(key.as_bytes(), &self.alloc);
+
+        self.root.as_mut().and_then(|root| iterative_mut_find(root, KeyProbe::from_bytes(&symbols)))
     }
 
     pub fn insert(&mut self, key: K, value: V) -> Option<V> {
-        let old_entry = if self.root.is_some() {
-            let old_root = mem::replace(&mut self.root, None).unwrap();
-
-            let probe = KeyProbe::new(&key);
-            let new_entry = KeyValue::new(key.clone(), value);
-            let (updated_node, old_entry) = recursive_insert(old_root, probe, new_entry);
+        let key_bytes = key.as_bytes();
+        let shared_key = SharedBytes::fill_in(key_bytes.len() * L::SYMBOLS_PER_BYTE, self.alloc.clone(), |symbols| {
+            L::expand_into(key_bytes, symbols)
+        });
+        let new_entry = S::new_entry(key, value, &self.alloc);
 
-            let _ = mem::replace(&mut self.root, Some(updated_node));
+        let old_entry = if let Some(ref mut root) = self.root {
+            let probe = KeyProbe::shared(&shared_key);
 
-            old_entry
+            iterative_insert(root, probe, new_entry)
         } else {
-            let new_leaf = RadixNode::new_leaf(key, value);
-            self.root = Some(box new_leaf);
+            let new_leaf = RadixNode::new_leaf(KeyPrefix::shared(&shared_key, 0, shared_key.len()), new_entry);
+            self.root = Some(Box::new_in(new_leaf, self.alloc.clone()));
             None
         };
 
@@ -92,23 +237,35 @@ impl<K: TreeKey, V> RadixTree<K, V> {
         old_entry
     }
 
-    pub fn remove<Q: ?Sized>(&mut self, key: &Q) -> Option<V>
+    // Inserts like `insert`, but fails rather than grow the tree past
+    // `max_len` keys. Replacing the value of a key already present always
+    // succeeds.
+    pub fn try_insert(&mut self, key: K, value: V, max_len: usize) -> ::error::Result<Option<V>> {
+        if self.size >= max_len {
+            let present = match self.root {
+                Some(ref root) => iterative_find(root, KeyProbe::from_bytes(&L::symbols_in(key.as_bytes(), &self.alloc))).is_some(),
+                None => false,
+            };
+
+            if !present {
+                return Err(::error::Error::CapacityExceeded {
+                    what: "radix tree keys",
+                    limit: max_len,
+                    needed: self.size + 1,
+                });
+            }
+        }
+
+        Ok(self.insert(key, value))
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
     where
         K: Borrow<Q>,
         Q: TreeKey,
     {
-        let old_entry = if self.root.is_some() {
-            let probe = KeyProbe::new(key);
-            let old_root = mem::replace(&mut self.root, None).unwrap();
-
-            let (updated_node, old_entry) = recursive_remove(old_root, probe);
-
-            let _ = mem::replace(&mut self.root, updated_node);
-
-            old_entry
-        } else {
-            None
-        };
+        let symbols = L::symbols_in(key.as_bytes(), &self.alloc);
+        let old_entry = iterative_remove(&mut self.root, KeyProbe::from_bytes(&symbols));
 
         if old_entry.is_some() {
             self.size -= 1;
@@ -118,12 +275,198 @@ impl<K: TreeKey, V> RadixTree<K, V> {
     }
 }
 
-#[cfg(any(debug_assertions, test))]
+// Readers of these layouts probe with key bytes, so only trees branching on
+// whole bytes can be written out
+impl<K: TreeKey, V, S: KeyStorage<K, V, A>, A: Allocator + Clone> RadixTree<K, V, S, A> {
+    // Writes the tree in the layout read by `FrozenRadixTree`, failing only
+    // when the nodes take more than 4GiB
+    pub fn to_frozen(&self) -> ::error::Result<Vec<u8>>
+    where
+        V: FrozenValue,
+    {
+        write_frozen(self.root.as_ref(), self.size)
+    }
+
+    // Lays out the keys as a succinct trie, leaving the values behind
+    pub fn to_louds(&self) -> LoudsTrie {
+        build_louds(self.root.as_ref())
+    }
+}
+
+impl<K: TreeKey, V, S: KeyStorage<K, V>> RadixTree<K, V, S> {
+    // Builds the tree in one pass over entries already in strictly ascending
+    // key order, skipping the sort done by `collect`. Fails on the first key
+    // out of order or repeated.
+    pub fn try_from_sorted<I: IntoIterator<Item = (K, V)>>(entries: I) -> ::error::Result<Self> {
+        let mut sorted: Vec<(SharedBytes, S::Entry)> = Vec::new();
+
+        for (key, value) in entries {
+            let key_bytes = SharedBytes::copy_from_slice(key.as_bytes());
+
+            if let Some(&(ref previous, _)) = sorted.last() {
+                if *previous >= key_bytes {
+                    return Err(::error::Error::InvalidKeyOrder {
+                        previous: previous.to_vec(),
+                        next: key_bytes.to_vec(),
+                    });
+                }
+            }
+
+            sorted.push((key_bytes, S::new_entry(key, value, &Global)));
+        }
+
+        Ok(RadixTree {
+            size: sorted.len(),
+            root: build_sorted(sorted, Global),
+            alloc: Global,
+            storage: PhantomData,
+        })
+    }
+}
+
+// Builds the tree in one pass over the sorted entries rather than inserting
+// them one at a time
+impl<K: TreeKey, V, S: KeyStorage<K, V>> FromIterator<(K, V)> for RadixTree<K, V, S> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(entries: I) -> Self {
+        let entries = sort_for_build(entries.into_iter().collect());
+
+        RadixTree {
+            size: entries.len(),
+            root: build_sorted(
+                entries
+                    .into_iter()
+                    .map(|(key_bytes, key, value)| (key_bytes, S::new_entry(key, value, &Global)))
+                    .collect(),
+                Global,
+            ),
+            alloc: Global,
+            storage: PhantomData,
+        }
+    }
+}
+
+impl<K: TreeKey, V, S: KeyStorage<K, V, A>, A: Allocator + Clone, L: KeyAlphabet> Drop for RadixTree<K, V, S, A, L> {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+impl<K, V, S, A, L> fmt::Debug for RadixTree<K, V, S, A, L>
+where
+    K: TreeKey,
+    S: KeyStorage<K, V, A>,
+    A: Allocator + Clone,
+    L: KeyAlphabet,
+    S::Entry: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RadixTree")
+            .field("size", &self.size)
+            .field("root", &self.root)
+            .finish()
+    }
+}
+
+// Starts a walk at the first leaf whose key is not below the key bytes of
+// `lower`. Expanding keys keeps their order, so comparing symbols agrees.
+fn seek_symbols<'a, E, A: Allocator + Clone, L: KeyAlphabet>(
+    root: Option<&'a Box<RadixNode<E, A>, A>>,
+    alloc: &A,
+    lower: Bound<&[u8]>,
+    track_path: bool,
+) -> LeafWalker<'a, E, A> {
+    match lower {
+        Bound::Included(lower) => LeafWalker::seek(root, Bound::Included(&L::symbols_in(lower, alloc)), track_path),
+        Bound::Excluded(lower) => LeafWalker::seek(root, Bound::Excluded(&L::symbols_in(lower, alloc)), track_path),
+        Bound::Unbounded => LeafWalker::new(root, track_path),
+    }
+}
+
+// Iterates over the entries of a tree which stores its keys, in key order
+pub struct Iter<'a, K: 'a + TreeKey, V: 'a, A: 'a + Allocator + Clone = Global> {
+    walker: LeafWalker<'a, Box<KeyValue<K, V>, A>, A>,
+    upper: Bound<Vec<u8>>,
+}
+
+impl<'a, K: 'a + TreeKey, V: 'a, A: 'a + Allocator + Clone> iter::Iterator for Iter<'a, K, V, A> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.walker.next_leaf() {
+            Some(leaf) if within_upper(leaf.entry().key().as_bytes(), &self.upper) => {
+                let entry = leaf.entry();
+
+                Some((entry.key(), entry.value()))
+            }
+            Some(_) => {
+                self.walker.finish();
+                None
+            }
+            None => None,
+        }
+    }
+}
+
+// Iterates over the entries of a tree which rebuilds its keys, in key order.
+// Every key is built anew from the symbols along the path to its leaf.
+pub struct RebuiltIter<'a, K, V: 'a, A: 'a + Allocator + Clone = Global, L: KeyAlphabet = ByteAlphabet> {
+    walker: LeafWalker<'a, BareValue<V>, A>,
+    upper: Bound<Vec<u8>>,
+    symbols: Vec<u8>,
+    key_bytes: Vec<u8>,
+    key_type: PhantomData<(K, L)>,
+}
+
+impl<'a, K: RebuildableKey, V: 'a, A: 'a + Allocator + Clone, L: KeyAlphabet> RebuiltIter<'a, K, V, A, L> {
+    fn new(walker: LeafWalker<'a, BareValue<V>, A>, upper: Bound<Vec<u8>>) -> Self {
+        RebuiltIter {
+            walker,
+            upper,
+            symbols: Vec::new(),
+            key_bytes: Vec::new(),
+            key_type: PhantomData,
+        }
+    }
+}
+
+impl<'a, K: RebuildableKey, V: 'a, A: 'a + Allocator + Clone, L: KeyAlphabet> iter::Iterator
+    for RebuiltIter<'a, K, V, A, L>
+{
+    type Item = (K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.walker.next_leaf() {
+            Some(leaf) => {
+                self.symbols.clear();
+                self.symbols.extend_from_slice(self.walker.path());
+                self.symbols.extend_from_slice(leaf.remaining_key().bytes());
+                self.key_bytes.clear();
+                L::pack_into(&self.symbols, &mut self.key_bytes);
+
+                if within_upper(&self.key_bytes, &self.upper) {
+                    Some((K::from_bytes(&self.key_bytes), leaf.entry().value()))
+                } else {
+                    self.walker.finish();
+                    None
+                }
+            }
+            None => None,
+        }
+    }
+}
+
+#[cfg(all(feature = "std", any(debug_assertions, test)))]
 use super::node::debug::TreeView;
 
-#[cfg(any(debug_assertions, test))]
-impl<K, V> RadixTree<K, V> where K: TreeKey, V: fmt::Debug {
-    pub fn debug_view<'a>(&'a self) -> TreeView<'a, K, V> {
+#[cfg(all(feature = "std", any(debug_assertions, test)))]
+impl<K, V, S, A> RadixTree<K, V, S, A>
+where
+    K: TreeKey,
+    V: fmt::Debug,
+    S: KeyStorage<K, V, A>,
+    A: Allocator + Clone,
+{
+    pub fn debug_view<'a>(&'a self) -> TreeView<'a, S::Entry, A> {
         TreeView::new(self.root.as_ref().expect("Tried to view an empty tree!"), 7)
     }
 }
@@ -131,6 +474,8 @@ impl<K, V> RadixTree<K, V> where K: TreeKey, V: fmt::Debug {
 #[cfg(test)]
 mod tree_tests {
     use super::*;
+    use super::super::validate::ViolationKind;
+    use super::super::alphabet::{BitAlphabet, NibbleAlphabet};
 
     #[test]
     fn create_tree() {
@@ -227,6 +572,302 @@ mod tree_tests {
         assert!(value.is_some());
         assert_eq!(value.unwrap(), &5);
     }
-}
 
+    #[test]
+    fn iterate_in_key_order() {
+        let mut rax = RadixTree::<&str, usize>::new();
+
+        rax.insert("hello", 1);
+        rax.insert("hel", 2);
+        rax.insert("", 3);
+        rax.insert("help", 4);
+        rax.insert("goodbye", 5);
+
+        let entries = rax.iter().collect::<Vec<_>>();
+
+        assert_eq!(
+            entries,
+            vec![(&"", &3), (&"goodbye", &5), (&"hel", &2), (&"hello", &1), (&"help", &4)]
+        );
+    }
+
+    #[test]
+    fn iterate_rebuilt_keys() {
+        let mut rax: RadixTree<String, usize, RebuiltKeys> = RadixTree::with_rebuilt_keys();
+        let words = ["romane", "romanus", "romulus", "rom", "rubens", "ruber", "r"];
+
+        for (idx, word) in words.iter().enumerate() {
+            rax.insert(word.to_string(), idx);
+        }
+        assert_eq!(rax.get(&String::from("romulus")), Some(&2));
+        assert_eq!(rax.remove(&String::from("rom")), Some(3));
+
+        let entries = rax.iter().collect::<Vec<_>>();
+
+        assert_eq!(
+            entries,
+            vec![
+                (String::from("r"), &6),
+                (String::from("romane"), &0),
+                (String::from("romanus"), &1),
+                (String::from("romulus"), &2),
+                (String::from("rubens"), &4),
+                (String::from("ruber"), &5),
+            ]
+        );
+    }
+
+    #[test]
+    fn range_first_last() {
+        let mut rax = RadixTree::new();
+        let words = ["romane", "romanus", "romulus", "rom", "rubens", "ruber", "r", "rubicon"];
+
+        for (idx, word) in words.iter().enumerate() {
+            rax.insert(*word, idx);
+        }
+
+        fn keys<'a>(iter: Iter<'a, &'static str, usize>) -> Vec<&'static str> {
+            iter.map(|(key, _)| *key).collect()
+        }
+
+        assert_eq!(keys(rax.range("rom".."rub")), vec!["rom", "romane", "romanus", "romulus"]);
+        assert_eq!(
+            keys(rax.range((Bound::Excluded("rom"), Bound::Included("rubens")))),
+            vec!["romane", "romanus", "romulus", "rubens"]
+        );
+        assert_eq!(keys(rax.range("roman"..)), vec!["romane", "romanus", "romulus", "rubens", "ruber", "rubicon"]);
+        assert_eq!(keys(rax.range(..="r")), vec!["r"]);
+        assert_eq!(keys(rax.range("s"..)), Vec::<&str>::new());
+        assert_eq!(keys(rax.range("romz".."rubf")), vec!["rubens", "ruber"]);
+
+        assert_eq!(rax.first(), Some((&"r", &6)));
+        assert_eq!(rax.last(), Some((&"rubicon", &7)));
+        assert_eq!(RadixTree::<&str, usize>::new().last(), None);
+    }
+
+    #[test]
+    fn range_rebuilt_keys() {
+        let mut rax: RadixTree<String, usize, RebuiltKeys> = RadixTree::with_rebuilt_keys();
+
+        for (idx, word) in ["alpha", "beta", "bet", "gamma", "delta"].iter().enumerate() {
+            rax.insert(word.to_string(), idx);
+        }
+
+        let entries = rax.range(String::from("bet")..String::from("d")).collect::<Vec<_>>();
+
+        assert_eq!(entries, vec![(String::from("bet"), &2), (String::from("beta"), &1)]);
+        assert_eq!(rax.first(), Some((String::from("alpha"), &0)));
+        assert_eq!(rax.last(), Some((String::from("gamma"), &3)));
+    }
+
+    #[test]
+    fn nibble_tree_branches_inside_bytes() {
+        let mut rax: RadixTree<&[u8], usize, StoredKeys, Global, NibbleAlphabet> = RadixTree::with_alphabet_in(Global);
+        let keys: [&[u8]; 5] = [b"\x12\x34", b"\x12\x3f", b"\x12", b"\x1a", b"\xff"];
+
+        for (idx, key) in keys.iter().enumerate() {
+            rax.insert(*key, idx);
+        }
+
+        let stats = rax.stats();
+
+        // Every nibble along "\x12\x3" is a branch, each taken by its own
+        // interior node, and only "\xff" keeps a nibble in its leaf
+        assert_eq!(stats.interior_nodes, 4);
+        assert_eq!(stats.prefix_bytes, 1);
+        assert_eq!(rax.validate(), Ok(()));
+        assert_eq!(rax.get(&&b"\x12\x3f"[..]), Some(&1));
+        assert_eq!(rax.get(&&b"\x12\x30"[..]), None);
+        assert_eq!(rax.remove(&&b"\x12"[..]), Some(2));
+        assert_eq!(
+            rax.range(&b"\x12\x35"[..]..).map(|(key, _)| *key).collect::<Vec<_>>(),
+            vec![&b"\x12\x3f"[..], b"\x1a", b"\xff"]
+        );
+        assert_eq!(rax.prefix_iter(b"\x1a").count(), 1);
+        assert_eq!(rax.prefix_iter(b"\x12").count(), 2);
+        assert_eq!(rax.last(), Some((&&b"\xff"[..], &4)));
+    }
+
+    #[test]
+    fn bit_tree_rebuilds_keys() {
+        let mut rax: RadixTree<Vec<u8>, usize, RebuiltKeys, Global, BitAlphabet> = RadixTree::with_alphabet_in(Global);
+        let keys = [&b"a"[..], b"b", b"c", b"ab", b"", b"\x80"];
+
+        for (idx, key) in keys.iter().enumerate() {
+            assert_eq!(rax.insert(key.to_vec(), idx), None);
+        }
 
+        // Nodes branch on a bit, which leaves at most the empty child beside
+        // two others
+        assert!(rax.stats().fan_out_histogram.keys().all(|&fan_out| fan_out <= 3));
+        assert_eq!(rax.validate(), Ok(()));
+        assert_eq!(rax.get(&b"c".to_vec()), Some(&2));
+        assert_eq!(rax.remove(&b"a".to_vec()), Some(0));
+        assert_eq!(
+            rax.iter().collect::<Vec<_>>(),
+            vec![(b"".to_vec(), &4), (b"ab".to_vec(), &3), (b"b".to_vec(), &1), (b"c".to_vec(), &2), (b"\x80".to_vec(), &5)]
+        );
+        assert_eq!(rax.range(b"ab".to_vec()..b"c".to_vec()).count(), 2);
+        assert_eq!(rax.last(), Some((b"\x80".to_vec(), &5)));
+    }
+
+    #[test]
+    fn prefix_iter_selects_extensions() {
+        let mut rax = RadixTree::new();
+        let keys: [&[u8]; 7] = [b"ab", b"ab\xff", b"ab\xff\xff", b"ac", b"a", b"b\xff", b"\xff\xff"];
+
+        for (idx, key) in keys.iter().enumerate() {
+            rax.insert(*key, idx);
+        }
+
+        let prefixed = |prefix: &[u8]| rax.prefix_iter(prefix).map(|(_, &idx)| idx).collect::<Vec<_>>();
+
+        assert_eq!(prefixed(b"ab"), vec![0, 1, 2]);
+        assert_eq!(prefixed(b"ab\xff"), vec![1, 2]);
+        assert_eq!(prefixed(b"a"), vec![4, 0, 1, 2, 3]);
+        assert_eq!(prefixed(b"\xff"), vec![6]);
+        assert_eq!(prefixed(b"abc"), Vec::<usize>::new());
+        assert_eq!(prefixed(b"").len(), keys.len());
+    }
+
+    #[test]
+    fn stats_count_nodes() {
+        let mut rax = RadixTree::new();
+
+        assert_eq!(rax.stats(), TreeStats::default());
+
+        for word in ["ab", "a", "ac", "bcd"].iter() {
+            rax.insert(*word, ());
+        }
+
+        let stats = rax.stats();
+
+        assert_eq!(stats.interior_nodes, 2);
+        assert_eq!(stats.leaf_nodes, 4);
+        assert_eq!(stats.empty_child_leaves, 1);
+        // The root branches on 'a' and 'b', leaving only the remaining key
+        // "cd" of "bcd"
+        assert_eq!(stats.prefix_bytes, 2);
+        assert_eq!(stats.depth_histogram.into_iter().collect::<Vec<_>>(), vec![(1, 1), (2, 3)]);
+        assert_eq!(stats.fan_out_histogram.into_iter().collect::<Vec<_>>(), vec![(2, 1), (3, 1)]);
+        assert!(stats.heap_bytes > 6 * ::core::mem::size_of::<RadixNode<Box<KeyValue<&str, ()>>>>());
+    }
+
+    #[test]
+    fn remove_compresses_interiors() {
+        let mut rax = RadixTree::new();
+        let words = ["romane", "romanus", "romulus", "rom", "rubens", "ruber", "r", "rubicon"];
+
+        for (idx, word) in words.iter().enumerate() {
+            rax.insert(*word, idx);
+            assert_eq!(rax.validate(), Ok(()));
+        }
+
+        for word in words[1..].iter() {
+            assert!(rax.remove(word).is_some());
+            assert_eq!(rax.validate(), Ok(()));
+        }
+
+        let stats = rax.stats();
+        assert_eq!((stats.interior_nodes, stats.leaf_nodes, stats.prefix_bytes), (0, 1, 6));
+        assert_eq!(rax.get(&"romane"), Some(&0));
+
+        rax.clear();
+        assert_eq!(rax.len(), 0);
+        assert_eq!(rax.validate(), Ok(()));
+    }
+
+    #[test]
+    fn validate_reports_size_mismatch() {
+        let mut rax = RadixTree::new();
+        rax.insert("a", 1);
+        rax.size = 3;
+
+        assert_eq!(
+            rax.validate().unwrap_err().kind,
+            ViolationKind::SizeMismatch { size: 3, leaves: 1 }
+        );
+    }
+
+    #[test]
+    fn dot_export() {
+        let mut rax = RadixTree::new();
+
+        assert_eq!(rax.to_dot(|value: &usize| value.to_string()), "digraph radix_tree {\n    node [fontname=\"monospace\"];\n}\n");
+
+        rax.insert(&b"ro"[..], 0);
+        rax.insert(&b"rom"[..], 1);
+        rax.insert(&b"ro\xff\x01"[..], 2);
+
+        assert_eq!(
+            rax.to_dot(|value| format!("#{}", value)),
+            concat!(
+                "digraph radix_tree {\n",
+                "    node [fontname=\"monospace\"];\n",
+                "    n0 [shape=box, label=\"ro\"];\n",
+                "    n1 [shape=ellipse, label=\"\\n#0\"];\n",
+                "    n0 -> n1 [label=\"∅\"];\n",
+                "    n2 [shape=ellipse, label=\"\\n#1\"];\n",
+                "    n0 -> n2 [label=\"m\"];\n",
+                "    n3 [shape=ellipse, label=\"\\\\u{1}\\n#2\"];\n",
+                "    n0 -> n3 [label=\"\\\\xff\"];\n",
+                "}\n",
+            )
+        );
+    }
+
+    #[test]
+    fn bulk_build_matches_inserts() {
+        let words = ["romane", "romanus", "romulus", "rom", "rubens", "ruber", "r", "rubicon", "", "rubicundus"];
+        let mut inserted = RadixTree::new();
+
+        for (idx, word) in words.iter().enumerate() {
+            inserted.insert(*word, idx);
+        }
+
+        let built = words.iter().cloned().zip(0..).collect::<RadixTree<_, _>>();
+
+        assert_eq!(built.validate(), Ok(()));
+        assert_eq!(built.len(), inserted.len());
+        assert_eq!(built.root, inserted.root);
+
+        // Repeated keys keep the last value, as with inserts
+        let repeated = vec![("b", 1), ("a", 2), ("b", 3), ("a", 4)].into_iter().collect::<RadixTree<_, _>>();
+
+        assert_eq!(repeated.iter().collect::<Vec<_>>(), vec![(&"a", &4), (&"b", &3)]);
+        assert_eq!(iter::empty::<(&str, ())>().collect::<RadixTree<_, _>>().len(), 0);
+    }
+
+    #[test]
+    fn try_from_sorted_checks_order() {
+        let words = ["", "r", "rom", "romane", "romanus", "romulus", "rubens", "ruber", "rubicon", "rubicundus"];
+        let built = RadixTree::<_, _>::try_from_sorted(words.iter().cloned().zip(0..)).unwrap();
+
+        assert_eq!(built.validate(), Ok(()));
+        assert_eq!(built.root, words.iter().cloned().zip(0..).collect::<RadixTree<_, _>>().root);
+
+        match RadixTree::<_, _>::try_from_sorted(vec![("a", 1), ("c", 2), ("b", 3)]) {
+            Err(::error::Error::InvalidKeyOrder { previous, next }) => {
+                assert_eq!((previous, next), (b"c".to_vec(), b"b".to_vec()));
+            }
+            other => panic!("unexpected {:?}", other.map(|tree| tree.len())),
+        }
+        assert!(RadixTree::<_, _>::try_from_sorted(vec![("a", 1), ("a", 2)]).is_err());
+    }
+
+    #[test]
+    fn try_insert_respects_limit() {
+        let mut rax = RadixTree::<&str, u32>::new();
+
+        assert_eq!(rax.try_insert("a", 1, 2).unwrap(), None);
+        assert_eq!(rax.try_insert("b", 2, 2).unwrap(), None);
+        assert!(rax.try_insert("c", 3, 2).is_err());
+        assert_eq!(rax.try_insert("a", 4, 2).unwrap(), Some(1));
+        assert_eq!(
+            rax.try_insert("c", 3, 0).map(|_| ()).unwrap_err().to_string(),
+            "radix tree keys needs 3, over the limit of 0"
+        );
+        assert_eq!(rax.len(), 2);
+        assert_eq!(rax.get(&"c"), None);
+    }
+}
@@ -0,0 +1,283 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::ascii;
+use core::error;
+use core::fmt;
+
+use allocator_api2::alloc::Allocator;
+use allocator_api2::boxed::Box;
+
+use super::alphabet::KeyAlphabet;
+use super::node::RadixNode;
+use super::entry::LeafEntry;
+
+// What is wrong with a tree, see `InvariantViolation`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ViolationKind {
+    // The size kept by the tree is not the number of leaves
+    SizeMismatch { size: usize, leaves: usize },
+    // The branching bytes of an interior node are not strictly ascending,
+    // equal bytes mean a duplicate child
+    UnsortedChildren { previous: u8, next: u8 },
+    // The layout holding the children disagrees with itself, like an index
+    // pointing past the children or a count that is off
+    InconsistentChildren { expected: usize, found: usize },
+    // An interior node with fewer than two entries should have been merged
+    // with its only entry, or removed
+    TooFewChildren { children: usize },
+    EmptyChildNotLeaf,
+    EmptyChildWithKey { remaining_key: Vec<u8> },
+    // The key stored in a leaf does not expand to the symbols along the path
+    // to it
+    KeyMismatch { stored_key: Vec<u8> },
+}
+
+// A broken invariant, with the symbols leading to the node at fault, which
+// are the key bytes unless the tree names another alphabet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvariantViolation {
+    pub path: Vec<u8>,
+    pub kind: ViolationKind,
+}
+
+fn escaped(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .flat_map(|&byte| ascii::escape_default(byte))
+        .map(char::from)
+        .collect()
+}
+
+impl fmt::Display for InvariantViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "at \"{}\": ", escaped(&self.path))?;
+
+        match self.kind {
+            ViolationKind::SizeMismatch { size, leaves } => {
+                write!(f, "tree size is {} but there are {} leaves", size, leaves)
+            }
+            ViolationKind::UnsortedChildren { previous, next } => write!(
+                f,
+                "child '{}' follows child '{}'",
+                escaped(&[next]),
+                escaped(&[previous])
+            ),
+            ViolationKind::InconsistentChildren { expected, found } => write!(
+                f,
+                "child layout expected {} children but found {}",
+                expected, found
+            ),
+            ViolationKind::TooFewChildren { children } => write!(f, "interior node has {} children", children),
+            ViolationKind::EmptyChildNotLeaf => write!(f, "empty child is an interior node"),
+            ViolationKind::EmptyChildWithKey { ref remaining_key } => write!(
+                f,
+                "empty child has remaining key \"{}\"",
+                escaped(remaining_key)
+            ),
+            ViolationKind::KeyMismatch { ref stored_key } => {
+                write!(f, "leaf stores key \"{}\"", escaped(stored_key))
+            }
+        }
+    }
+}
+
+impl error::Error for InvariantViolation {}
+
+// Checks every node, walking with an explicit stack and stopping at the
+// first violation found in key order
+pub fn validate_tree<E: LeafEntry, A: Allocator + Clone, L: KeyAlphabet>(
+    root: Option<&Box<RadixNode<E, A>, A>>,
+    size: usize,
+) -> Result<(), InvariantViolation> {
+    let mut pending = root.into_iter().map(|root| (&**root, 0, None, false)).collect::<Vec<_>>();
+    let mut path = Vec::new();
+    let mut leaves = 0;
+
+    while let Some((node, path_len, branch, is_empty_child)) = pending.pop() {
+        path.truncate(path_len);
+        path.extend(branch);
+
+        let violation = |path: &[u8], kind| {
+            Err(InvariantViolation {
+                path: path.to_vec(),
+                kind,
+            })
+        };
+
+        match *node {
+            RadixNode::Leaf(ref leaf) => {
+                let remaining_key = leaf.remaining_key().bytes();
+                leaves += 1;
+
+                if is_empty_child && !remaining_key.is_empty() {
+                    return violation(
+                        &path,
+                        ViolationKind::EmptyChildWithKey {
+                            remaining_key: remaining_key.to_vec(),
+                        },
+                    );
+                }
+
+                path.extend_from_slice(remaining_key);
+                if let Some(stored_key) = leaf.entry().key_bytes() {
+                    if *L::symbols(stored_key) != path[..] {
+                        return violation(
+                            &path,
+                            ViolationKind::KeyMismatch {
+                                stored_key: stored_key.to_vec(),
+                            },
+                        );
+                    }
+                }
+            }
+            RadixNode::Interior(ref interior) => {
+                if is_empty_child {
+                    return violation(&path, ViolationKind::EmptyChildNotLeaf);
+                }
+
+                path.extend_from_slice(interior.prefix().bytes());
+
+                let children = interior.children();
+                if let Err(kind) = children.check_layout() {
+                    return violation(&path, kind);
+                }
+
+                let entries = children.len() + children.contains_empty() as usize;
+                if entries < 2 {
+                    return violation(&path, ViolationKind::TooFewChildren { children: entries });
+                }
+
+                let path_len = path.len();
+                let first_child = pending.len();
+                pending.extend(
+                    children
+                        .iter()
+                        .map(|(branch, child)| (&**child, path_len, Some(branch), false)),
+                );
+                pending[first_child..].reverse();
+                pending.extend(
+                    children
+                        .get_child(None)
+                        .map(|child| (&**child, path_len, None, true)),
+                );
+            }
+        }
+    }
+
+    if leaves != size {
+        return Err(InvariantViolation {
+            path: Vec::new(),
+            kind: ViolationKind::SizeMismatch { size, leaves },
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod validate_tests {
+    use super::*;
+    use super::super::children::NodeChildren;
+    use super::super::entry::KeyValue;
+    use super::super::key::KeyPrefix;
+    use super::super::alphabet::ByteAlphabet;
+    use allocator_api2::alloc::Global;
+
+    type TestNode = RadixNode<Box<KeyValue<&'static str, usize>>>;
+
+    fn leaf(remaining_key: &str, key: &'static str) -> Box<TestNode> {
+        Box::new(RadixNode::new_leaf(KeyPrefix::new(remaining_key.as_bytes()), Box::new(KeyValue::new(key, 0))))
+    }
+
+    fn interior(prefix: &str, children: Vec<(Option<u8>, Box<TestNode>)>) -> Box<TestNode> {
+        let mut node_children = NodeChildren::new();
+        for (branch, child) in children {
+            node_children.insert_child(branch, child);
+        }
+
+        Box::new(RadixNode::new_interior(KeyPrefix::new(prefix.as_bytes()), node_children))
+    }
+
+    #[test]
+    fn valid_tree() {
+        let root = interior(
+            "ro",
+            vec![
+                (None, leaf("", "ro")),
+                (Some(b'm'), leaf("e", "rome")),
+                (Some(b'b'), leaf("", "rob")),
+            ],
+        );
+
+        assert_eq!(validate_tree::<_, _, ByteAlphabet>(Some(&root), 3), Ok(()));
+        assert_eq!(validate_tree::<Box<KeyValue<&str, usize>>, Global, ByteAlphabet>(None, 0), Ok(()));
+    }
+
+    #[test]
+    fn size_mismatch() {
+        let root = leaf("a", "a");
+
+        assert_eq!(
+            validate_tree::<_, _, ByteAlphabet>(Some(&root), 2),
+            Err(InvariantViolation {
+                path: Vec::new(),
+                kind: ViolationKind::SizeMismatch { size: 2, leaves: 1 },
+            })
+        );
+    }
+
+    #[test]
+    fn key_mismatch_reports_path() {
+        let root = interior(
+            "ro",
+            vec![
+                (Some(b'b'), leaf("", "rob")),
+                (Some(b'm'), leaf("e", "rome")),
+                (Some(b't'), leaf("", "rat")),
+            ],
+        );
+        let violation = validate_tree::<_, _, ByteAlphabet>(Some(&root), 3).unwrap_err();
+
+        assert_eq!(violation.path, b"rot".to_vec());
+        assert_eq!(
+            violation.kind,
+            ViolationKind::KeyMismatch {
+                stored_key: b"rat".to_vec(),
+            }
+        );
+        assert_eq!(violation.to_string(), "at \"rot\": leaf stores key \"rat\"");
+    }
+
+    #[test]
+    fn uncompressed_interior() {
+        let root = interior("ab", vec![(Some(b'c'), interior("", vec![(None, leaf("", "abc"))]))]);
+
+        assert_eq!(
+            validate_tree::<_, _, ByteAlphabet>(Some(&root), 1),
+            Err(InvariantViolation {
+                path: b"ab".to_vec(),
+                kind: ViolationKind::TooFewChildren { children: 1 },
+            })
+        );
+    }
+
+    #[test]
+    fn bad_empty_children() {
+        let root = interior("a", vec![(None, leaf("x", "ax")), (Some(b'b'), leaf("", "ab"))]);
+
+        assert_eq!(
+            validate_tree::<_, _, ByteAlphabet>(Some(&root), 2).unwrap_err().kind,
+            ViolationKind::EmptyChildWithKey {
+                remaining_key: b"x".to_vec(),
+            }
+        );
+
+        let nested = interior("", vec![(Some(b'b'), leaf("", "ab")), (Some(b'c'), leaf("", "ac"))]);
+        let root = interior("a", vec![(None, nested), (Some(b'd'), leaf("", "ad"))]);
+
+        assert_eq!(
+            validate_tree::<_, _, ByteAlphabet>(Some(&root), 3).unwrap_err().kind,
+            ViolationKind::EmptyChildNotLeaf
+        );
+    }
+}
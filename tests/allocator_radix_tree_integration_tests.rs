@@ -0,0 +1,154 @@
+extern crate streamers;
+extern crate unicode_segmentation;
+
+mod common;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::env;
+use std::ptr::NonNull;
+
+use streamers::radix_tree::{AllocError, Allocator, BitAlphabet, ByteAlphabet, KeyAlphabet, NibbleAlphabet, RadixTree,
+                            RebuiltKeys, StoredKeys};
+
+// Counts the allocations made through the global allocator by each thread,
+// so that other tests running alongside do not disturb the count
+struct CountingGlobal;
+
+thread_local! {
+    static GLOBAL_ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingGlobal {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = GLOBAL_ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingGlobal = CountingGlobal;
+
+fn global_allocations() -> usize {
+    GLOBAL_ALLOCATIONS.with(|count| count.get())
+}
+
+// Takes memory straight from the system, bypassing the global allocator, and
+// keeps track of what is still allocated
+#[derive(Default)]
+struct CountingAllocator {
+    allocations: Cell<usize>,
+    live_bytes: Cell<usize>,
+}
+
+unsafe impl Allocator for CountingAllocator {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        let ptr = if layout.size() == 0 {
+            NonNull::new(layout.align() as *mut u8)
+        } else {
+            NonNull::new(unsafe { System.alloc(layout) })
+        };
+
+        match ptr {
+            Some(ptr) => {
+                self.allocations.set(self.allocations.get() + 1);
+                self.live_bytes.set(self.live_bytes.get() + layout.size());
+
+                Ok(NonNull::slice_from_raw_parts(ptr, layout.size()))
+            }
+            None => Err(AllocError),
+        }
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        self.live_bytes.set(self.live_bytes.get() - layout.size());
+
+        if layout.size() != 0 {
+            System.dealloc(ptr.as_ptr(), layout);
+        }
+    }
+}
+
+// Inserts, removes and looks up every word in a tree over the alphabet `L`
+fn allocates_only_from_its_allocator<L: KeyAlphabet>() {
+    let mut words = common::read_file_into_unique_words("./assets/inferno.txt")
+        .unwrap_or_else(|_| panic!("Load file failed. Cwd {:?}", env::current_dir()));
+    // Long enough to expand past the stack in any alphabet
+    words.push("abandonment".repeat(16));
+    let alloc = CountingAllocator::default();
+    let before = global_allocations();
+
+    {
+        let mut rax: RadixTree<&str, usize, StoredKeys, &CountingAllocator, L> = RadixTree::with_alphabet_in(&alloc);
+
+        for (idx, word) in words.iter().enumerate() {
+            assert_eq!(rax.insert(word.as_str(), idx), None);
+        }
+
+        for (idx, word) in words.iter().enumerate().filter(|&(idx, _)| idx % 3 == 0) {
+            assert_eq!(rax.remove(&word.as_str()), Some(idx));
+
+            if idx % 2 == 0 {
+                assert_eq!(rax.insert(word.as_str(), idx * 10), None);
+            }
+        }
+
+        for (idx, word) in words.iter().enumerate() {
+            let expected = match idx % 6 {
+                0 => Some(idx * 10),
+                3 => None,
+                _ => Some(idx),
+            };
+
+            assert_eq!(rax.get(&word.as_str()).cloned(), expected);
+        }
+
+        assert!(alloc.live_bytes.get() > 0);
+    }
+
+    assert_eq!(global_allocations(), before);
+    assert!(alloc.allocations.get() > words.len());
+    assert_eq!(alloc.live_bytes.get(), 0);
+}
+
+#[test]
+fn tree_allocates_only_from_its_allocator() {
+    allocates_only_from_its_allocator::<ByteAlphabet>();
+}
+
+#[test]
+fn nibble_tree_allocates_only_from_its_allocator() {
+    allocates_only_from_its_allocator::<NibbleAlphabet>();
+}
+
+#[test]
+fn bit_tree_allocates_only_from_its_allocator() {
+    allocates_only_from_its_allocator::<BitAlphabet>();
+}
+
+#[test]
+fn rebuilt_keys_tree_allocates_only_from_its_allocator() {
+    let words = common::read_file_into_unique_words("./assets/inferno.txt")
+        .unwrap_or_else(|_| panic!("Load file failed. Cwd {:?}", env::current_dir()));
+    let alloc = CountingAllocator::default();
+    let mut rax = RadixTree::<Vec<u8>, usize, RebuiltKeys, _>::with_rebuilt_keys_in(&alloc);
+    let keys = words.iter().map(|word| word.as_bytes().to_vec()).collect::<Vec<_>>();
+    let removed = keys.iter().step_by(2).cloned().collect::<Vec<_>>();
+    let before = global_allocations();
+
+    for (idx, key) in keys.into_iter().enumerate() {
+        assert_eq!(rax.insert(key, idx), None);
+    }
+    for key in removed.iter() {
+        assert!(rax.remove(key).is_some());
+    }
+    assert_eq!(rax.len(), words.len() - removed.len());
+    rax.clear();
+
+    assert_eq!(global_allocations(), before);
+    assert_eq!(alloc.live_bytes.get(), 0);
+}
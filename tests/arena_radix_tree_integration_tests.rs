@@ -0,0 +1,39 @@
+extern crate streamers;
+extern crate unicode_segmentation;
+
+mod common;
+
+use std::env;
+
+use streamers::radix_tree::{ArenaRadixTree, RadixTree};
+
+#[test]
+fn arena_matches_boxed_tree() {
+    let words = common::read_file_into_unique_words("./assets/inferno.txt")
+        .unwrap_or_else(|_| panic!("Load file failed. Cwd {:?}", env::current_dir()));
+    let mut rax = ArenaRadixTree::new();
+    let mut expected = RadixTree::new();
+
+    for (idx, word) in words.iter().enumerate() {
+        assert_eq!(rax.insert(word.clone(), idx), expected.insert(word.clone(), idx));
+    }
+
+    for (idx, word) in words.iter().enumerate().filter(|&(idx, _)| idx % 3 == 0) {
+        assert_eq!(rax.remove(word), expected.remove(word));
+
+        if idx % 2 == 0 {
+            assert_eq!(rax.insert(word.clone(), idx * 10), expected.insert(word.clone(), idx * 10));
+        }
+    }
+
+    assert_eq!(rax.len(), expected.len());
+    for word in words.iter() {
+        assert_eq!(rax.get(word), expected.get(word), "{}", word);
+    }
+
+    for word in words.iter() {
+        assert_eq!(rax.remove(word), expected.remove(word), "{}", word);
+    }
+
+    assert!(rax.is_empty());
+}
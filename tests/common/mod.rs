@@ -0,0 +1,52 @@
+// Helpers shared by the integration tests, each test crate uses some of them
+#![allow(dead_code)]
+
+use std::fs;
+use std::io::{self, Read};
+use std::thread;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+pub fn read_file_into_words(filename: &str) -> io::Result<Vec<String>> {
+    let mut file = fs::File::open(filename)?;
+    let mut contents = String::new();
+
+    file.read_to_string(&mut contents)?;
+
+    let words = contents
+        .unicode_words()
+        .map(From::from)
+        .collect::<Vec<String>>();
+
+    Ok(words)
+}
+
+// The words of the file sorted and without repeats
+pub fn read_file_into_unique_words(filename: &str) -> io::Result<Vec<String>> {
+    let mut words = read_file_into_words(filename)?;
+
+    words.sort();
+    words.dedup();
+
+    Ok(words)
+}
+
+// Every key is a run of 'x' followed by a 'y', so each key branches off
+// the previous one at its last byte and the tree is as deep as the longest key
+pub fn pathological_keys(depth: usize) -> Vec<u8> {
+    let mut buffer = vec![b'x'; depth];
+    buffer.push(b'y');
+
+    buffer
+}
+
+// Runs `test` on a thread with a small stack, where walking a tree by
+// recursing once per level overflows long before the depths tests reach
+pub fn run_with_small_stack<F: FnOnce() + Send + 'static>(test: F) {
+    thread::Builder::new()
+        .stack_size(128 * 1024)
+        .spawn(test)
+        .expect("Spawning thread failed")
+        .join()
+        .expect("Thread panicked");
+}
@@ -0,0 +1,310 @@
+#![cfg(feature = "std")]
+
+extern crate streamers;
+extern crate unicode_segmentation;
+
+mod common;
+
+use std::env;
+use std::sync::{Arc, Barrier};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+
+use streamers::radix_tree::{ConcurrentRadixTree, RadixTree, RcuRadixTree};
+
+const NUM_THREADS: usize = 8;
+
+enum Operation<'a> {
+    Insert(&'a String, usize),
+    Remove(&'a String),
+}
+
+// Each thread owns every NUM_THREADS-th word, so the final contents do not
+// depend on how the threads interleave even though they share prefixes.
+fn thread_operations<'a>(words: &'a [String], thread_idx: usize) -> Vec<Operation<'a>> {
+    let owned = words
+        .iter()
+        .enumerate()
+        .filter(|&(idx, _)| idx % NUM_THREADS == thread_idx)
+        .collect::<Vec<_>>();
+    let mut operations = Vec::new();
+
+    for &(idx, word) in owned.iter() {
+        operations.push(Operation::Insert(word, idx));
+    }
+
+    for &(idx, word) in owned.iter().filter(|&&(idx, _)| idx % 3 == 0) {
+        operations.push(Operation::Remove(word));
+
+        if idx % 2 == 0 {
+            operations.push(Operation::Insert(word, idx * 10));
+        }
+    }
+
+    for &(idx, word) in owned.iter().filter(|&&(idx, _)| idx % 5 == 0) {
+        operations.push(Operation::Insert(word, idx + 1));
+    }
+
+    operations
+}
+
+#[test]
+fn concurrent_matches_sequential() {
+    let words = common::read_file_into_unique_words("./assets/inferno.txt")
+        .unwrap_or_else(|_| panic!("Load file failed. Cwd {:?}", env::current_dir()));
+    let words = Arc::new(words);
+    let rax = Arc::new(ConcurrentRadixTree::new());
+
+    let writers = (0..NUM_THREADS)
+        .map(|thread_idx| {
+            let words = words.clone();
+            let rax = rax.clone();
+
+            thread::spawn(move || {
+                for operation in thread_operations(&words, thread_idx) {
+                    match operation {
+                        Operation::Insert(word, value) => {
+                            rax.insert(word.clone(), value);
+                        }
+                        Operation::Remove(word) => {
+                            rax.remove(word);
+                        }
+                    }
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let readers = (0..NUM_THREADS / 2)
+        .map(|thread_idx| {
+            let words = words.clone();
+            let rax = rax.clone();
+
+            thread::spawn(move || {
+                for word in words.iter().skip(thread_idx).step_by(NUM_THREADS / 2) {
+                    if let Some(value) = rax.get(word) {
+                        assert!(value <= words.len() * 10);
+                    }
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for handle in writers.into_iter().chain(readers) {
+        handle.join().expect("Thread panicked");
+    }
+
+    let mut expected = RadixTree::new();
+    for thread_idx in 0..NUM_THREADS {
+        for operation in thread_operations(&words, thread_idx) {
+            match operation {
+                Operation::Insert(word, value) => {
+                    expected.insert(word.clone(), value);
+                }
+                Operation::Remove(word) => {
+                    expected.remove(word);
+                }
+            }
+        }
+    }
+
+    assert_eq!(rax.len(), expected.len());
+    for word in words.iter() {
+        assert_eq!(rax.get(word), expected.get(word).cloned(), "{}", word);
+    }
+}
+
+#[test]
+fn concurrent_shared_keys() {
+    let words = common::read_file_into_unique_words("./assets/inferno.txt")
+        .unwrap_or_else(|_| panic!("Load file failed. Cwd {:?}", env::current_dir()));
+    let words = Arc::new(words);
+    let rax = Arc::new(ConcurrentRadixTree::new());
+    let barrier = Arc::new(Barrier::new(NUM_THREADS));
+
+    let handles = (0..NUM_THREADS)
+        .map(|thread_idx| {
+            let words = words.clone();
+            let rax = rax.clone();
+            let barrier = barrier.clone();
+
+            thread::spawn(move || {
+                for (idx, word) in words.iter().enumerate() {
+                    rax.insert(word.clone(), idx);
+                }
+
+                barrier.wait();
+
+                for (idx, word) in words.iter().enumerate() {
+                    if idx % NUM_THREADS == thread_idx && idx % 2 == 0 {
+                        assert_eq!(rax.remove(word), Some(idx));
+                    }
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for handle in handles {
+        handle.join().expect("Thread panicked");
+    }
+
+    assert_eq!(rax.len(), words.len() / 2);
+    for (idx, word) in words.iter().enumerate() {
+        if idx % 2 == 0 {
+            assert_eq!(rax.get(word), None);
+        } else {
+            assert_eq!(rax.get(word), Some(idx));
+        }
+    }
+}
+
+#[test]
+fn concurrent_pathological_keys_branch_at_every_byte() {
+    common::run_with_small_stack(|| {
+        const DEPTH: usize = 5_000;
+
+        let buffer = common::pathological_keys(DEPTH);
+        let keys = (0..DEPTH + 1)
+            .rev()
+            .map(|idx| buffer[idx..].to_vec())
+            .collect::<Vec<Vec<u8>>>();
+        let rax = ConcurrentRadixTree::new();
+
+        for (idx, key) in keys.iter().enumerate() {
+            assert_eq!(rax.insert(key.clone(), idx), None);
+        }
+        assert_eq!(rax.len(), DEPTH + 1);
+
+        // Finding a key walks one level per byte, so only check a sample
+        for idx in (0..DEPTH + 1).step_by(997) {
+            assert_eq!(rax.get(&keys[idx]), Some(idx));
+        }
+        assert_eq!(rax.remove(&keys[DEPTH]), Some(DEPTH));
+        assert_eq!(rax.get(&keys[DEPTH]), None);
+    });
+}
+
+#[test]
+fn rcu_pathological_keys_branch_at_every_byte() {
+    common::run_with_small_stack(|| {
+        const DEPTH: usize = 2_000;
+
+        let buffer = common::pathological_keys(DEPTH);
+        let keys = (0..DEPTH + 1)
+            .rev()
+            .map(|idx| buffer[idx..].to_vec())
+            .collect::<Vec<Vec<u8>>>();
+        let mut rax = RcuRadixTree::new();
+
+        for (idx, key) in keys.iter().enumerate() {
+            assert_eq!(rax.insert(key.clone(), idx), None);
+        }
+        assert_eq!(rax.len(), DEPTH + 1);
+
+        // Finding a key walks one level per byte, so only check a sample
+        let sample = (0..DEPTH + 1).step_by(997).chain(DEPTH - 2..DEPTH + 1).collect::<Vec<_>>();
+        let snapshot = rax.read();
+        for &idx in sample.iter() {
+            assert_eq!(snapshot.get(&keys[idx]), Some(&idx));
+        }
+
+        for &idx in sample.iter().filter(|&&idx| idx != DEPTH) {
+            assert_eq!(rax.remove(&keys[idx]), Some(idx));
+            assert_eq!(rax.read().get(&keys[idx]), None);
+        }
+        assert_eq!(rax.len(), DEPTH + 1 - (sample.len() - 1));
+        assert_eq!(rax.read().get(&keys[DEPTH]), Some(&DEPTH));
+
+        // The snapshot still holds the tree from before the removals
+        assert_eq!(snapshot.len(), DEPTH + 1);
+        assert_eq!(snapshot.get(&keys[0]), Some(&0));
+    });
+}
+
+#[test]
+fn rcu_long_keys_share_prefixes() {
+    const KEY_LEN: usize = 100_000;
+
+    let mut rax = RcuRadixTree::new();
+    let keys = (0..64u8)
+        .map(|idx| {
+            let mut key = vec![b'a'; KEY_LEN];
+            key[KEY_LEN - 1 - idx as usize * 997] = b'b' + idx % 8;
+            key
+        })
+        .collect::<Vec<Vec<u8>>>();
+
+    for (idx, key) in keys.iter().enumerate() {
+        assert_eq!(rax.insert(key.clone(), idx), None);
+    }
+
+    let snapshot = rax.read();
+    for (idx, key) in keys.iter().enumerate() {
+        assert_eq!(snapshot.get(key), Some(&idx));
+    }
+    assert_eq!(snapshot.get(&vec![b'a'; KEY_LEN]), None);
+    assert_eq!(snapshot.iter().count(), keys.len());
+    drop(snapshot);
+
+    for (idx, key) in keys.iter().enumerate().step_by(2) {
+        assert_eq!(rax.remove(key), Some(idx));
+    }
+    assert_eq!(rax.len(), keys.len() / 2);
+}
+
+#[test]
+fn rcu_readers_observe_consistent_snapshots() {
+    let words = common::read_file_into_unique_words("./assets/inferno.txt")
+        .unwrap_or_else(|_| panic!("Load file failed. Cwd {:?}", env::current_dir()));
+    let words = Arc::new(words);
+    let mut rax: RcuRadixTree<String, usize> = RcuRadixTree::new();
+    let done = Arc::new(AtomicBool::new(false));
+
+    let readers = (0..NUM_THREADS)
+        .map(|_| {
+            let reader = rax.reader();
+            let words = words.clone();
+            let done = done.clone();
+
+            thread::spawn(move || {
+                while !done.load(Ordering::Acquire) {
+                    let snapshot = reader.read();
+                    let entries = snapshot.iter().collect::<Vec<_>>();
+
+                    assert_eq!(entries.len(), snapshot.len());
+                    assert!(entries.windows(2).all(|pair| pair[0].0 < pair[1].0));
+
+                    for &(word, value) in entries.iter() {
+                        assert_eq!(&words[*value], word);
+                        assert_eq!(snapshot.get(word), Some(value));
+                    }
+
+                    let prefixed = snapshot.prefix_iter(&String::from("th")).count();
+                    let expected = entries.iter().filter(|&&(word, _)| word.starts_with("th")).count();
+                    assert_eq!(prefixed, expected);
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut expected = RadixTree::new();
+    for (idx, word) in words.iter().enumerate() {
+        rax.insert(word.clone(), idx);
+        expected.insert(word.clone(), idx);
+
+        if idx % 3 == 0 {
+            let removed = &words[idx / 2];
+            assert_eq!(rax.remove(removed), expected.remove(removed));
+        }
+    }
+
+    done.store(true, Ordering::Release);
+    for handle in readers {
+        handle.join().expect("Thread panicked");
+    }
+
+    assert_eq!(rax.len(), expected.len());
+    for word in words.iter() {
+        assert_eq!(rax.read().get(word), expected.get(word));
+    }
+}
@@ -0,0 +1,43 @@
+#![cfg(feature = "std")]
+
+extern crate streamers;
+extern crate unicode_segmentation;
+
+mod common;
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::env;
+use std::process;
+
+use streamers::radix_tree::DurableRadixTree;
+
+#[test]
+fn durable_tree_survives_reopens() {
+    let words = common::read_file_into_words("./assets/inferno.txt")
+        .unwrap_or_else(|_| panic!("Load file failed. Cwd {:?}", env::current_dir()));
+    let dir = env::temp_dir().join(format!("durable_radix_tree_integration_{}", process::id()));
+    let _ = fs::remove_dir_all(&dir);
+
+    let mut map = BTreeMap::new();
+    for chunk in words.chunks(2000).take(5) {
+        let mut tree = DurableRadixTree::open(&dir, 3000).expect("Opening tree failed");
+        assert_eq!(tree.len(), map.len());
+
+        for (idx, word) in chunk.iter().enumerate() {
+            let key = word.as_bytes().to_vec();
+
+            if idx % 7 == 0 {
+                assert_eq!(tree.remove(&key).unwrap(), map.remove(&key));
+            } else {
+                assert_eq!(tree.insert(key.clone(), idx as u32).unwrap(), map.insert(key, idx as u32));
+            }
+        }
+    }
+
+    let tree = DurableRadixTree::<u32>::open(&dir, 3000).expect("Reopening tree failed");
+    assert!(tree.iter().map(|(key, &value)| (key.clone(), value)).eq(map.into_iter()));
+
+    drop(tree);
+    fs::remove_dir_all(&dir).expect("Removing directory failed");
+}
@@ -0,0 +1,60 @@
+extern crate streamers;
+extern crate unicode_segmentation;
+
+mod common;
+
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::fs;
+use std::env;
+
+use streamers::radix_tree::{FrozenRadixTree, RadixTree};
+
+#[test]
+fn frozen_file_matches_btree_map() {
+    let words = common::read_file_into_words("./assets/inferno.txt")
+        .unwrap_or_else(|_| panic!("Load file failed. Cwd {:?}", env::current_dir()));
+
+    let mut map = BTreeMap::new();
+    let mut rax = RadixTree::new();
+    for (idx, word) in words.iter().enumerate() {
+        map.insert(word.as_bytes().to_vec(), idx as u64);
+        rax.insert(word.as_bytes().to_vec(), idx as u64);
+    }
+
+    let path = env::temp_dir().join(format!("frozen_radix_tree_{}.bin", std::process::id()));
+    fs::File::create(&path)
+        .and_then(|mut file| file.write_all(&rax.to_frozen().expect("Writing tree failed")))
+        .expect("Writing file failed");
+    let mut bytes = Vec::new();
+    fs::File::open(&path)
+        .and_then(|mut file| file.read_to_end(&mut bytes))
+        .expect("Reading file failed");
+    fs::remove_file(&path).expect("Removing file failed");
+
+    let frozen = FrozenRadixTree::<u64>::new(&bytes).expect("Opening tree failed");
+    assert_eq!(frozen.len(), map.len());
+
+    for (key, value) in map.iter() {
+        assert_eq!(frozen.get(key).expect("Decoding value failed"), Some(*value));
+    }
+    assert!(frozen.iter().map(Result::unwrap).eq(map.iter().map(|(key, value)| (key.clone(), *value))));
+
+    let prefix = b"the";
+    assert!(
+        frozen
+            .prefix_iter(prefix)
+            .map(Result::unwrap)
+            .eq(map.iter()
+                .filter(|&(key, _)| key.starts_with(prefix))
+                .map(|(key, value)| (key.clone(), *value)))
+    );
+
+    let (lower, upper) = (b"dark".to_vec(), b"light".to_vec());
+    assert!(
+        frozen
+            .range(lower.clone()..upper.clone())
+            .map(Result::unwrap)
+            .eq(map.range(lower..upper).map(|(key, value)| (key.clone(), *value)))
+    );
+}
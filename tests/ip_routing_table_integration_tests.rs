@@ -0,0 +1,84 @@
+extern crate streamers;
+
+use std::collections::BTreeMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use streamers::radix_tree::{IpNet, IpRoutingTable, Ipv4Net, Ipv6Net};
+
+// A fixed xorshift sequence, so every run checks the same routes
+struct Random(u64);
+
+impl Random {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    // Addresses drawn from a few narrow blocks so that routes nest
+    fn addr(&mut self, v6: bool) -> IpAddr {
+        let block = self.next() % 4;
+
+        if v6 {
+            let low = u128::from(self.next()) & 0xffff_ffff;
+            IpAddr::V6(Ipv6Addr::from((0x2001_0db8 + u128::from(block)) << 96 | low))
+        } else {
+            let low = self.next() as u32 & 0x00ff_ffff;
+            IpAddr::V4(Ipv4Addr::from((10 + block as u32) << 24 | low))
+        }
+    }
+
+    fn net(&mut self, v6: bool) -> IpNet {
+        let addr = self.addr(v6);
+
+        match addr {
+            IpAddr::V4(addr) => Ipv4Net::new(addr, (self.next() % 33) as u8).unwrap().into(),
+            IpAddr::V6(addr) => Ipv6Net::new(addr, (self.next() % 129) as u8).unwrap().into(),
+        }
+    }
+}
+
+#[test]
+fn routing_table_matches_linear_scan() {
+    let mut random = Random(0x9e37_79b9_7f4a_7c15);
+    let mut table = IpRoutingTable::new();
+    let mut expected = BTreeMap::new();
+
+    for idx in 0..3000 {
+        let net = random.net(idx % 3 == 0);
+        assert_eq!(table.insert(net, idx), expected.insert(net, idx));
+    }
+
+    for net in expected.keys().cloned().step_by(3).collect::<Vec<_>>() {
+        assert_eq!(table.remove(net), expected.remove(&net));
+        assert_eq!(table.remove(net), None);
+    }
+
+    assert_eq!(table.len(), expected.len());
+
+    for idx in 0..2000 {
+        let addr = random.addr(idx % 3 == 0);
+        let host = IpNet::from(addr);
+        let covering = expected
+            .iter()
+            .filter(|&(net, _)| net.contains(&host))
+            .map(|(net, value)| (*net, value))
+            .collect::<Vec<_>>();
+        let longest = covering.iter().max_by_key(|&&(net, _)| net.prefix_len()).cloned();
+
+        assert_eq!(table.longest_match(addr), longest, "{}", addr);
+        assert_eq!(table.covering(host).count(), covering.len(), "{}", addr);
+
+        let net = random.net(idx % 3 == 0);
+        let covered = expected
+            .iter()
+            .filter(|&(route, _)| net.contains(route))
+            .map(|(route, value)| (*route, value))
+            .collect::<Vec<_>>();
+        let mut found = table.covered_by(net).collect::<Vec<_>>();
+        found.sort();
+
+        assert_eq!(found, covered, "{}", net);
+    }
+}
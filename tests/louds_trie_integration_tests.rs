@@ -0,0 +1,43 @@
+extern crate streamers;
+extern crate unicode_segmentation;
+
+mod common;
+
+use std::collections::BTreeSet;
+use std::env;
+
+use streamers::radix_tree::RadixTree;
+
+#[test]
+fn louds_matches_btree_set() {
+    let words = common::read_file_into_words("./assets/inferno.txt")
+        .unwrap_or_else(|_| panic!("Load file failed. Cwd {:?}", env::current_dir()));
+
+    let set = words.iter().map(|word| word.as_bytes().to_vec()).collect::<BTreeSet<_>>();
+    let rax = words
+        .iter()
+        .map(|word| (word.as_bytes().to_vec(), ()))
+        .collect::<RadixTree<_, _>>();
+    let louds = rax.to_louds();
+
+    assert_eq!(louds.len(), set.len());
+    assert!(louds.iter().map(|(key, _)| key).eq(set.iter().cloned()));
+
+    let mut ids = BTreeSet::new();
+    for key in set.iter() {
+        let id = louds.lookup(key).expect("Key is missing");
+
+        assert_eq!(louds.key(id).as_ref(), Some(key));
+        assert!(ids.insert(id));
+    }
+    assert_eq!(ids.into_iter().collect::<Vec<_>>(), (0..set.len()).collect::<Vec<_>>());
+
+    let prefix = b"the";
+    assert!(
+        louds
+            .prefix_iter(prefix)
+            .map(|(key, _)| key)
+            .eq(set.iter().filter(|key| key.starts_with(prefix)).cloned())
+    );
+    assert!(!louds.contains(b"thee thou"));
+}
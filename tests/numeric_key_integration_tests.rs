@@ -0,0 +1,87 @@
+extern crate streamers;
+
+use streamers::radix_tree::{F64Key, I64Key, RadixTree, RebuiltKeys, U64Key};
+
+// Small xorshift generator, enough to scramble the insertion order
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+#[test]
+fn unsigned_keys_iterate_in_numeric_order() {
+    let mut rng = XorShift(0x2545_f491_4f6c_dd1d);
+    let mut values = (0..2_000).map(|_| rng.next() >> (rng.next() % 64)).collect::<Vec<_>>();
+    let mut rax = RadixTree::new();
+
+    for &value in values.iter() {
+        rax.insert(U64Key::new(value), value);
+    }
+    values.sort();
+    values.dedup();
+
+    assert_eq!(rax.iter().map(|(_, &value)| value).collect::<Vec<_>>(), values);
+    assert_eq!(rax.first().map(|(key, _)| key.value()), values.first().cloned());
+    assert_eq!(rax.last().map(|(key, _)| key.value()), values.last().cloned());
+
+    let (low, high) = (values[values.len() / 4], values[values.len() / 2]);
+    let expected = values.iter().cloned().filter(|&value| low <= value && value < high).collect::<Vec<_>>();
+
+    assert_eq!(
+        rax.range(U64Key::new(low)..U64Key::new(high)).map(|(_, &value)| value).collect::<Vec<_>>(),
+        expected
+    );
+}
+
+#[test]
+fn signed_keys_iterate_in_numeric_order() {
+    let mut rng = XorShift(0x9e37_79b9_7f4a_7c15);
+    let mut values = (0..2_000).map(|_| (rng.next() % 2_000_000) as i64 - 1_000_000).collect::<Vec<_>>();
+    let mut rax: RadixTree<I64Key, (), RebuiltKeys> = RadixTree::with_rebuilt_keys();
+
+    for &value in values.iter() {
+        rax.insert(I64Key::new(value), ());
+    }
+    values.sort();
+    values.dedup();
+
+    assert_eq!(rax.iter().map(|(key, _)| key.value()).collect::<Vec<_>>(), values);
+    assert_eq!(rax.first().map(|(key, _)| key.value()), values.first().cloned());
+    assert_eq!(rax.last().map(|(key, _)| key.value()), values.last().cloned());
+
+    let expected = values.iter().cloned().filter(|&value| (-500..=500_000).contains(&value)).collect::<Vec<_>>();
+
+    assert_eq!(
+        rax.range(I64Key::new(-500)..=I64Key::new(500_000)).map(|(key, _)| key.value()).collect::<Vec<_>>(),
+        expected
+    );
+}
+
+#[test]
+fn float_keys_iterate_in_numeric_order() {
+    let mut rng = XorShift(0xd1b5_4a32_d192_ed03);
+    let mut values = (0..2_000).map(|_| (rng.next() as f64 / u64::MAX as f64 - 0.5) * 2e6).collect::<Vec<_>>();
+    let mut rax = RadixTree::new();
+
+    for &value in values.iter() {
+        rax.insert(F64Key::new(value), value);
+    }
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+    assert_eq!(rax.iter().map(|(_, &value)| value).collect::<Vec<_>>(), values);
+    assert_eq!(rax.first().map(|(_, &value)| value), values.first().cloned());
+    assert_eq!(rax.last().map(|(_, &value)| value), values.last().cloned());
+
+    let expected = values.iter().cloned().filter(|&value| value > -0.5).collect::<Vec<_>>();
+
+    assert_eq!(
+        rax.range(F64Key::new(-0.5)..).map(|(_, &value)| value).collect::<Vec<_>>(),
+        expected
+    );
+}
@@ -0,0 +1,54 @@
+#![cfg(feature = "std")]
+
+extern crate streamers;
+extern crate unicode_segmentation;
+
+mod common;
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::env;
+use std::process;
+
+use streamers::radix_tree::PagedRadixTree;
+
+#[test]
+fn paged_tree_matches_btree_map() {
+    let words = common::read_file_into_words("./assets/inferno.txt")
+        .unwrap_or_else(|_| panic!("Load file failed. Cwd {:?}", env::current_dir()));
+    let path = env::temp_dir().join(format!("paged_radix_tree_integration_{}.bin", process::id()));
+    let _ = fs::remove_file(&path);
+
+    let mut map = BTreeMap::new();
+    {
+        let mut tree = PagedRadixTree::open(&path, 64).expect("Opening tree failed");
+
+        for (idx, word) in words.iter().enumerate() {
+            let idx = idx as u64;
+
+            assert_eq!(tree.insert(word.as_bytes(), idx).unwrap(), map.insert(word.as_bytes().to_vec(), idx));
+            if idx.is_multiple_of(5) {
+                let removed = words[idx as usize / 2].as_bytes();
+                assert_eq!(tree.remove(removed).unwrap(), map.remove(removed));
+            }
+        }
+
+        tree.commit().expect("Commit failed");
+    }
+
+    let mut tree = PagedRadixTree::<u64>::open(&path, 64).expect("Reopening tree failed");
+    assert_eq!(tree.len(), map.len());
+    for (key, value) in map.iter() {
+        assert_eq!(tree.get(key).unwrap(), Some(*value));
+    }
+    assert!(
+        tree.prefix_iter(b"the")
+            .map(|entry| entry.unwrap())
+            .eq(map.iter()
+                .filter(|&(key, _)| key.starts_with(b"the"))
+                .map(|(key, value)| (key.clone(), *value)))
+    );
+
+    drop(tree);
+    fs::remove_file(&path).expect("Removing file failed");
+}
@@ -0,0 +1,47 @@
+extern crate streamers;
+extern crate unicode_segmentation;
+
+mod common;
+
+use std::collections::BTreeMap;
+use std::env;
+
+use streamers::radix_tree::RadixMultiMap;
+
+// Indexes every word by its positions in the text, like the postings of an
+// inverted index
+#[test]
+fn positions_match_btree_map() {
+    let words = common::read_file_into_words("./assets/inferno.txt")
+        .unwrap_or_else(|_| panic!("Load file failed. Cwd {:?}", env::current_dir()));
+    let mut index = RadixMultiMap::new();
+    let mut oracle = BTreeMap::new();
+
+    for (position, word) in words.iter().enumerate() {
+        index.insert(word.clone(), position);
+        oracle.entry(word.clone()).or_insert_with(Vec::new).push(position);
+    }
+    assert_eq!(index.len(), words.len());
+    assert_eq!(index.key_count(), oracle.len());
+
+    for (position, word) in words.iter().enumerate().step_by(3) {
+        assert!(index.remove_one(word, &position));
+
+        let positions = oracle.get_mut(word).unwrap();
+        positions.retain(|&other| other != position);
+        if positions.is_empty() {
+            oracle.remove(word);
+        }
+    }
+    for word in words.iter().step_by(101) {
+        assert_eq!(index.remove_all(word), oracle.remove(word).unwrap_or_default());
+    }
+
+    assert_eq!(index.len(), oracle.values().map(Vec::len).sum::<usize>());
+    assert_eq!(index.key_count(), oracle.len());
+    assert!(
+        index
+            .iter()
+            .eq(oracle.iter().map(|(word, positions)| (word, &positions[..])))
+    );
+}
@@ -0,0 +1,37 @@
+extern crate streamers;
+extern crate unicode_segmentation;
+
+mod common;
+
+use std::collections::BTreeSet;
+use std::env;
+
+use streamers::radix_tree::RadixSet;
+
+#[test]
+fn set_matches_btree_set() {
+    let words = common::read_file_into_words("./assets/inferno.txt")
+        .unwrap_or_else(|_| panic!("Load file failed. Cwd {:?}", env::current_dir()));
+    let mut set = RadixSet::new();
+    let mut oracle = BTreeSet::new();
+
+    for word in words.iter() {
+        assert_eq!(set.insert(word.clone()), oracle.insert(word.clone()));
+    }
+    for word in words.iter().step_by(5) {
+        assert_eq!(set.remove(word), oracle.remove(word));
+    }
+
+    assert_eq!(set.len(), oracle.len());
+    assert!(set.iter().eq(oracle.iter()));
+    assert!(set.prefix_iter(b"th").eq(oracle.iter().filter(|word| word.starts_with("th"))));
+
+    let evens = words.iter().step_by(2).cloned().collect::<RadixSet<_>>();
+    let oracle_evens = words.iter().step_by(2).cloned().collect::<BTreeSet<_>>();
+
+    assert!(set.union(&evens).eq(oracle.union(&oracle_evens)));
+    assert!(set.intersection(&evens).eq(oracle.intersection(&oracle_evens)));
+    assert!(set.difference(&evens).eq(oracle.difference(&oracle_evens)));
+    assert!(evens.difference(&set).eq(oracle_evens.difference(&oracle)));
+    assert!(set.symmetric_difference(&evens).eq(oracle.symmetric_difference(&oracle_evens)));
+}
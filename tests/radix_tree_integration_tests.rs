@@ -1,13 +1,11 @@
 extern crate streamers;
 extern crate unicode_segmentation;
 
-use std::io::{self, Read};
-use std::fs;
-use std::env;
+mod common;
 
-use unicode_segmentation::UnicodeSegmentation;
+use std::env;
 
-use streamers::radix_tree::RadixTree;
+use streamers::radix_tree::{BitAlphabet, Global, NibbleAlphabet, RadixTree, RebuiltKeys, StoredKeys};
 
 #[test]
 fn basic_insert_retrieve() {
@@ -31,49 +29,36 @@ fn basic_insert_retrieve() {
     assert_eq!(rax.get(&"hella"), Some(&30));
     assert_eq!(rax.get(&"hello"), Some(&31));
 
+    #[cfg(feature = "std")]
     println!("{:?}", rax.debug_view());
 }
 
-fn read_file_into_words(filename: &str) -> io::Result<Vec<String>> {
-    let mut file = fs::File::open(filename)?;
-    let mut contents = String::new();
-
-    file.read_to_string(&mut contents)?;
-
-    let words = contents
-        .unicode_words()
-        .map(From::from)
-        .collect::<Vec<String>>();
-
-    Ok(words)
-}
-
 #[test]
 #[ignore]
 fn insert_large_file_words_inferno() {
-    let words = read_file_into_words("./assets/inferno.txt")
-        .expect(&format!("Load file failed. Cwd {:?}", env::current_dir()));
+    let words = common::read_file_into_words("./assets/inferno.txt")
+        .unwrap_or_else(|_| panic!("Load file failed. Cwd {:?}", env::current_dir()));
     let mut rax = RadixTree::new();
 
     for word in words.iter() {
         rax.insert(word, ());
     }
 
-    assert!(rax.len() < words.iter().count());
+    assert!(rax.len() < words.len());
 }
 
 #[test]
 #[ignore]
 fn insert_large_file_words_don_quixote() {
-    let words = read_file_into_words("./assets/don_quixote.txt")
-        .expect(&format!("Load file failed. Cwd {:?}", env::current_dir()));
+    let words = common::read_file_into_words("./assets/don_quixote.txt")
+        .unwrap_or_else(|_| panic!("Load file failed. Cwd {:?}", env::current_dir()));
     let mut rax = RadixTree::new();
 
     for word in words.iter() {
         rax.insert(word, ());
     }
 
-    assert!(rax.len() < words.iter().count());
+    assert!(rax.len() < words.len());
 }
 
 use std::collections::{HashMap, HashSet};
@@ -81,8 +66,8 @@ use std::collections::{HashMap, HashSet};
 #[test]
 #[ignore]
 fn insert_large_file_words_word_list() {
-    let words = read_file_into_words("./assets/words.txt")
-        .expect(&format!("Load file failed. Cwd {:?}", env::current_dir()));
+    let words = common::read_file_into_words("./assets/words.txt")
+        .unwrap_or_else(|_| panic!("Load file failed. Cwd {:?}", env::current_dir()));
     let mut rax = RadixTree::new();
 
     let mut word_map = HashMap::new();
@@ -103,5 +88,126 @@ fn insert_large_file_words_word_list() {
         rax.insert(word, ());
     }
 
-    assert_eq!(rax.len(), words.iter().count());
+    assert_eq!(rax.len(), words.len());
+}
+
+#[test]
+fn pathological_keys_branch_at_every_byte() {
+    const DEPTH: usize = 5_000;
+
+    let buffer = common::pathological_keys(DEPTH);
+    let keys = (0..DEPTH + 1)
+        .rev()
+        .map(|idx| &buffer[idx..])
+        .collect::<Vec<&[u8]>>();
+    let mut rax = RadixTree::new();
+
+    for (idx, key) in keys.iter().enumerate() {
+        assert_eq!(rax.insert(*key, idx), None);
+    }
+    assert_eq!(rax.len(), DEPTH + 1);
+
+    // Finding a key walks one level per byte, so only check a sample
+    let sample = (0..DEPTH + 1).step_by(997).chain(DEPTH - 2..DEPTH + 1).collect::<Vec<_>>();
+
+    for &idx in sample.iter() {
+        assert_eq!(rax.get(&keys[idx]), Some(&idx));
+    }
+    assert_eq!(rax.get(&&buffer[..DEPTH]), None);
+
+    *rax.get_mut(&keys[DEPTH]).unwrap() += 1;
+    assert_eq!(rax.insert(keys[DEPTH], 0), Some(DEPTH + 1));
+
+    for &idx in sample.iter().filter(|&&idx| idx != DEPTH) {
+        assert_eq!(rax.remove(&keys[idx]), Some(idx));
+        assert_eq!(rax.get(&keys[idx]), None);
+    }
+    assert_eq!(rax.len(), DEPTH + 1 - (sample.len() - 1));
+    assert_eq!(rax.get(&keys[DEPTH]), Some(&0));
+    assert_eq!(rax.validate(), Ok(()));
+}
+
+#[test]
+fn long_keys_share_prefixes() {
+    const KEY_LEN: usize = 100_000;
+
+    let mut rax = RadixTree::new();
+    let keys = (0..64u8)
+        .map(|idx| {
+            let mut key = vec![b'a'; KEY_LEN];
+            key[KEY_LEN - 1 - idx as usize * 997] = b'b' + idx % 8;
+            key
+        })
+        .collect::<Vec<Vec<u8>>>();
+
+    for (idx, key) in keys.iter().enumerate() {
+        assert_eq!(rax.insert(key.clone(), idx), None);
+    }
+
+    for (idx, key) in keys.iter().enumerate() {
+        assert_eq!(rax.get(key), Some(&idx));
+    }
+    assert_eq!(rax.get(&vec![b'a'; KEY_LEN]), None);
+    assert_eq!(rax.validate(), Ok(()));
+}
+
+#[test]
+fn rebuilt_keys_match_stored_keys() {
+    let words = common::read_file_into_words("./assets/inferno.txt")
+        .unwrap_or_else(|_| panic!("Load file failed. Cwd {:?}", env::current_dir()));
+    let mut stored = RadixTree::new();
+    let mut rebuilt = RadixTree::with_rebuilt_keys();
+
+    for (idx, word) in words.iter().enumerate() {
+        assert_eq!(stored.insert(word.clone(), idx), rebuilt.insert(word.clone(), idx));
+    }
+
+    for word in words.iter().step_by(7) {
+        assert_eq!(stored.remove(word), rebuilt.remove(word));
+    }
+
+    assert_eq!(stored.len(), rebuilt.len());
+    assert_eq!(stored.validate(), Ok(()));
+    assert_eq!(rebuilt.validate(), Ok(()));
+    assert_eq!(rebuilt.iter().count(), rebuilt.len());
+    for ((stored_key, stored_value), (rebuilt_key, rebuilt_value)) in stored.iter().zip(rebuilt.iter()) {
+        assert_eq!(stored_key, &rebuilt_key);
+        assert_eq!(stored_value, rebuilt_value);
+    }
+}
+
+#[test]
+fn smaller_alphabets_match_byte_tree() {
+    let words = common::read_file_into_words("./assets/inferno.txt")
+        .unwrap_or_else(|_| panic!("Load file failed. Cwd {:?}", env::current_dir()));
+    let mut bytes = RadixTree::new();
+    let mut nibbles: RadixTree<String, usize, StoredKeys, Global, NibbleAlphabet> = RadixTree::with_alphabet_in(Global);
+    let mut bits: RadixTree<String, usize, RebuiltKeys, Global, BitAlphabet> = RadixTree::with_alphabet_in(Global);
+
+    for (idx, word) in words.iter().enumerate() {
+        let expected = bytes.insert(word.clone(), idx);
+
+        assert_eq!(nibbles.insert(word.clone(), idx), expected);
+        assert_eq!(bits.insert(word.clone(), idx), expected);
+    }
+
+    for word in words.iter().step_by(5) {
+        let expected = bytes.remove(word);
+
+        assert_eq!(nibbles.remove(word), expected);
+        assert_eq!(bits.remove(word), expected);
+    }
+
+    assert_eq!(nibbles.validate(), Ok(()));
+    assert_eq!(bits.validate(), Ok(()));
+    assert!(nibbles.stats().max_depth() > bytes.stats().max_depth());
+    assert!(bits.stats().max_depth() > nibbles.stats().max_depth());
+    for word in words.iter() {
+        assert_eq!(nibbles.get(word), bytes.get(word), "{}", word);
+        assert_eq!(bits.get(word), bytes.get(word), "{}", word);
+    }
+
+    let expected = bytes.iter().map(|(key, value)| (key.clone(), *value)).collect::<Vec<_>>();
+    assert_eq!(nibbles.iter().map(|(key, value)| (key.clone(), *value)).collect::<Vec<_>>(), expected);
+    assert_eq!(bits.iter().map(|(key, value)| (key, *value)).collect::<Vec<_>>(), expected);
 }
@@ -0,0 +1,87 @@
+#![cfg(feature = "serde")]
+
+extern crate bincode;
+extern crate serde_json;
+extern crate streamers;
+extern crate unicode_segmentation;
+
+mod common;
+
+use std::env;
+
+use streamers::radix_tree::{I64Key, RadixSet, RadixTree, RebuiltKeys, TupleKey, U64Key};
+
+fn inferno_words() -> Vec<String> {
+    common::read_file_into_words("./assets/inferno.txt").unwrap_or_else(|_| panic!("Load file failed. Cwd {:?}", env::current_dir()))
+}
+
+#[test]
+fn tree_round_trips_through_json() {
+    let mut rax = RadixTree::new();
+    for (idx, word) in inferno_words().into_iter().enumerate() {
+        rax.insert(word, idx);
+    }
+
+    let json = serde_json::to_string(&rax).unwrap();
+    let read: RadixTree<String, usize> = serde_json::from_str(&json).unwrap();
+
+    assert_eq!(read.len(), rax.len());
+    assert_eq!(read.validate(), Ok(()));
+    assert!(read.iter().eq(rax.iter()));
+}
+
+#[test]
+fn rebuilt_tree_and_set_round_trip_through_bincode() {
+    let words = inferno_words();
+    let mut rax: RadixTree<String, usize, RebuiltKeys> = RadixTree::with_rebuilt_keys();
+    for (idx, word) in words.iter().enumerate() {
+        rax.insert(word.clone(), idx);
+    }
+    let set = words.iter().cloned().collect::<RadixSet<_>>();
+
+    let read: RadixTree<String, usize, RebuiltKeys> = bincode::deserialize(&bincode::serialize(&rax).unwrap()).unwrap();
+    assert_eq!(read.validate(), Ok(()));
+    assert!(read.iter().eq(rax.iter()));
+
+    let read: RadixSet<String> = bincode::deserialize(&bincode::serialize(&set).unwrap()).unwrap();
+    assert_eq!(read.len(), set.len());
+    assert!(read.iter().eq(set.iter()));
+}
+
+#[test]
+fn key_wrappers_serialize_as_values() {
+    let rax = vec![(I64Key::new(-3), "a"), (I64Key::new(12), "b")]
+        .into_iter()
+        .collect::<RadixTree<_, _>>();
+
+    let json = serde_json::to_string(&rax).unwrap();
+    assert_eq!(json, r#"{"-3":"a","12":"b"}"#);
+
+    let read: RadixTree<I64Key, String> = serde_json::from_str(&json).unwrap();
+    assert_eq!(read.get(&I64Key::new(-3)).map(String::as_str), Some("a"));
+
+    let events = vec![
+        (TupleKey::new((7u32, String::from("al"), U64Key::new(30))), 1),
+        (TupleKey::new((7u32, String::from("al"), U64Key::new(2))), 2),
+    ].into_iter()
+        .collect::<RadixTree<_, _>>();
+    let read: RadixTree<TupleKey<(u32, String, U64Key)>, i32> =
+        bincode::deserialize(&bincode::serialize(&events).unwrap()).unwrap();
+
+    assert!(read.iter().eq(events.iter()));
+    assert_eq!(read.first().map(|(key, _)| key.parts().2.value()), Some(2));
+}
+
+#[test]
+fn unsorted_input_is_sorted() {
+    let read: RadixTree<String, u32> = serde_json::from_str(r#"{"rom": 1, "r": 2, "romane": 3, "rom": 4}"#).unwrap();
+
+    assert_eq!(read.validate(), Ok(()));
+    assert_eq!(
+        read.iter().map(|(key, &value)| (key.as_str(), value)).collect::<Vec<_>>(),
+        vec![("r", 2), ("rom", 4), ("romane", 3)]
+    );
+
+    let read: RadixSet<String> = serde_json::from_str(r#"["b", "a", "b"]"#).unwrap();
+    assert_eq!(read.len(), 2);
+}
@@ -0,0 +1,69 @@
+#![cfg(feature = "std")]
+
+extern crate streamers;
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use streamers::radix_tree::{RadixTree, RebuiltKeys, TupleKey};
+
+type EventKey = TupleKey<(u32, String, SystemTime)>;
+
+fn events() -> Vec<(u32, String, SystemTime)> {
+    let mut events = Vec::new();
+
+    for tenant in [7u32, 1, 256, 3].iter() {
+        for user in ["carol", "al", "alice", "bob", "al\u{0}x"].iter() {
+            for seconds in [30u64, 1, 600].iter() {
+                events.push((*tenant, user.to_string(), UNIX_EPOCH + Duration::from_secs(*seconds)));
+            }
+        }
+    }
+
+    events
+}
+
+#[test]
+fn prefix_scans_on_leading_parts() {
+    let mut rax = RadixTree::new();
+    let mut expected = events();
+
+    for (idx, event) in expected.iter().enumerate() {
+        rax.insert(EventKey::new(event.clone()), idx);
+    }
+    expected.sort();
+
+    let all = rax.iter().map(|(key, _)| key.parts().clone()).collect::<Vec<_>>();
+    assert_eq!(all, expected);
+
+    let tenant = TupleKey::new((3u32,));
+    let tenant_events = rax.prefix_iter(tenant.as_ref()).map(|(key, _)| key.parts().clone()).collect::<Vec<_>>();
+    assert_eq!(
+        tenant_events,
+        expected.iter().filter(|event| event.0 == 3).cloned().collect::<Vec<_>>()
+    );
+
+    // "al" must not pick up "alice" or "al\0x"
+    let user = TupleKey::new((3u32, String::from("al")));
+    let user_events = rax.prefix_iter(user.as_ref()).map(|(key, _)| key.parts().clone()).collect::<Vec<_>>();
+    assert_eq!(
+        user_events,
+        expected.iter().filter(|event| event.0 == 3 && event.1 == "al").cloned().collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn rebuilt_tuple_keys_decode() {
+    let mut rax: RadixTree<EventKey, usize, RebuiltKeys> = RadixTree::with_rebuilt_keys();
+    let mut expected = events();
+
+    for (idx, event) in expected.iter().enumerate() {
+        rax.insert(EventKey::new(event.clone()), idx);
+    }
+    expected.sort();
+
+    let all = rax.iter().map(|(key, _)| key.into_parts()).collect::<Vec<_>>();
+    assert_eq!(all, expected);
+
+    let user = TupleKey::new((256u32, String::from("bob")));
+    assert_eq!(rax.prefix_iter(user.as_ref()).count(), 3);
+}